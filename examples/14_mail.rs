@@ -1,9 +1,9 @@
-use flyer::{mail::Mail, server};
+use flyer::{mail::{Mail, smtp::SmtpMailer}, server};
 use uuid::Uuid;
 
 fn main() {
     let server = server("127.0.0.1", 9999)
-        .mailer("127.0.0.1".to_string(), 5555, "".to_string(), "".to_string(), false);
+        .mailer("default", SmtpMailer::unencrypted("127.0.0.1").port(5555));
     
     server.router().get("/send-mail", async |_req, res| {
         // Send to single email
@@ -11,6 +11,7 @@ fn main() {
             .from("no-reply@test.com".to_string(), Some("no-reply"))
             .html(format!("<h1>Token: {}</h1>", Uuid::new_v4()))
             .send("user@test.com".to_string(), Some("User"))
+            .await
             .unwrap();
 
         return res.html("<h1>Email sent!</h1>")
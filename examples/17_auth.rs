@@ -0,0 +1,41 @@
+use flyer::{
+    auth::{api_key::{ApiKey, ApiKeyAuth}, basic::BasicAuth},
+    request::Request,
+    response::Response,
+    server,
+};
+
+pub async fn dashboard(req: Request, res: Response) -> Response {
+    let user = req.identity().map(|i| i.id.clone()).unwrap_or_default();
+    res.html(format!("<h1>Welcome {}</h1>", user).as_str())
+}
+
+pub async fn reports(req: Request, res: Response) -> Response {
+    let client = req.identity().map(|i| i.id.clone()).unwrap_or_default();
+    res.json(&serde_json::json!({ "client": client, "reports": [] }))
+}
+
+fn main() {
+    let server = server("127.0.0.1", 9999);
+
+    // Admin pages protected with HTTP Basic authentication
+    server.router().group("admin", |router| {
+        router.get("/", dashboard);
+    }).middleware(BasicAuth::credentials("Admin", "admin", "secret").middleware());
+
+    // Machine clients authenticate with an API key from a header or query parameter
+    server.router().group("api", |router| {
+        router.get("reports", reports).middleware(
+            ApiKeyAuth::new()
+                .header("X-Api-Key")
+                .query("api_key")
+                .key(ApiKey::new("reporting-service", "my-api-key").scopes(vec!["reports:read"]))
+                .require(vec!["reports:read"])
+                .middleware()
+        );
+    });
+
+    print!("\r\n\r\nRunning server: {}\r\n\r\n", server.address());
+
+    server.listen();
+}
@@ -0,0 +1,269 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    Data,
+    DeriveInput,
+    Expr,
+    ExprLit,
+    ExprUnary,
+    Fields,
+    GenericArgument,
+    Lit,
+    LitStr,
+    PathArguments,
+    Token,
+    Type,
+    UnOp,
+    ext::IdentExt,
+    meta::ParseNestedMeta,
+    parse_macro_input,
+    punctuated::Punctuated,
+    spanned::Spanned,
+    token::Paren,
+};
+
+mod rules;
+
+use rules::{NEEDS_ARGUMENTS, RULES};
+
+/// Implements `flyer::validation::Validate` from `#[validate(...)]` field attributes:
+///
+/// ```ignore
+/// #[derive(Deserialize, Validate)]
+/// struct Signup {
+///     #[validate(required, email, max = 255)]
+///     email: String,
+///     #[validate(required, between(18, 130))]
+///     age: u32,
+///     #[validate(each(string, max = 20))]
+///     tags: Vec<String>,
+///     #[validate(nested)]
+///     address: Address,
+///     #[validate(rule = "postcode:za")]
+///     postcode: Option<String>,
+/// }
+/// ```
+///
+/// Built-in rule names are checked at compile time; rules added with `Rules::add` are named
+/// with `rule = "..."`. `Option` fields are `nullable` unless they are `required`.
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn derive_validate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+struct FieldRules {
+    name: String,
+    specs: Vec<String>,
+    each: Vec<String>,
+    nested: Option<(String, Type)>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(input.ident.span(), "Validate can only be derived for structs"));
+    };
+
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(input.ident.span(), "Validate needs a struct with named fields"));
+    };
+
+    let mut statements = Vec::new();
+
+    for field in &fields.named {
+        let rules = parse_field(field)?;
+        let name = &rules.name;
+
+        if !rules.specs.is_empty() {
+            let specs = &rules.specs;
+            statements.push(quote! { rules.rule(#name, vec![#(#specs),*]); });
+        }
+
+        if !rules.each.is_empty() {
+            let each_name = format!("{}.*", name);
+            let each = &rules.each;
+            statements.push(quote! { rules.rule(#each_name, vec![#(#each),*]); });
+        }
+
+        if let Some((prefix, ty)) = &rules.nested {
+            statements.push(quote! { rules.nest(#prefix, <#ty as ::flyer::validation::Validate>::rules()); });
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::flyer::validation::Validate for #ident #ty_generics #where_clause {
+            fn rules() -> ::flyer::validation::Rules {
+                let mut rules = ::flyer::validation::Rules::new();
+                #(#statements)*
+                rules
+            }
+        }
+    })
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<FieldRules> {
+    let ident = field.ident.as_ref().expect("named field");
+    let mut rules = FieldRules {
+        name: serde_rename(field)?.unwrap_or_else(|| ident.unraw().to_string()),
+        specs: Vec::new(),
+        each: Vec::new(),
+        nested: None,
+    };
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("validate")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("nested") {
+                let ty = wrapped(&field.ty, "Option").unwrap_or(&field.ty);
+
+                rules.nested = Some(match wrapped(ty, "Vec") {
+                    Some(item) => (format!("{}.*", rules.name), item.clone()),
+                    None => (rules.name.clone(), ty.clone()),
+                });
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("each") {
+                return meta.parse_nested_meta(|item| {
+                    rules.each.push(rule_spec(&item)?);
+                    Ok(())
+                });
+            }
+
+            rules.specs.push(rule_spec(&meta)?);
+            Ok(())
+        })?;
+    }
+
+    let required = rules.specs.iter().any(|spec| spec.starts_with("required"));
+    let nullable = rules.specs.iter().any(|spec| spec == "nullable");
+
+    if wrapped(&field.ty, "Option").is_some() && !rules.specs.is_empty() && !required && !nullable {
+        rules.specs.insert(0, String::from("nullable"));
+    }
+
+    Ok(rules)
+}
+
+/// Turns `max = 255`, `between(1, 10)` or `email` into the `max:255` style spec `Rules` takes.
+fn rule_spec(meta: &ParseNestedMeta) -> syn::Result<String> {
+    let Some(ident) = meta.path.get_ident() else {
+        return Err(meta.error("expected a rule name"));
+    };
+
+    let name = ident.unraw().to_string();
+
+    if name == "rule" {
+        return Ok(meta.value()?.parse::<LitStr>()?.value());
+    }
+
+    if !RULES.contains(&name.as_str()) {
+        return Err(meta.error(format!("unknown validation rule `{}`; use `rule = \"...\"` for custom rules", name)));
+    }
+
+    if meta.input.peek(Token![=]) {
+        let value: Expr = meta.value()?.parse()?;
+        return Ok(format!("{}:{}", name, literal(&value)?));
+    }
+
+    if meta.input.peek(Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+
+        let args = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?
+            .iter()
+            .map(literal)
+            .collect::<syn::Result<Vec<String>>>()?;
+
+        return Ok(format!("{}:{}", name, args.join(",")));
+    }
+
+    if NEEDS_ARGUMENTS.contains(&name.as_str()) {
+        return Err(meta.error(format!("rule `{}` needs an argument, e.g. `{} = ...` or `{}(...)`", name, name, name)));
+    }
+
+    Ok(name)
+}
+
+fn literal(expr: &Expr) -> syn::Result<String> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Str(s), .. }) => Ok(s.value()),
+        Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => Ok(int.base10_digits().to_string()),
+        Expr::Lit(ExprLit { lit: Lit::Float(float), .. }) => Ok(float.base10_digits().to_string()),
+        Expr::Lit(ExprLit { lit: Lit::Bool(b), .. }) => Ok(b.value.to_string()),
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => Ok(format!("-{}", literal(expr)?)),
+        _ => Err(syn::Error::new(expr.span(), "expected a literal")),
+    }
+}
+
+/// The name serde deserializes the field from, when it is renamed.
+fn serde_rename(field: &syn::Field) -> syn::Result<Option<String>> {
+    let mut rename = None;
+
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident("rename") {
+                return skip(&meta);
+            }
+
+            if meta.input.peek(Token![=]) {
+                rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                return Ok(());
+            }
+
+            meta.parse_nested_meta(|inner| {
+                if inner.path.is_ident("deserialize") {
+                    rename = Some(inner.value()?.parse::<LitStr>()?.value());
+                    return Ok(());
+                }
+
+                skip(&inner)
+            })
+        })?;
+    }
+
+    Ok(rename)
+}
+
+/// Consumes a serde option this macro does not care about.
+fn skip(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(Paren) {
+        let content;
+        syn::parenthesized!(content in meta.input);
+        content.parse::<TokenStream2>()?;
+    }
+
+    Ok(())
+}
+
+/// `T` when `ty` is spelled `Wrapper<T>`.
+fn wrapped<'t>(ty: &'t Type, wrapper: &str) -> Option<&'t Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+
+    if segment.ident != wrapper {
+        return None;
+    }
+
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+
+    match args.args.first()? {
+        GenericArgument::Type(inner) => Some(inner),
+        _ => None,
+    }
+}
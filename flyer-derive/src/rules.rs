@@ -0,0 +1,27 @@
+// Shared with the tests of `flyer::validation`, which check these lists against its rule
+// registry, so a rule added there without being listed here fails them.
+
+/// Rules registered by `flyer::validation`.
+pub(crate) const RULES: &[&str] = &[
+    "accepted", "accepted_if", "active_url", "after", "after_or_equal", "alpha", "alpha_dash",
+    "alpha_numeric", "alpha_num", "array", "ascii", "before", "before_or_equal", "between",
+    "boolean", "confirmed", "date", "date_equals", "date_format", "decimal", "declined",
+    "declined_if", "different", "digits", "digits_between", "distinct", "doesnt_start_with",
+    "doesnt_end_with", "email", "ends_with", "exists", "extensions", "file", "filled", "gt", "gte",
+    "hex_color", "image", "in", "integer", "ip", "ipv4", "ipv6", "json", "lt", "lte", "lowercase", "mac_address",
+    "max", "max_digits", "mimetypes", "mimes", "min", "min_digits", "missing", "missing_if",
+    "missing_unless", "multiple_of", "not_in", "not_regex", "nullable", "numeric", "present",
+    "present_if", "present_unless", "prohibited", "prohibited_if", "prohibited_unless",
+    "prohibited_with", "prohibited_with_all", "regex", "required", "required_if",
+    "required_if_accepted", "required_unless", "required_with", "required_with_all",
+    "required_without", "required_without_all", "same", "size", "starts_with", "string",
+    "unique", "uppercase", "url", "ulid", "uuid",
+];
+
+/// Rules that do nothing without an argument.
+pub(crate) const NEEDS_ARGUMENTS: &[&str] = &[
+    "after", "after_or_equal", "before", "before_or_equal", "between", "date_equals", "date_format",
+    "different", "digits", "digits_between", "gt", "gte", "in", "lt", "lte", "max", "max_digits",
+    "mimetypes", "mimes", "min", "min_digits", "multiple_of", "not_in", "not_regex", "regex",
+    "same", "size", "unique", "exists",
+];
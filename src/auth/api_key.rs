@@ -0,0 +1,212 @@
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use crate::{
+    auth::{Identity, constant_time_eq, reject},
+    request::Request,
+    response::{HTTP_FORBIDDEN, HTTP_UNAUTHORIZED, Response},
+    routing::next::Next,
+};
+
+#[derive(Clone, Debug)]
+pub struct ApiKey {
+    pub name: String,
+    pub key: String,
+    pub scopes: Vec<String>,
+}
+
+impl ApiKey {
+    pub fn new(name: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            key: key.into(),
+            scopes: Vec::new(),
+        }
+    }
+
+    pub fn scopes(mut self, scopes: Vec<&str>) -> Self {
+        self.scopes = scopes.into_iter().map(String::from).collect();
+        self
+    }
+}
+
+pub struct ApiKeyAuth {
+    header: Option<String>,
+    query: Option<String>,
+    keys: Vec<ApiKey>,
+    required_scopes: Vec<String>,
+}
+
+impl Default for ApiKeyAuth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ApiKeyAuth {
+    pub fn new() -> Self {
+        Self {
+            header: Some("x-api-key".into()),
+            query: None,
+            keys: Vec::new(),
+            required_scopes: Vec::new(),
+        }
+    }
+
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.header = Some(name.into().to_lowercase());
+        self
+    }
+
+    pub fn query(mut self, name: impl Into<String>) -> Self {
+        self.query = Some(name.into());
+        self
+    }
+
+    pub fn key(mut self, key: ApiKey) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    pub fn keys(mut self, keys: Vec<ApiKey>) -> Self {
+        self.keys.extend(keys);
+        self
+    }
+
+    pub fn require(mut self, scopes: Vec<&str>) -> Self {
+        self.required_scopes = scopes.into_iter().map(String::from).collect();
+        self
+    }
+
+    pub fn middleware(self) -> impl Fn(Request, Response, Next) -> BoxFuture<'static, Response> + Send + Sync + 'static {
+        let auth = Arc::new(self);
+
+        move |req, res, next| {
+            let auth = Arc::clone(&auth);
+
+            Box::pin(async move { auth.handle(req, res, next).await })
+        }
+    }
+
+    pub async fn handle(&self, mut req: Request, res: Response, next: Next) -> Response {
+        let Some(candidate) = self.extract(&req) else {
+            return reject(res, HTTP_UNAUTHORIZED, "Unauthorized", req.is_json());
+        };
+
+        let Some(key) = self.find(&candidate) else {
+            return reject(res, HTTP_UNAUTHORIZED, "Unauthorized", req.is_json());
+        };
+
+        let identity = Identity::new(key.name.clone(), key.scopes.clone());
+
+        if !self.required_scopes.iter().all(|scope| identity.has_scope(scope)) {
+            return reject(res, HTTP_FORBIDDEN, "Forbidden", req.is_json());
+        }
+
+        req.identity = Some(identity);
+
+        next.handle(req, res)
+    }
+
+    fn extract(&self, req: &Request) -> Option<String> {
+        if let Some(header) = &self.header {
+            let value = req.header(header);
+
+            if !value.is_empty() {
+                return Some(value);
+            }
+        }
+
+        if let Some(query) = &self.query {
+            let value = req.query(query);
+
+            if !value.is_empty() {
+                return Some(value);
+            }
+        }
+
+        None
+    }
+
+    fn find(&self, candidate: &str) -> Option<&ApiKey> {
+        let mut found = None;
+
+        // Every key is compared so the lookup time does not reveal which one matched.
+        for key in &self.keys {
+            if constant_time_eq(candidate.as_bytes(), key.key.as_bytes()) && found.is_none() {
+                found = Some(key);
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn auth() -> ApiKeyAuth {
+        ApiKeyAuth::new()
+            .query("api_key")
+            .key(ApiKey::new("billing", "key-one").scopes(vec!["invoices:read"]))
+            .key(ApiKey::new("reports", "key-two"))
+    }
+
+    async fn call(auth: &ApiKeyAuth, header: Option<&str>, query: Option<&str>, json: bool) -> Response {
+        let mut req = Request::fake("GET", "/invoices");
+
+        if let Some(key) = header {
+            req.headers.insert("x-api-key".to_string(), key.to_string());
+        }
+
+        if let Some(key) = query {
+            req.queries.insert("api_key".to_string(), key.to_string());
+        }
+
+        if json {
+            req.headers.insert("content-type".to_string(), "application/json".to_string());
+        }
+
+        auth.handle(req, Response::new(), Next::new()).await
+    }
+
+    #[tokio::test]
+    async fn matching_key_sets_the_identity() {
+        let mut res = call(&auth(), Some("key-one"), None, false).await;
+        let req = res.request();
+        let identity = req.identity().unwrap();
+
+        assert_eq!(identity.id, "billing");
+        assert!(identity.has_scope("invoices:read"));
+    }
+
+    #[tokio::test]
+    async fn key_may_come_from_the_query() {
+        let mut res = call(&auth(), None, Some("key-two"), false).await;
+
+        assert_eq!(res.request().identity().unwrap().id, "reports");
+    }
+
+    #[tokio::test]
+    async fn unknown_or_missing_key_is_unauthorized() {
+        for header in [Some("key-three"), Some("key-on"), None] {
+            let res = call(&auth(), header, None, false).await;
+
+            assert!(!res.is_next());
+            assert_eq!(res.status_code, HTTP_UNAUTHORIZED);
+            assert_eq!(res.content.as_ref(), b"<h1>Unauthorized</h1>");
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_scope_is_forbidden() {
+        let auth = auth().require(vec!["invoices:write"]);
+        let res = call(&auth, Some("key-one"), None, true).await;
+
+        assert!(!res.is_next());
+        assert_eq!(res.status_code, HTTP_FORBIDDEN);
+        assert_eq!(res.content.as_ref(), br#"{"message":"Forbidden"}"#);
+    }
+}
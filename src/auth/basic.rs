@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use base64::{Engine, engine::general_purpose};
+use futures::future::BoxFuture;
+
+use crate::{
+    auth::{Identity, constant_time_eq, reject},
+    request::Request,
+    response::{HTTP_UNAUTHORIZED, Response},
+    routing::next::Next,
+};
+
+pub type CredentialVerifier = dyn Fn(String, String) -> BoxFuture<'static, bool> + Send + Sync;
+
+pub struct BasicAuth {
+    realm: String,
+    verifier: Arc<CredentialVerifier>,
+}
+
+impl BasicAuth {
+    pub fn new<C, Fut>(realm: impl Into<String>, verifier: C) -> Self
+    where
+        C: Fn(String, String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = bool> + Send + 'static,
+    {
+        Self {
+            realm: realm.into(),
+            verifier: Arc::new(move |username, password| Box::pin(verifier(username, password))),
+        }
+    }
+
+    pub fn credentials(realm: impl Into<String>, username: impl Into<String>, password: impl Into<String>) -> Self {
+        let username = Arc::new(username.into());
+        let password = Arc::new(password.into());
+
+        Self::new(realm, move |user, pass| {
+            let username = Arc::clone(&username);
+            let password = Arc::clone(&password);
+
+            async move {
+                let user_ok = constant_time_eq(user.as_bytes(), username.as_bytes());
+                let pass_ok = constant_time_eq(pass.as_bytes(), password.as_bytes());
+
+                user_ok & pass_ok
+            }
+        })
+    }
+
+    pub fn middleware(self) -> impl Fn(Request, Response, Next) -> BoxFuture<'static, Response> + Send + Sync + 'static {
+        let auth = Arc::new(self);
+
+        move |req, res, next| {
+            let auth = Arc::clone(&auth);
+
+            Box::pin(async move { auth.handle(req, res, next).await })
+        }
+    }
+
+    pub async fn handle(&self, mut req: Request, res: Response, next: Next) -> Response {
+        let Some((username, password)) = Self::parse(&req.header("authorization")) else {
+            return self.challenge(&req, res);
+        };
+
+        if !(self.verifier)(username.clone(), password).await {
+            return self.challenge(&req, res);
+        }
+
+        req.identity = Some(Identity::new(username, Vec::new()));
+
+        next.handle(req, res)
+    }
+
+    fn challenge(&self, req: &Request, res: Response) -> Response {
+        let realm = self.realm.replace('"', "\\\"");
+
+        reject(res, HTTP_UNAUTHORIZED, "Unauthorized", req.is_json())
+            .set_header("WWW-Authenticate", format!("Basic realm=\"{}\", charset=\"UTF-8\"", realm))
+    }
+
+    fn parse(header: &str) -> Option<(String, String)> {
+        let (scheme, encoded) = header.trim().split_once(' ')?;
+
+        if !scheme.eq_ignore_ascii_case("basic") {
+            return None;
+        }
+
+        let decoded = general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (username, password) = decoded.split_once(':')?;
+
+        Some((username.to_string(), password.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn call(auth: &BasicAuth, authorization: Option<&str>, json: bool) -> Response {
+        let mut req = Request::fake("GET", "/admin");
+
+        if let Some(authorization) = authorization {
+            req.headers.insert("authorization".to_string(), authorization.to_string());
+        }
+
+        if json {
+            req.headers.insert("content-type".to_string(), "application/json".to_string());
+        }
+
+        auth.handle(req, Response::new(), Next::new()).await
+    }
+
+    fn basic(credentials: &str) -> String {
+        format!("Basic {}", general_purpose::STANDARD.encode(credentials))
+    }
+
+    #[tokio::test]
+    async fn valid_credentials_set_the_identity() {
+        let auth = BasicAuth::credentials("Admin", "jane", "s3cr:et");
+        let mut res = call(&auth, Some(&basic("jane:s3cr:et")), false).await;
+
+        assert!(res.is_next());
+        assert_eq!(res.request().identity().unwrap().id, "jane");
+    }
+
+    #[tokio::test]
+    async fn verifier_decides_asynchronously() {
+        let auth = BasicAuth::new("Admin", |user, pass| async move { user == "ann" && pass.len() > 3 });
+
+        assert!(call(&auth, Some(&basic("ann:long")), false).await.is_next());
+        assert!(!call(&auth, Some(&basic("ann:abc")), false).await.is_next());
+    }
+
+    #[tokio::test]
+    async fn wrong_or_malformed_credentials_are_challenged() {
+        let auth = BasicAuth::credentials("Admin \"area\"", "jane", "secret");
+
+        for authorization in [Some(basic("jane:wrong")), Some(basic("jane")), Some("Bearer abc".to_string()), Some("Basic !!".to_string()), None] {
+            let res = call(&auth, authorization.as_deref(), false).await;
+
+            assert!(!res.is_next());
+            assert_eq!(res.status_code, HTTP_UNAUTHORIZED);
+            assert_eq!(res.content.as_ref(), b"<h1>Unauthorized</h1>");
+            assert_eq!(res.header("WWW-Authenticate"), r#"Basic realm="Admin \"area\"", charset="UTF-8""#);
+        }
+    }
+
+    #[tokio::test]
+    async fn json_clients_get_a_json_challenge() {
+        let auth = BasicAuth::credentials("Admin", "jane", "secret");
+        let res = call(&auth, None, true).await;
+
+        assert_eq!(res.status_code, HTTP_UNAUTHORIZED);
+        assert_eq!(res.content.as_ref(), br#"{"message":"Unauthorized"}"#);
+    }
+
+    #[test]
+    fn scheme_is_case_insensitive() {
+        assert_eq!(BasicAuth::parse(&basic("a:b").replace("Basic", "bAsIc")), Some(("a".to_string(), "b".to_string())));
+    }
+}
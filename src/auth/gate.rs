@@ -0,0 +1,231 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    sync::{Arc, LazyLock},
+};
+
+use arc_swap::ArcSwap;
+use futures::future::BoxFuture;
+
+use crate::{
+    auth::{Identity, reject},
+    request::Request,
+    response::{HTTP_FORBIDDEN, HTTP_UNAUTHORIZED, Response},
+    routing::next::Next,
+};
+
+type Ability = dyn Fn(&Identity, &dyn Any) -> bool + Send + Sync;
+
+type PolicyCheck = dyn Fn(&Identity, &str, &dyn Any) -> bool + Send + Sync;
+
+type Abilities = HashMap<(String, TypeId), Arc<Ability>>;
+
+pub trait Policy<T: 'static>: Send + Sync {
+    fn check(&self, ability: &str, identity: &Identity, resource: &T) -> bool;
+}
+
+static ABILITIES: LazyLock<ArcSwap<Abilities>> =
+    LazyLock::new(|| ArcSwap::from_pointee(HashMap::new()));
+
+static POLICIES: LazyLock<ArcSwap<HashMap<TypeId, Arc<PolicyCheck>>>> =
+    LazyLock::new(|| ArcSwap::from_pointee(HashMap::new()));
+
+pub struct Gate;
+
+impl Gate {
+    /// Registers an ability which does not need a resource, e.g. `edit-settings`.
+    pub fn define<F>(ability: impl Into<String>, callback: F)
+    where
+        F: Fn(&Identity) -> bool + Send + Sync + 'static,
+    {
+        Self::define_for::<(), _>(ability, move |identity, _| callback(identity));
+    }
+
+    /// Registers an ability checked against a resource of type `T`, e.g. `update` on a `Post`.
+    pub fn define_for<T: 'static, F>(ability: impl Into<String>, callback: F)
+    where
+        F: Fn(&Identity, &T) -> bool + Send + Sync + 'static,
+    {
+        let key = (ability.into(), TypeId::of::<T>());
+        let callback: Arc<Ability> = Arc::new(move |identity, resource| {
+            resource
+                .downcast_ref::<T>()
+                .map(|resource| callback(identity, resource))
+                .unwrap_or(false)
+        });
+
+        ABILITIES.rcu(|current| {
+            let mut map = (**current).clone();
+            map.insert(key.clone(), Arc::clone(&callback));
+            map
+        });
+    }
+
+    /// Registers a policy answering every ability for resources of type `T`.
+    pub fn policy<T: 'static, P: Policy<T> + 'static>(policy: P) {
+        let policy = Arc::new(policy);
+        let callback: Arc<PolicyCheck> = Arc::new(move |identity, ability, resource| {
+            resource
+                .downcast_ref::<T>()
+                .map(|resource| policy.check(ability, identity, resource))
+                .unwrap_or(false)
+        });
+
+        POLICIES.rcu(|current| {
+            let mut map = (**current).clone();
+            map.insert(TypeId::of::<T>(), Arc::clone(&callback));
+            map
+        });
+    }
+
+    pub fn allows<T: 'static>(identity: &Identity, ability: &str, resource: &T) -> bool {
+        let key = (ability.to_string(), TypeId::of::<T>());
+
+        if let Some(callback) = ABILITIES.load().get(&key) {
+            return callback(identity, resource);
+        }
+
+        if let Some(callback) = POLICIES.load().get(&TypeId::of::<T>()) {
+            return callback(identity, ability, resource);
+        }
+
+        false
+    }
+
+    #[inline]
+    pub fn denies<T: 'static>(identity: &Identity, ability: &str, resource: &T) -> bool {
+        !Self::allows(identity, ability, resource)
+    }
+
+    /// Builds a middleware from a gate spec: `can:ability`, `role:name` or `scope:name`. Any
+    /// other spec denies every request, and fails a debug assertion when the route is built.
+    pub fn middleware(spec: impl Into<String>) -> impl Fn(Request, Response, Next) -> BoxFuture<'static, Response> + Send + Sync + 'static {
+        let spec = spec.into();
+
+        debug_assert!(
+            matches!(spec.split_once(':'), Some(("can" | "role" | "scope", _))),
+            "The gate `{}` is not valid, expected `can:`, `role:` or `scope:`",
+            spec
+        );
+
+        let spec = Arc::new(spec);
+
+        move |req, res, next| {
+            let spec = Arc::clone(&spec);
+
+            Box::pin(async move { Self::handle(&spec, req, res, next) })
+        }
+    }
+
+    fn handle(spec: &str, req: Request, res: Response, next: Next) -> Response {
+        let Some(identity) = req.identity() else {
+            return reject(res, HTTP_UNAUTHORIZED, "Unauthorized", req.is_json());
+        };
+
+        let allowed = match spec.split_once(':') {
+            Some(("can", ability)) => Self::allows(identity, ability, &()),
+            Some(("role", roles)) => roles.split(',').any(|role| identity.has_role(role.trim())),
+            Some(("scope", scopes)) => scopes.split(',').all(|scope| identity.has_scope(scope.trim())),
+            _ => false,
+        };
+
+        if !allowed {
+            return reject(res, HTTP_FORBIDDEN, "Forbidden", req.is_json());
+        }
+
+        next.handle(req, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Post {
+        author: String,
+    }
+
+    struct Comment {
+        author: String,
+    }
+
+    struct CommentPolicy;
+
+    impl Policy<Comment> for CommentPolicy {
+        fn check(&self, ability: &str, identity: &Identity, comment: &Comment) -> bool {
+            match ability {
+                "delete" => comment.author == identity.id || identity.has_role("moderator"),
+                _ => false,
+            }
+        }
+    }
+
+    fn jane() -> Identity {
+        Identity::new("jane", vec!["posts:read".to_string()]).roles(vec!["editor"])
+    }
+
+    async fn through(spec: &str, identity: Option<Identity>, json: bool) -> Response {
+        let mut req = Request::fake("GET", "/admin");
+
+        if let Some(identity) = identity {
+            req.set_identity(identity);
+        }
+
+        if json {
+            req.headers.insert("content-type".to_string(), "application/json".to_string());
+        }
+
+        Gate::middleware(spec)(req, Response::new(), Next::new()).await
+    }
+
+    #[test]
+    fn abilities_are_checked_by_resource_type() {
+        Gate::define("gate-test-settings", |identity| identity.has_role("editor"));
+        Gate::define_for::<Post, _>("gate-test-update", |identity, post| post.author == identity.id);
+
+        let own = Post { author: "jane".to_string() };
+        let other = Post { author: "john".to_string() };
+
+        assert!(Gate::allows(&jane(), "gate-test-settings", &()));
+        assert!(Gate::denies(&Identity::default(), "gate-test-settings", &()));
+        assert!(Gate::allows(&jane(), "gate-test-update", &own));
+        assert!(Gate::denies(&jane(), "gate-test-update", &other));
+        assert!(Gate::denies(&jane(), "gate-test-update", &()));
+        assert!(Gate::denies(&jane(), "gate-test-unknown", &()));
+    }
+
+    #[test]
+    fn policies_answer_every_ability_of_their_type() {
+        Gate::policy(CommentPolicy);
+
+        let comment = Comment { author: "john".to_string() };
+        let moderator = Identity::new("ann", Vec::new()).roles(vec!["moderator"]);
+
+        assert!(Gate::denies(&jane(), "delete", &comment));
+        assert!(Gate::allows(&moderator, "delete", &comment));
+        assert!(Gate::denies(&moderator, "update", &comment));
+    }
+
+    #[tokio::test]
+    async fn middleware_checks_abilities_roles_and_scopes() {
+        Gate::define("gate-test-middleware", |identity| identity.id == "jane");
+
+        assert!(through("can:gate-test-middleware", Some(jane()), false).await.is_next());
+        assert!(through("role:admin, editor", Some(jane()), false).await.is_next());
+        assert!(through("scope:posts:read", Some(jane()), false).await.is_next());
+
+        let denied = through("scope:posts:read,posts:write", Some(jane()), false).await;
+        assert!(!denied.is_next());
+        assert_eq!(denied.status_code, HTTP_FORBIDDEN);
+        assert!(String::from_utf8_lossy(&denied.content).contains("<h1>Forbidden</h1>"));
+    }
+
+    #[tokio::test]
+    async fn middleware_answers_json_clients_with_json() {
+        let res = through("role:admin", None, true).await;
+
+        assert!(!res.is_next());
+        assert_eq!(res.status_code, HTTP_UNAUTHORIZED);
+        assert_eq!(res.content.as_ref(), br#"{"message":"Unauthorized"}"#);
+    }
+}
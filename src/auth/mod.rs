@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::response::Response;
+
+pub mod api_key;
+pub mod basic;
+pub mod gate;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Identity {
+    pub id: String,
+    pub roles: Vec<String>,
+    pub scopes: Vec<String>,
+}
+
+impl Identity {
+    pub fn new(id: impl Into<String>, scopes: Vec<String>) -> Self {
+        Self {
+            id: id.into(),
+            roles: Vec::new(),
+            scopes,
+        }
+    }
+
+    pub fn roles(mut self, roles: Vec<&str>) -> Self {
+        self.roles = roles.into_iter().map(String::from).collect();
+        self
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles
+            .iter()
+            .any(|r| r == role)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|s| s == "*" || s == scope)
+    }
+}
+
+/// Compares two byte slices in time independent of where they differ.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let mut diff = a.len() ^ b.len();
+
+    for i in 0..a.len().max(b.len()) {
+        let x = a.get(i).copied().unwrap_or(0);
+        let y = b.get(i).copied().unwrap_or(0);
+
+        diff |= (x ^ y) as usize;
+    }
+
+    diff == 0
+}
+
+pub(crate) fn reject(res: Response, status_code: u16, message: &str, is_json: bool) -> Response {
+    if is_json {
+        return res
+            .status_code(status_code)
+            .json(&serde_json::json!({ "message": message }));
+    }
+
+    res
+        .status_code(status_code)
+        .html(format!("<h1>{}</h1>", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_time_eq_compares_whole_slices() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+        assert!(!constant_time_eq(b"secret", b"secre"));
+        assert!(!constant_time_eq(b"", b"\0"));
+    }
+
+    #[test]
+    fn wildcard_scope_grants_everything() {
+        let identity = Identity::new("jane", vec!["*".to_string()]);
+
+        assert!(identity.has_scope("posts:write"));
+        assert!(!Identity::new("john", vec!["posts:read".to_string()]).has_scope("posts:write"));
+    }
+}
@@ -3,7 +3,7 @@ use std::time::Duration;
 use cookie::time::OffsetDateTime;
 use serde::Serialize;
 
-use crate::cookies::SameSite;
+use crate::cookies::{Protection, SameSite};
 
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Cookie {
@@ -15,7 +15,8 @@ pub struct Cookie {
     pub(crate) path: Option<String>,
     pub(crate) secure: Option<bool>,
     pub(crate) http_only: Option<bool>,
-    pub(crate) same_site: Option<SameSite>
+    pub(crate) same_site: Option<SameSite>,
+    pub(crate) protection: Protection,
 }
 
 impl Cookie {
@@ -29,7 +30,8 @@ impl Cookie {
             path: None,
             secure: None,
             http_only: None,
-            same_site: None
+            same_site: None,
+            protection: Protection::Plain,
         }
     }
 
@@ -87,6 +89,22 @@ impl Cookie {
         return self;
     }
 
+    /// Checks the rules browsers enforce for `__Secure-` and `__Host-` prefixed names, which
+    /// otherwise silently drop the cookie.
+    pub fn validate_prefix(&self) -> Result<(), String> {
+        let secure = self.secure.unwrap_or(false);
+
+        if self.name.starts_with("__Host-") {
+            if !secure || self.path.as_deref() != Some("/") || self.domain.is_some() {
+                return Err(format!("Cookie '{}' must be Secure, have Path=/ and no Domain", self.name));
+            }
+        } else if self.name.starts_with("__Secure-") && !secure {
+            return Err(format!("Cookie '{}' must be Secure", self.name));
+        }
+
+        Ok(())
+    }
+
     pub fn parse(&self) -> String {
         let mut cookie = cookie::Cookie::new(self.name.to_string(), self.value.to_string());
 
@@ -1,18 +1,24 @@
-use std::time::Duration;
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
+use anyhow::{Result, anyhow, bail};
 use serde::Serialize;
 
 use crate::{
     cookies::cookie::Cookie,
+    error::Error,
     hooks::Hook,
     request::Request,
     response::Response,
     routing::next::Next,
-    utils::{Values, http::Headers}
+    utils::{Values, crypto::{self, Key}}
 };
 
 pub mod cookie;
 
+const SIGNING_CONTEXT: &str = "flyer.cookies.signed";
+
+const ENCRYPTION_CONTEXT: &str = "flyer.cookies.encrypted";
+
 #[derive(Clone, Debug, Copy, PartialEq, Eq, Hash, Serialize)]
 pub enum SameSite {
     Strict,
@@ -20,33 +26,156 @@ pub enum SameSite {
     None,
 }
 
+/// How a cookie value is protected when it is sent to the client.
+#[derive(Clone, Debug, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub enum Protection {
+    #[default]
+    Plain,
+    /// Readable by the client, but any change is detected.
+    Signed,
+    /// Neither readable nor changeable by the client.
+    Encrypted,
+}
+
+/// Keys derived from the cookie secrets; the first pair seals new cookies, the rest only open old ones.
+#[derive(Clone, Default)]
+struct CookieKeys {
+    signing: Vec<Key>,
+    encryption: Vec<Key>,
+    empty_secret: bool,
+}
+
+impl Debug for CookieKeys {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CookieKeys([redacted])")
+    }
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct Cookies {
     inner: Values,
+    #[serde(skip)]
+    signed: Values,
+    #[serde(skip)]
+    encrypted: Values,
     outer: Vec<Cookie>,
+    #[serde(skip)]
+    keys: Arc<CookieKeys>,
 }
 
 impl Cookies {
     pub fn new() -> Self {
         return Self::default();
     }
+
+    /// Secret used to sign and encrypt cookies set with `set_signed`/`set_encrypted`.
+    pub fn key(mut self, secret: impl Into<String>) -> Self {
+        let secret = secret.into();
+        let keys = Arc::make_mut(&mut self.keys);
+
+        keys.empty_secret |= secret.trim().is_empty();
+        keys.signing.insert(0, crypto::derive_key(&secret, SIGNING_CONTEXT));
+        keys.encryption.insert(0, crypto::derive_key(&secret, ENCRYPTION_CONTEXT));
+
+        self
+    }
+
+    /// Secrets used before the current key. Cookies sealed with them are still accepted, so
+    /// rotating the key does not invalidate cookies clients already hold.
+    pub fn previous_keys<T: Into<String>>(mut self, secrets: Vec<T>) -> Self {
+        let keys = Arc::make_mut(&mut self.keys);
+
+        for secret in secrets {
+            let secret = secret.into();
+
+            keys.empty_secret |= secret.trim().is_empty();
+            keys.signing.push(crypto::derive_key(&secret, SIGNING_CONTEXT));
+            keys.encryption.push(crypto::derive_key(&secret, ENCRYPTION_CONTEXT));
+        }
+
+        self
+    }
+
+    /// Seals and opens a cookie with the configured keys, so `Server::cookies` rejects a bad
+    /// configuration at startup instead of failing requests.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.keys.empty_secret {
+            bail!("Cookie secrets must not be empty");
+        }
+
+        if self.keys.signing.is_empty() {
+            return Ok(());
+        }
+
+        for protection in [Protection::Signed, Protection::Encrypted] {
+            let mut cookie = Cookie::new("check", "value");
+            cookie.protection = protection;
+
+            let sealed = self.seal(&cookie)?;
+            let opened = match protection {
+                Protection::Signed => self.unsign(&cookie.name, &sealed.value),
+                _ => self.decrypt(&cookie.name, &sealed.value),
+            };
+
+            if opened.as_deref() != Some("value") {
+                bail!("Cookie keys failed to open a {:?} cookie they sealed", protection);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Hook for Cookies {
     async fn before(&self, mut req: Request, res: Response, next: Next) -> Response {
-        req.cookies.inner = self.parse(&req.header("cookie").into_boxed_str());
+        // HTTP/2 and HTTP/3 clients may split cookies over several `cookie` headers.
+        let raw = req
+            .headers
+            .get_all("cookie")
+            .into_iter()
+            .map(|value| value.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+
+        req.cookies.inner = self.parse(&raw);
+
+        if !self.keys.signing.is_empty() {
+            for (name, value) in &req.cookies.inner {
+                if let Some(value) = self.unsign(name, value) {
+                    req.cookies.signed.insert(name.clone(), value);
+                }
+
+                if let Some(value) = self.decrypt(name, value) {
+                    req.cookies.encrypted.insert(name.clone(), value);
+                }
+            }
+        }
 
         return next.handle(req, res);
     }
     
-    async fn after(&self, req: Request, res: Response, next: Next) -> Response {
-        let mut headers = Headers::new();
+    /// Cookies that break their prefix rules or cannot be sealed are not sent; the request is
+    /// failed with an error for the loggers and error handlers instead.
+    async fn after(&self, req: Request, mut res: Response, next: Next) -> Response {
+        let mut errors = Vec::new();
 
         for cookie in &res.cookies.outer {
-            headers.insert(cookie.name.clone(), cookie.parse());
+            let sealed = cookie
+                .validate_prefix()
+                .map_err(|message| anyhow!(message))
+                .and_then(|_| self.seal(cookie));
+
+            match sealed {
+                Ok(sealed) => res.headers.append("Set-Cookie", sealed.parse()),
+                Err(err) => errors.push(format!("{:#}", err)),
+            }
         }
 
-        return next.handle(req, res.set_headers(headers));
+        if !errors.is_empty() && res.error.is_none() {
+            res.error = Some(Error::new("Invalid cookie".to_string(), errors.join("; ")));
+        }
+
+        next.handle(req, res)
     }
 }
 
@@ -75,10 +204,64 @@ impl Cookies {
             .unwrap();
     } 
 
-    pub fn remove(&mut self, k: impl Into<String>) {
+    /// Value of a cookie set with `set_signed`, or `None` if it is missing or was tampered with.
+    pub fn signed(&self, k: &str) -> Option<&str> {
         self
-            .set(k, "")
-            .set_expires(Duration::from_secs(0) - Duration::from_secs(100));
+            .signed
+            .get(k)
+            .map(|s| s.as_str())
+    }
+
+    /// Value of a cookie set with `set_encrypted`, or `None` if it is missing or was tampered with.
+    pub fn encrypted(&self, k: &str) -> Option<&str> {
+        self
+            .encrypted
+            .get(k)
+            .map(|s| s.as_str())
+    }
+
+    /// Sets a cookie the client can read but not forge.
+    pub fn set_signed(&mut self, k: impl Into<String>, v: impl Into<String>) -> &mut Cookie {
+        let cookie = self.set(k, v);
+        cookie.protection = Protection::Signed;
+
+        cookie
+    }
+
+    /// Sets a cookie the client can neither read nor forge.
+    pub fn set_encrypted(&mut self, k: impl Into<String>, v: impl Into<String>) -> &mut Cookie {
+        let cookie = self.set(k, v);
+        cookie.protection = Protection::Encrypted;
+
+        cookie
+    }
+
+    /// Expires the cookie `k`. Browsers only drop a cookie when the path and domain match the
+    /// ones it was set with, so they are copied from a cookie of that name set earlier in this
+    /// response, or can be set on the returned cookie. `__Host-` and `__Secure-` names get the
+    /// attributes their prefix requires.
+    pub fn remove(&mut self, k: impl Into<String>) -> &mut Cookie {
+        let name = k.into();
+        let previous = self.outer.iter().rev().find(|cookie| cookie.name == name).cloned();
+        let cookie = self.set(name, "");
+
+        if let Some(previous) = previous {
+            cookie.path = previous.path;
+            cookie.domain = previous.domain;
+            cookie.secure = previous.secure;
+            cookie.http_only = previous.http_only;
+            cookie.same_site = previous.same_site;
+        }
+
+        if cookie.name.starts_with("__Host-") {
+            cookie.secure = Some(true);
+            cookie.path = Some("/".to_string());
+            cookie.domain = None;
+        } else if cookie.name.starts_with("__Secure-") {
+            cookie.secure = Some(true);
+        }
+
+        cookie.set_max_age(Duration::ZERO)
     }
 
     pub(crate) fn parse(&self, raw: &str) -> Values {
@@ -92,4 +275,136 @@ impl Cookies {
 
         return cookies;
     }
-}
\ No newline at end of file
+
+    // The name is part of the sealed data so a value cannot be replayed under another cookie.
+    fn seal(&self, cookie: &Cookie) -> Result<Cookie> {
+        if cookie.protection == Protection::Plain || cookie.value.is_empty() {
+            return Ok(cookie.clone());
+        }
+
+        if self.keys.signing.is_empty() {
+            bail!("Cookie '{}' must be signed or encrypted but no cookie key is set; use Server::cookies(Cookies::new().key(..))", cookie.name);
+        }
+
+        let data = format!("{}={}", cookie.name, cookie.value);
+        let mut sealed = cookie.clone();
+
+        sealed.value = match cookie.protection {
+            Protection::Signed => format!("{}.{}", cookie.value, crypto::sign(&self.keys.signing[0], data.as_bytes())),
+            _ => crypto::encrypt(&self.keys.encryption[0], data.as_bytes())
+                .map_err(|err| anyhow!("Failed to encrypt cookie '{}': {}", cookie.name, err))?,
+        };
+
+        Ok(sealed)
+    }
+
+    fn unsign(&self, name: &str, raw: &str) -> Option<String> {
+        let (value, signature) = raw.rsplit_once('.')?;
+        let data = format!("{}={}", name, value);
+
+        crypto::verify(&self.keys.signing, data.as_bytes(), signature).then(|| value.to_string())
+    }
+
+    fn decrypt(&self, name: &str, raw: &str) -> Option<String> {
+        let plaintext = String::from_utf8(crypto::decrypt(&self.keys.encryption, raw).ok()?).ok()?;
+
+        plaintext
+            .strip_prefix(&format!("{}=", name))
+            .map(|value| value.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn send(cookies: &Cookies, res: Response) -> Response {
+        cookies.after(Request::fake("GET", "/"), res, Next::new()).await
+    }
+
+    fn set_cookies(res: &Response) -> Vec<String> {
+        res.headers.get_all("Set-Cookie").into_iter().cloned().collect()
+    }
+
+    #[tokio::test]
+    async fn prefix_violations_fail_the_request_instead_of_panicking() {
+        let mut res = Response::new();
+        res.cookies.set("__Host-id", "1").set_secure(true);
+        res.cookies.set("__Secure-id", "2");
+        res.cookies.set("theme", "dark");
+
+        let mut res = send(&Cookies::new(), res).await;
+        let error = res.error.take().unwrap();
+
+        assert!(error.message.contains("'__Host-id' must be Secure, have Path=/ and no Domain"));
+        assert!(error.message.contains("'__Secure-id' must be Secure"));
+        assert_eq!(set_cookies(&res), vec!["theme=dark"]);
+    }
+
+    #[tokio::test]
+    async fn removing_prefixed_cookies_keeps_their_required_attributes() {
+        let mut res = Response::new();
+        res.cookies.remove("__Host-id");
+        res.cookies.remove("__Secure-id");
+
+        let mut res = send(&Cookies::new(), res).await;
+
+        assert!(res.error.take().is_none());
+        assert_eq!(set_cookies(&res), vec!["__Host-id=; Secure; Path=/; Max-Age=0", "__Secure-id=; Secure; Max-Age=0"]);
+    }
+
+    #[tokio::test]
+    async fn removing_a_cookie_set_earlier_copies_its_path_and_domain() {
+        let mut res = Response::new();
+        res.cookies.set("cart", "3").set_path("/shop").set_domain("example.com").set_http_only(true);
+        res.cookies.remove("cart");
+
+        let res = send(&Cookies::new(), res).await;
+        let removal = set_cookies(&res).pop().unwrap();
+
+        assert!(removal.starts_with("cart=;"));
+        assert!(removal.contains("Path=/shop"));
+        assert!(removal.contains("Domain=example.com"));
+        assert!(removal.contains("HttpOnly"));
+        assert!(removal.contains("Max-Age=0"));
+    }
+
+    #[tokio::test]
+    async fn sealing_without_a_key_fails_the_request() {
+        let mut res = Response::new();
+        res.cookies.set_signed("theme", "dark");
+
+        let mut res = send(&Cookies::new(), res).await;
+
+        assert!(res.error.take().unwrap().message.contains("no cookie key is set"));
+        assert!(set_cookies(&res).is_empty());
+    }
+
+    #[tokio::test]
+    async fn sealed_cookies_open_and_reject_tampering() {
+        let jar = Cookies::new().key("a secret");
+        let mut res = Response::new();
+        res.cookies.set_signed("theme", "dark");
+        res.cookies.set_encrypted("user", "42");
+
+        let res = send(&jar, res).await;
+        let header = set_cookies(&res).join("; ").replace("theme=dark.", "theme=light.");
+
+        let mut req = Request::fake("GET", "/");
+        req.headers.append("cookie", header);
+        let mut res = jar.before(req, Response::new(), Next::new()).await;
+        let req = res.request();
+
+        assert_eq!(req.cookies.signed("theme"), None);
+        assert_eq!(req.cookies.encrypted("user"), Some("42"));
+        assert_eq!(req.cookies.get("user").map(|value| value.contains("42")), Some(false));
+    }
+
+    #[test]
+    fn configuration_is_checked_up_front() {
+        assert!(Cookies::new().check().is_ok());
+        assert!(Cookies::new().key("a secret").previous_keys(vec!["an old secret"]).check().is_ok());
+        assert!(Cookies::new().key("").check().is_err());
+        assert!(Cookies::new().key("a secret").previous_keys(vec![" "]).check().is_err());
+    }
+}
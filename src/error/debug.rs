@@ -0,0 +1,213 @@
+use std::{cell::RefCell, collections::HashMap, env};
+
+use serde_json::{Map, Value, json};
+
+use crate::{
+    error::Error,
+    request::Request,
+    response::Response,
+    routing::route::Route,
+    utils::Values,
+    view::debug::escape,
+};
+
+tokio::task_local! {
+    pub(crate) static GLOBAL_PANIC_TRACE: RefCell<Option<Trace>>;
+}
+
+/// Environment variables and headers whose names contain one of these are masked on the error page.
+const SECRET_NAMES: &[&str] = &["KEY", "SECRET", "PASSWORD", "PASS", "TOKEN", "CREDENTIAL", "DSN", "AUTH", "COOKIE"];
+
+/// The matched route, kept in development so the error page can name it. Only what is cheap to
+/// copy is recorded, since every matched request records one.
+pub(crate) struct Trace {
+    route: String,
+    middlewares: Vec<&'static str>,
+    parameters: Values,
+}
+
+/// Records the route about to handle `req`, when a panic trace is being collected.
+pub(crate) fn record<H>(route: &Route<H>, req: &Request, names: &HashMap<String, &'static str>) {
+    let _ = GLOBAL_PANIC_TRACE.try_with(|cell| {
+        *cell.borrow_mut() = Some(Trace {
+            route: req.route.clone().unwrap_or_default(),
+            middlewares: route.middlewares.iter().filter_map(|key| names.get(key).copied()).collect(),
+            parameters: req.parameters.clone(),
+        });
+    });
+}
+
+/// Fills `res` with the development error page for `error`, as JSON when the client wants it.
+pub(crate) fn render(res: Response, error: &Error, req: &Request, trace: Option<Trace>, hooks: Vec<&'static str>) -> Response {
+    let report = report(error, req, trace, hooks);
+
+    if req.wants_json() {
+        return res.json(&report);
+    }
+
+    res.html(page(&report))
+}
+
+fn report(error: &Error, req: &Request, trace: Option<Trace>, hooks: Vec<&'static str>) -> Value {
+    let (route, middlewares, parameters) = match trace {
+        Some(trace) => (Value::from(trace.route), trace.middlewares, trace.parameters),
+        None => (Value::Null, Vec::new(), Values::new()),
+    };
+
+    let headers = sorted(req.headers.iter().map(|(name, value)| masked(name.clone(), value.clone())));
+    let environment = sorted(env::vars().map(|(name, value)| masked(name, value)));
+
+    let message = if error.message.is_empty() { &error.error } else { &error.message };
+
+    json!({
+        "message": message,
+        "error": error.error,
+        "backtrace": error.backtrace.as_deref().map(frames).unwrap_or_default(),
+        "route": route,
+        "middlewares": middlewares,
+        "hooks": hooks,
+        "request": {
+            "method": req.method,
+            "path": req.path,
+            "host": req.host,
+            "parameters": sorted(parameters),
+            "query": sorted(req.queries.clone()),
+            "headers": headers,
+        },
+        "environment": environment,
+    })
+}
+
+fn masked(name: String, value: String) -> (String, String) {
+    let secret = SECRET_NAMES.iter().any(|part| name.to_uppercase().contains(part));
+
+    (name, if secret { String::from("********") } else { value })
+}
+
+fn sorted(values: impl IntoIterator<Item = (String, String)>) -> Map<String, Value> {
+    let mut values: Vec<(String, String)> = values.into_iter().collect();
+    values.sort();
+
+    values.into_iter().map(|(k, v)| (k, Value::from(v))).collect()
+}
+
+fn frames(backtrace: &str) -> Vec<String> {
+    backtrace
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn page(report: &Value) -> String {
+    let text = |key: &str| escape(report[key].as_str().unwrap_or(""));
+    let list = |key: &str| {
+        report[key]
+            .as_array()
+            .map(|items| items.iter().filter_map(Value::as_str).map(escape).collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default()
+    };
+
+    let request = &report["request"];
+    let sections = [
+        ("Route parameters", &request["parameters"]),
+        ("Query", &request["query"]),
+        ("Headers", &request["headers"]),
+        ("Environment", &report["environment"]),
+    ];
+
+    let tables: String = sections
+        .iter()
+        .map(|(title, values)| format!("<h2>{}</h2>\n        {}", title, table(values)))
+        .collect::<Vec<_>>()
+        .join("\n        ");
+
+    let route = match report["route"].as_str() {
+        Some(route) => escape(route),
+        None => String::from("No route matched"),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>{message}</title>
+    <style>
+        body {{ font-family: -apple-system, "Segoe UI", sans-serif; margin: 0; background: #f6f7f9; color: #1f2328; }}
+        header {{ background: #b42318; color: #fff; padding: 24px 32px; }}
+        header h1 {{ margin: 0 0 8px; font-size: 20px; }}
+        header p {{ margin: 0; opacity: .85; font-family: monospace; }}
+        main {{ padding: 24px 32px; }}
+        h2 {{ font-size: 15px; margin: 24px 0 8px; }}
+        pre {{ background: #fff; border: 1px solid #d0d7de; border-radius: 6px; padding: 12px; overflow: auto; font-size: 13px; line-height: 1.5; max-height: 480px; }}
+        table {{ border-collapse: collapse; width: 100%; background: #fff; font-size: 13px; }}
+        td {{ border: 1px solid #d0d7de; padding: 6px 10px; vertical-align: top; font-family: monospace; word-break: break-all; }}
+        td:first-child {{ width: 30%; font-weight: 600; }}
+        .empty {{ color: #8c959f; }}
+    </style>
+</head>
+<body>
+    <header>
+        <h1>{message}</h1>
+        <p>{error}</p>
+    </header>
+    <main>
+        <h2>Route</h2>
+        <pre>{method} {path} &rarr; {route}</pre>
+        <h2>Middlewares</h2>
+        <pre>{middlewares}</pre>
+        <h2>Hooks</h2>
+        <pre>{hooks}</pre>
+        <h2>Backtrace</h2>
+        <pre>{backtrace}</pre>
+        {tables}
+    </main>
+</body>
+</html>"#,
+        message = text("message"),
+        error = text("error"),
+        method = escape(request["method"].as_str().unwrap_or("")),
+        path = escape(request["path"].as_str().unwrap_or("")),
+        route = route,
+        middlewares = list("middlewares"),
+        hooks = list("hooks"),
+        backtrace = list("backtrace"),
+        tables = tables,
+    )
+}
+
+fn table(values: &Value) -> String {
+    let Some(values) = values.as_object().filter(|values| !values.is_empty()) else {
+        return String::from(r#"<p class="empty">Empty</p>"#);
+    };
+
+    let rows: String = values
+        .iter()
+        .map(|(key, value)| format!("<tr><td>{}</td><td>{}</td></tr>", escape(key), escape(value.as_str().unwrap_or(""))))
+        .collect();
+
+    format!("<table>{}</table>", rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credentials_in_headers_are_masked() {
+        let mut req = Request::fake("GET", "/");
+        req.headers.append("authorization".to_string(), "Bearer secret".to_string());
+        req.headers.append("cookie".to_string(), "session=secret".to_string());
+        req.headers.append("x-api-key".to_string(), "secret".to_string());
+        req.headers.append("accept".to_string(), "text/html".to_string());
+
+        let report = report(&Error::default(), &req, None, Vec::new());
+        let headers = &report["request"]["headers"];
+
+        assert_eq!(headers["authorization"], "********");
+        assert_eq!(headers["cookie"], "********");
+        assert_eq!(headers["x-api-key"], "********");
+        assert_eq!(headers["accept"], "text/html");
+    }
+}
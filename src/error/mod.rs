@@ -1,9 +1,11 @@
-
+pub(crate) mod debug;
 
 #[derive(Clone, Default, Debug)]
 pub struct Error {
     pub error: String,
     pub message: String,
+    /// Captured for panics in development.
+    pub backtrace: Option<String>,
 }
 
 impl Error {
@@ -11,6 +13,7 @@ impl Error {
         return Self {
             error: error,
             message: message,
+            backtrace: None,
         };
     } 
 }
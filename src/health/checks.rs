@@ -0,0 +1,44 @@
+use anyhow::Result;
+
+use crate::{health::Check, mail, storage};
+
+/// Fails when a storage registered with `Server::storage` cannot be reached.
+pub struct StorageCheck {
+    storage: String,
+}
+
+impl StorageCheck {
+    pub fn new(storage: impl Into<String>) -> Self {
+        Self {
+            storage: storage.into(),
+        }
+    }
+}
+
+impl Check for StorageCheck {
+    async fn check(&self) -> Result<()> {
+        // Whether the file exists does not matter, only that the backend answered.
+        storage::exists(&self.storage, ".flyer-health").await?;
+
+        Ok(())
+    }
+}
+
+/// Fails when a mailer registered with `Server::mailer` cannot reach its server.
+pub struct MailerCheck {
+    mailer: String,
+}
+
+impl MailerCheck {
+    pub fn new(mailer: impl Into<String>) -> Self {
+        Self {
+            mailer: mailer.into(),
+        }
+    }
+}
+
+impl Check for MailerCheck {
+    async fn check(&self) -> Result<()> {
+        mail::ping(&self.mailer).await
+    }
+}
@@ -0,0 +1,289 @@
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use futures::future::{BoxFuture, join_all};
+use serde_json::{Map, Value, json};
+
+use crate::{response::Response, utils::future::SendFuture};
+
+pub mod checks;
+
+pub const DEFAULT_LIVENESS_PATH: &str = "/healthz";
+pub const DEFAULT_READINESS_PATH: &str = "/readyz";
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether the server received a shutdown signal and is draining its requests.
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+pub(crate) fn begin_shutdown() {
+    SHUTTING_DOWN.store(true, Ordering::Relaxed);
+}
+
+/// Something a probe depends on, e.g. a database or a queue.
+#[allow(async_fn_in_trait)]
+pub trait Check: Send + Sync {
+    async fn check(&self) -> Result<()>;
+}
+
+pub(crate) trait CheckErasure: Send + Sync {
+    fn check(&self) -> BoxFuture<'static, Result<()>>;
+}
+
+pub(crate) struct CheckWrapper<T: Check + 'static> {
+    pub instance: Arc<T>,
+}
+
+impl<T: Check + 'static> CheckWrapper<T> {
+    pub fn new(instance: T) -> Self {
+        Self {
+            instance: Arc::new(instance)
+        }
+    }
+}
+
+impl<T: Check + 'static> CheckErasure for CheckWrapper<T> {
+    fn check(&self) -> BoxFuture<'static, Result<()>> {
+        let instance = Arc::clone(&self.instance);
+
+        Box::pin(async move {
+            SendFuture(instance.check()).await
+        })
+    }
+}
+
+/// A check made of a closure, see `Health::check_fn`.
+pub struct FnCheck<F>(F);
+
+impl<F, Fut> Check for FnCheck<F>
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<()>>,
+{
+    async fn check(&self) -> Result<()> {
+        (self.0)().await
+    }
+}
+
+struct Probe {
+    name: String,
+    check: Arc<dyn CheckErasure>,
+    timeout: Option<Duration>,
+}
+
+/// Liveness and readiness probes, served by `Server::health`. Readiness fails as soon as the
+/// server is shutting down, so the orchestrator stops routing traffic to it.
+pub struct Health {
+    liveness: Vec<Probe>,
+    readiness: Vec<Probe>,
+    timeout: Duration,
+    liveness_path: String,
+    readiness_path: String,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Self {
+            liveness: Vec::new(),
+            readiness: Vec::new(),
+            timeout: Duration::from_secs(2),
+            liveness_path: String::from(DEFAULT_LIVENESS_PATH),
+            readiness_path: String::from(DEFAULT_READINESS_PATH),
+        }
+    }
+
+    /// Adds a readiness check.
+    pub fn check<C: Check + 'static>(mut self, name: impl Into<String>, check: C) -> Self {
+        self.readiness.push(Self::probe(name, check, None));
+        self
+    }
+
+    /// Adds a readiness check allowed to run for `timeout` instead of the default.
+    pub fn check_timeout<C: Check + 'static>(mut self, name: impl Into<String>, check: C, timeout: Duration) -> Self {
+        self.readiness.push(Self::probe(name, check, Some(timeout)));
+        self
+    }
+
+    pub fn check_fn<F, Fut>(self, name: impl Into<String>, check: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<()>> + 'static,
+    {
+        self.check(name, FnCheck(check))
+    }
+
+    /// Adds a liveness check, for what a restart would fix.
+    pub fn live<C: Check + 'static>(mut self, name: impl Into<String>, check: C) -> Self {
+        self.liveness.push(Self::probe(name, check, None));
+        self
+    }
+
+    /// How long a check may run before it counts as failed. Defaults to 2 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn paths(mut self, liveness: impl Into<String>, readiness: impl Into<String>) -> Self {
+        self.liveness_path = liveness.into();
+        self.readiness_path = readiness.into();
+        self
+    }
+
+    pub(crate) fn liveness_path(&self) -> String {
+        self.liveness_path.clone()
+    }
+
+    pub(crate) fn readiness_path(&self) -> String {
+        self.readiness_path.clone()
+    }
+
+    fn probe<C: Check + 'static>(name: impl Into<String>, check: C, timeout: Option<Duration>) -> Probe {
+        Probe {
+            name: name.into(),
+            check: Arc::new(CheckWrapper::new(check)),
+            timeout,
+        }
+    }
+
+    pub(crate) async fn liveness(&self, res: Response) -> Response {
+        let (healthy, checks) = self.run(&self.liveness).await;
+
+        Self::respond(res, if healthy { "ok" } else { "failing" }, checks)
+    }
+
+    pub(crate) async fn readiness(&self, res: Response) -> Response {
+        if is_shutting_down() {
+            return Self::respond(res, "shutting_down", Map::new());
+        }
+
+        let (healthy, checks) = self.run(&self.readiness).await;
+
+        Self::respond(res, if healthy { "ok" } else { "failing" }, checks)
+    }
+
+    async fn run(&self, probes: &[Probe]) -> (bool, Map<String, Value>) {
+        let results = join_all(probes.iter().map(|probe| async move {
+            let timeout = probe.timeout.unwrap_or(self.timeout);
+            let started = Instant::now();
+
+            let result = match tokio::time::timeout(timeout, probe.check.check()).await {
+                Ok(result) => result,
+                Err(_) => Err(anyhow!("Timed out after {}ms", timeout.as_millis())),
+            };
+
+            (probe.name.clone(), result, started.elapsed())
+        }))
+        .await;
+
+        let healthy = results.iter().all(|(_, result, _)| result.is_ok());
+        let checks = results
+            .into_iter()
+            .map(|(name, result, duration)| {
+                let mut check = json!({
+                    "status": if result.is_ok() { "ok" } else { "failing" },
+                    "duration_ms": duration.as_secs_f64() * 1000.0,
+                });
+
+                if let Err(err) = result {
+                    check["error"] = Value::from(format!("{:#}", err));
+                }
+
+                (name, check)
+            })
+            .collect();
+
+        (healthy, checks)
+    }
+
+    fn respond(res: Response, status: &str, checks: Map<String, Value>) -> Response {
+        res.status_code(if status == "ok" { 200 } else { 503 })
+            .set_header("Cache-Control", "no-store")
+            .json(&json!({ "status": status, "checks": checks }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Failing;
+
+    impl Check for Failing {
+        async fn check(&self) -> Result<()> {
+            Err(anyhow!("Connection refused"))
+        }
+    }
+
+    fn body(res: &Response) -> Value {
+        serde_json::from_slice(&res.content).unwrap()
+    }
+
+    #[tokio::test]
+    async fn liveness_only_runs_live_checks() {
+        let health = Health::new()
+            .check("database", Failing)
+            .live("worker", FnCheck(|| async { Ok(()) }));
+
+        let res = health.liveness(Response::new()).await;
+        let body = body(&res);
+
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.header("Cache-Control"), "no-store");
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["checks"]["worker"]["status"], "ok");
+        assert!(body["checks"].get("database").is_none());
+    }
+
+    #[tokio::test]
+    async fn slow_checks_time_out() {
+        let health = Health::new()
+            .timeout(Duration::from_millis(10))
+            .live("worker", FnCheck(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Ok(())
+            }));
+
+        let res = health.liveness(Response::new()).await;
+
+        assert_eq!(res.status_code, 503);
+        assert_eq!(body(&res)["checks"]["worker"]["error"], "Timed out after 10ms");
+    }
+
+    #[tokio::test]
+    async fn readiness_fails_on_failing_checks_and_shutdown() {
+        let health = Health::new()
+            .check("database", Failing)
+            .check_fn("queue", || async { Ok(()) });
+
+        let res = health.readiness(Response::new()).await;
+        let failing = body(&res);
+
+        assert_eq!(res.status_code, 503);
+        assert_eq!(failing["status"], "failing");
+        assert_eq!(failing["checks"]["database"]["error"], "Connection refused");
+        assert_eq!(failing["checks"]["queue"]["status"], "ok");
+
+        begin_shutdown();
+        let res = Health::new().readiness(Response::new()).await;
+        SHUTTING_DOWN.store(false, Ordering::Relaxed);
+
+        assert_eq!(res.status_code, 503);
+        assert_eq!(body(&res)["status"], "shutting_down");
+    }
+}
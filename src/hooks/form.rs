@@ -1,18 +1,13 @@
 use std::collections::HashMap;
-use std::fmt::Write;
 
 use anyhow::{Context, Result};
-use bytes::Bytes;
-use futures_util::stream;
-use multer::Multipart;
 use serde_json::Value;
 
 use crate::{
     hooks::Hook,
-    request::{form::File, Request},
-    response::Response,
+    request::{Request, form::{Form, FormError}, multipart::read_leading_fields},
+    response::{HTTP_BAD_REQUEST, Response},
     routing::next::Next,
-    utils::url::parse_query,
 };
 
 pub type JsonMap = HashMap<String, Value>;
@@ -25,22 +20,6 @@ impl FormHook {
         Self
     }
 
-    fn extract_boundary<'a>(&self, header: &'a str) -> Result<&'a str> {
-        header
-            .split(';')
-            .map(str::trim)
-            .find_map(|part| {
-                if part.len() >= 9 && part[..9].eq_ignore_ascii_case("boundary=") {
-                    let boundary = part[9..].trim_matches('"');
-                    if !boundary.is_empty() {
-                        return Some(boundary);
-                    }
-                }
-                None
-            })
-            .context("Multipart boundary parameter missing from Content-Type header")
-    }
-
     async fn parse(&self, req: &mut Request) -> Result<()> {
         let content_type = req.header("content-type");
         let mime = content_type
@@ -52,7 +31,7 @@ impl FormHook {
         if mime.eq_ignore_ascii_case("application/x-www-form-urlencoded") {
             self.parse_form_urlencoded(req).await?;
         } else if mime.eq_ignore_ascii_case("multipart/form-data") {
-            self.parse_multipart_form(req).await?;
+            read_leading_fields(req).await;
         } else if mime.eq_ignore_ascii_case("application/json") {
             self.parse_json_form(req).await?;
         }
@@ -69,72 +48,12 @@ impl FormHook {
         Ok(())
     }
 
-    async fn parse_multipart_form(&self, req: &mut Request) -> Result<()> {
-        let header = req.header("content-type");
-        let boundary = self.extract_boundary(&header)?;
-        let body_bytes = std::mem::take(&mut req.body);
-
-        let stream = stream::once(async move {
-            Ok::<_, std::convert::Infallible>(Bytes::from(body_bytes))
-        });
-
-        let mut multipart = Multipart::new(stream, boundary);
-        let mut values = HashMap::new();
-        let mut raw_files: Vec<(String, File)> = Vec::new();
-        let mut field_file_counts: HashMap<String, usize> = HashMap::new();
-
-        while let Some(field) = multipart.next_field().await? {
-            let name = field.name().unwrap_or_default().to_string();
-
-            if let Some(filename) = field.file_name() {
-                let filename = filename.to_string();
-                let content_type = field
-                    .content_type()
-                    .map(|mime| mime.as_ref())
-                    .unwrap_or("application/octet-stream")
-                    .to_string();
-
-                let data: Bytes = field.bytes().await?;
-                if data.is_empty() {
-                    continue;
-                }
-
-                *field_file_counts.entry(name.clone()).or_default() += 1;
-                raw_files.push((name, File::create(&filename, &content_type, data)));
-            } else {
-                let text = field.text().await.unwrap_or_default();
-                values.insert(name, text);
-            }
-        }
-
-        let mut files = HashMap::with_capacity(raw_files.len());
-        let mut field_file_indices: HashMap<String, usize> = HashMap::new();
-
-        for (name, file) in raw_files {
-            let total_count = field_file_counts.get(&name).copied().unwrap_or(0);
-            if total_count > 1 {
-                let idx = field_file_indices.entry(name.clone()).or_default();
-                files.insert(format!("{}[{}]", name, idx), file);
-                *idx += 1;
-            } else {
-                files.insert(name, file);
-            }
-        }
-
-        req.form.values.extend(values);
-        req.form.files.extend(files);
-        req.body.clear();
-
-        Ok(())
-    }
-
     async fn parse_form_urlencoded(&self, req: &mut Request) -> Result<()> {
         let body_bytes = std::mem::take(&mut req.body);
-        let body_str = std::str::from_utf8(&body_bytes)
-            .context("Failed to parse URL-encoded body as valid UTF-8")?;
+        let pairs = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body_bytes)
+            .context("Failed to parse URL-encoded body")?;
 
-        let values = parse_query(body_str);
-        req.form.values.extend(values);
+        req.form.merge(Form::from_pairs(pairs)?);
         req.body.clear();
 
         Ok(())
@@ -142,80 +61,55 @@ impl FormHook {
 
     async fn parse_json_form(&self, req: &mut Request) -> Result<()> {
         let parsed: Value = serde_json::from_slice(&req.body)?;
-        let mut out_map = HashMap::new();
-        let mut current_path = String::with_capacity(32);
-
-        match parsed {
-            Value::Array(values) => {
-                for (i, item) in values.into_iter().enumerate() {
-                    current_path.clear();
-                    let _ = write!(current_path, "{}", i);
-                    self.json_to_map(item, &mut current_path, &mut out_map);
-                }
-            }
-            Value::Object(obj) => {
-                for (key, val) in obj {
-                    current_path.clear();
-                    current_path.push_str(&key);
-                    self.json_to_map(val, &mut current_path, &mut out_map);
-                }
-            }
-            _ => {}
-        }
 
-        req.form.values.extend(out_map);
+        req.form.merge(Form::from_value(parsed));
 
         Ok(())
     }
-
-    fn json_to_map(
-        &self,
-        value: Value,
-        current_path: &mut String,
-        out_map: &mut HashMap<String, String>,
-    ) {
-        match value {
-            Value::Null => {
-                out_map.insert(current_path.clone(), "null".into());
-            }
-            Value::Bool(b) => {
-                out_map.insert(current_path.clone(), b.to_string());
-            }
-            Value::Number(num) => {
-                out_map.insert(current_path.clone(), num.to_string());
-            }
-            Value::String(s) => {
-                out_map.insert(current_path.clone(), s);
-            }
-            Value::Array(arr) => {
-                let base_len = current_path.len();
-                for (i, item) in arr.into_iter().enumerate() {
-                    current_path.truncate(base_len);
-                    let _ = write!(current_path, "[{}]", i);
-                    self.json_to_map(item, current_path, out_map);
-                }
-                current_path.truncate(base_len);
-            }
-            Value::Object(obj) => {
-                let base_len = current_path.len();
-                for (key, val) in obj {
-                    current_path.truncate(base_len);
-                    let _ = write!(current_path, "[{}]", key);
-                    self.json_to_map(val, current_path, out_map);
-                }
-                current_path.truncate(base_len);
-            }
-        }
-    }
 }
 
 impl Hook for FormHook {
     async fn before(&self, mut req: Request, res: Response, next: Next) -> Response {
-        let _ = self.parse(&mut req).await;
+        if let Err(err) = self.parse(&mut req).await && let Some(err) = err.downcast_ref::<FormError>() {
+            return crate::auth::reject(res, HTTP_BAD_REQUEST, &err.to_string(), req.wants_json());
+        }
+
         next.handle(req, res)
     }
 
     async fn after(&self, req: Request, res: Response, next: Next) -> Response {
         next.handle(req, res)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    async fn submit(body: &'static str) -> Response {
+        let mut req = Request::fake("POST", "/users");
+        req.headers.append("content-type".to_string(), "application/x-www-form-urlencoded".to_string());
+        req.body = Bytes::from_static(body.as_bytes());
+
+        FormHook::new().before(req, Response::new(), Next::new()).await
+    }
+
+    #[tokio::test]
+    async fn method_field_overrides_the_method() {
+        let mut res = submit("_method=put&name=ada").await;
+        let req = res.request();
+
+        assert!(res.is_next());
+        assert_eq!(req.method, "PUT");
+        assert_eq!(req.value("name"), "ada");
+    }
+
+    #[tokio::test]
+    async fn out_of_range_indices_are_rejected() {
+        let res = submit("a[99999999999]=x").await;
+
+        assert!(!res.is_next());
+        assert_eq!(res.status_code, HTTP_BAD_REQUEST);
+    }
+}
@@ -11,6 +11,7 @@ use crate::{
 
 pub mod assets;
 pub mod form;
+pub mod request_id;
 
 #[allow(async_fn_in_trait)]
 pub trait Hook: Send + Sync {
@@ -19,6 +20,7 @@ pub trait Hook: Send + Sync {
 }
 
 pub(crate) trait HookErasure: Send + Sync {
+    fn name(&self) -> &'static str;
     fn before(&self, req: Request, res: Response, next: Next) -> BoxFuture<'static, Response>;
     fn after(&self, req: Request, res: Response, next: Next) -> BoxFuture<'static, Response>;
 }
@@ -36,6 +38,10 @@ impl <T: Hook + 'static>HookWrapper<T> {
 }
 
 impl<T: Hook + 'static> HookErasure for HookWrapper<T> {
+    fn name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
     fn before(&self, req: Request, res: Response, next: Next) -> BoxFuture<'static, Response> {
         let instance = Arc::clone(&self.instance);
         
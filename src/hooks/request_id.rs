@@ -0,0 +1,149 @@
+use ulid::Ulid;
+
+use crate::{
+    hooks::Hook,
+    request::Request,
+    response::Response,
+    routing::next::Next,
+};
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Longest ID accepted from a client.
+const MAX_ID_LENGTH: usize = 128;
+
+/// Gives every request an ID, available as `Request::id()`, recorded on its tracing span and
+/// echoed in the response's `X-Request-Id` header. An ID sent by the client or a proxy in that
+/// header is kept when it looks safe to log; otherwise a new ULID is generated.
+///
+/// Enable it with `Server::request_id` so the ID is assigned before any other hook runs and is
+/// also known to error handlers and loggers when a handler panics.
+pub struct RequestId {
+    header: String,
+    trust_incoming: bool,
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestId {
+    pub fn new() -> Self {
+        Self {
+            header: String::from(REQUEST_ID_HEADER),
+            trust_incoming: true,
+        }
+    }
+
+    /// Reads and writes the ID under another header name.
+    pub fn header(mut self, name: impl Into<String>) -> Self {
+        self.header = name.into();
+        self
+    }
+
+    /// Whether to reuse an ID sent with the request. Turn it off when clients reach the
+    /// server directly instead of through a proxy that sets the header.
+    pub fn trust_incoming(mut self, trust: bool) -> Self {
+        self.trust_incoming = trust;
+        self
+    }
+
+    /// Sets the request's ID, unless it already has one, and echoes it on the response.
+    pub(crate) fn assign(&self, req: &mut Request, res: &mut Response) {
+        let id = match &req.id {
+            Some(id) => id.clone(),
+            None => self.resolve(req),
+        };
+
+        tracing::Span::current().record("request_id", id.as_str());
+
+        res.headers.insert(self.header.clone(), id.clone());
+        req.id = Some(id);
+    }
+
+    fn resolve(&self, req: &Request) -> String {
+        let incoming = req.header(&self.header);
+
+        if self.trust_incoming && is_valid(&incoming) {
+            return incoming;
+        }
+
+        Ulid::new().to_string()
+    }
+}
+
+impl Hook for RequestId {
+    async fn before(&self, mut req: Request, mut res: Response, next: Next) -> Response {
+        self.assign(&mut req, &mut res);
+
+        next.handle(req, res)
+    }
+
+    async fn after(&self, mut req: Request, mut res: Response, next: Next) -> Response {
+        self.assign(&mut req, &mut res);
+
+        next.handle(req, res)
+    }
+}
+
+/// Short and made of characters that cannot break a log line or a header.
+fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_ID_LENGTH
+        && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(incoming: &str) -> Request {
+        let mut req = Request::fake("GET", "/");
+        req.headers.append(REQUEST_ID_HEADER.to_string(), incoming.to_string());
+        req
+    }
+
+    #[test]
+    fn safe_incoming_ids_are_kept() {
+        let mut req = request("edge-01:42");
+        let mut res = Response::new();
+
+        RequestId::new().assign(&mut req, &mut res);
+
+        assert_eq!(req.id(), "edge-01:42");
+        assert_eq!(res.headers.get(REQUEST_ID_HEADER).map(String::as_str), Some("edge-01:42"));
+    }
+
+    #[test]
+    fn unsafe_or_untrusted_ids_are_replaced() {
+        for (hook, incoming) in [
+            (RequestId::new(), "bad id\r\nX-Injected: 1".to_string()),
+            (RequestId::new(), "a".repeat(MAX_ID_LENGTH + 1)),
+            (RequestId::new().trust_incoming(false), "edge-01".to_string()),
+        ] {
+            let mut req = request(&incoming);
+
+            hook.assign(&mut req, &mut Response::new());
+
+            assert_ne!(req.id(), incoming);
+            assert!(Ulid::from_string(&req.id()).is_ok());
+        }
+    }
+
+    #[test]
+    fn an_assigned_id_survives_later_hooks() {
+        let mut req = request("");
+        let hook = RequestId::new().header("X-Trace");
+
+        hook.assign(&mut req, &mut Response::new());
+        let first = req.id();
+
+        let mut res = Response::new();
+        hook.assign(&mut req, &mut res);
+
+        assert_eq!(req.id(), first);
+        assert_eq!(res.headers.get("X-Trace").cloned(), Some(first));
+    }
+}
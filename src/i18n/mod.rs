@@ -0,0 +1,350 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::LazyLock,
+};
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use serde_json::{Map, Value};
+
+use crate::{
+    hooks::Hook,
+    request::Request,
+    response::Response,
+    routing::next::Next,
+    validation::messages::{LOCALE_SESSION_KEY, Messages},
+};
+
+pub mod plural;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+type Catalogs = HashMap<String, HashMap<String, String>>;
+
+static TRANSLATIONS: LazyLock<ArcSwap<Catalogs>> = LazyLock::new(|| ArcSwap::from_pointee(Catalogs::new()));
+
+/// Loads translations from a directory and picks each request's locale from, in order, the
+/// `lang` query parameter, the `locale` cookie, the `locale` session key and `Accept-Language`.
+///
+/// A locale is a `<locale>.json` or `<locale>.toml` file, or a `<locale>/` directory whose
+/// files become key groups (`lang/fr/messages.json` holds `messages.*`). A `validation` group
+/// also translates validation messages.
+pub struct I18n {
+    default_locale: String,
+    query: String,
+    cookie: String,
+    remember: bool,
+}
+
+impl I18n {
+    pub fn new(directory: impl AsRef<Path>) -> Self {
+        let directory = directory.as_ref();
+
+        load(directory).unwrap_or_else(|err| panic!("Failed to load translations from {}: {:#}", directory.display(), err));
+
+        Self {
+            default_locale: DEFAULT_LOCALE.to_string(),
+            query: String::from("lang"),
+            cookie: String::from("locale"),
+            remember: true,
+        }
+    }
+
+    /// Locale used when the request asks for none of the loaded ones; `en` by default.
+    pub fn default_locale(mut self, locale: impl Into<String>) -> Self {
+        self.default_locale = locale.into().to_lowercase();
+        self
+    }
+
+    pub fn query(mut self, name: impl Into<String>) -> Self {
+        self.query = name.into();
+        self
+    }
+
+    pub fn cookie(mut self, name: impl Into<String>) -> Self {
+        self.cookie = name.into();
+        self
+    }
+
+    /// Keeps a locale picked with the query parameter in the cookie for later requests.
+    /// Enabled by default.
+    pub fn remember(mut self, remember: bool) -> Self {
+        self.remember = remember;
+        self
+    }
+
+    fn negotiate(&self, req: &Request) -> (String, bool) {
+        let available = locales();
+        let queried = req.query(self.query.clone());
+
+        if let Some(locale) = negotiate(&queried, &available) {
+            return (locale, true);
+        }
+
+        let candidates = [req.cookie(self.cookie.clone()), req.session(LOCALE_SESSION_KEY), req.header("accept-language")];
+
+        for candidate in candidates {
+            if let Some(locale) = negotiate(&candidate, &available) {
+                return (locale, false);
+            }
+        }
+
+        (self.default_locale.clone(), false)
+    }
+}
+
+impl Hook for I18n {
+    async fn before(&self, mut req: Request, mut res: Response, next: Next) -> Response {
+        let (locale, queried) = self.negotiate(&req);
+
+        if queried && self.remember {
+            res.set_cookie(self.cookie.clone(), locale.clone()).set_path("/");
+        }
+
+        req.locale = Some(locale);
+
+        next.handle(req, res)
+    }
+
+    async fn after(&self, req: Request, res: Response, next: Next) -> Response {
+        next.handle(req, res)
+    }
+}
+
+fn load(directory: &Path) -> Result<()> {
+    let mut trees: HashMap<String, Map<String, Value>> = HashMap::new();
+
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+
+        let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let locale = stem.to_lowercase();
+
+        if path.is_dir() {
+            for group in fs::read_dir(&path)? {
+                let group = group?.path();
+
+                let Some(name) = group.file_stem().and_then(|stem| stem.to_str()).map(String::from) else {
+                    continue;
+                };
+
+                if let Some(tree) = read_catalog(&group)? {
+                    trees.entry(locale.clone()).or_default().insert(name, tree);
+                }
+            }
+
+            continue;
+        }
+
+        if let Some(Value::Object(fields)) = read_catalog(&path)? {
+            trees.entry(locale).or_default().extend(fields);
+        }
+    }
+
+    for (locale, tree) in trees {
+        if let Some(validation) = tree.get("validation") {
+            Messages::add_tree(locale.clone(), validation)?;
+        }
+
+        let mut messages = HashMap::new();
+        flatten(&Value::Object(tree), String::new(), &mut messages);
+        add(locale, messages);
+    }
+
+    Ok(())
+}
+
+fn read_catalog(path: &Path) -> Result<Option<Value>> {
+    let tree = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => serde_json::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("Invalid translations in {}", path.display()))?,
+        Some("toml") => toml::from_str(&fs::read_to_string(path)?)
+            .with_context(|| format!("Invalid translations in {}", path.display()))?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(tree))
+}
+
+/// Flattens a catalog tree into dotted keys (`cart.items.one`).
+pub(crate) fn flatten(node: &Value, prefix: String, out: &mut HashMap<String, String>) {
+    match node {
+        Value::Object(fields) => {
+            for (key, value) in fields {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                flatten(value, path, out);
+            }
+        }
+        Value::String(message) => {
+            out.insert(prefix, message.clone());
+        }
+        other => {
+            out.insert(prefix, other.to_string());
+        }
+    }
+}
+
+/// Adds translations for `locale` with dotted keys (`messages.welcome`, `cart.items.one`).
+pub fn add(locale: impl Into<String>, messages: HashMap<String, String>) {
+    let locale = locale.into().to_lowercase();
+
+    TRANSLATIONS.rcu(|current| {
+        let mut catalogs = Catalogs::clone(current);
+
+        catalogs.entry(locale.clone()).or_default().extend(messages.clone());
+
+        catalogs
+    });
+}
+
+pub fn locales() -> Vec<String> {
+    TRANSLATIONS.load().keys().cloned().collect()
+}
+
+/// Translates `key`, trying `fr-ca`, then `fr`, then `en`, and returning the
+/// key itself when no catalog has it. `:name` placeholders are filled from `params`.
+pub fn trans(locale: &str, key: &str, params: &[(&str, &str)]) -> String {
+    let message = lookup(locale, &[key.to_string()]).unwrap_or_else(|| key.to_string());
+
+    replace_placeholders(&message, params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect())
+}
+
+/// Translates a counted message. The catalog gives one form per plural category of the
+/// locale, and optionally exact counts: `{"0": "No apples", "one": ":count apple", "other":
+/// ":count apples"}`. `:count` is filled in.
+pub fn trans_choice(locale: &str, key: &str, count: f64, params: &[(&str, &str)]) -> String {
+    let category = plural::category(locale, count);
+    let candidates = [
+        format!("{}.{}", key, count),
+        format!("{}.{}", key, category),
+        format!("{}.other", key),
+        key.to_string(),
+    ];
+
+    let message = lookup(locale, &candidates).unwrap_or_else(|| key.to_string());
+    let mut replacements: Vec<(String, String)> = params.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+    replacements.push((String::from("count"), count.to_string()));
+
+    replace_placeholders(&message, replacements)
+}
+
+fn lookup(locale: &str, keys: &[String]) -> Option<String> {
+    let translations = TRANSLATIONS.load();
+    let locale = locale.to_lowercase();
+    let language = locale.split(['-', '_']).next().unwrap_or(&locale).to_string();
+
+    for candidate in [locale.clone(), language, DEFAULT_LOCALE.to_string()] {
+        let Some(catalog) = translations.get(&candidate) else {
+            continue;
+        };
+
+        if let Some(message) = keys.iter().find_map(|key| catalog.get(key)) {
+            return Some(message.clone());
+        }
+    }
+
+    None
+}
+
+/// Picks the available locale the client ranks highest from an `Accept-Language` style list,
+/// matching `fr-CA` to `fr` when only the language is available.
+pub(crate) fn negotiate(header: &str, available: &[String]) -> Option<String> {
+    let mut ranked: Vec<(String, f32)> = header
+        .split(',')
+        .filter_map(|part| {
+            let mut params = part.split(';');
+            let tag = params.next()?.trim().to_lowercase().replace('_', "-");
+
+            if tag.is_empty() || tag == "*" {
+                return None;
+            }
+
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            Some((tag, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (tag, _) in &ranked {
+        if let Some(locale) = available.iter().find(|locale| locale.eq_ignore_ascii_case(tag)) {
+            return Some(locale.clone());
+        }
+
+        let language = tag.split('-').next().unwrap_or(tag);
+
+        if let Some(locale) = available.iter().find(|locale| locale.eq_ignore_ascii_case(language)) {
+            return Some(locale.clone());
+        }
+    }
+
+    None
+}
+
+/// Fills `:name` placeholders, longest names first so `:values` is not read as `:value`.
+pub(crate) fn replace_placeholders(message: &str, mut replacements: Vec<(String, String)>) -> String {
+    replacements.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    let mut message = message.to_string();
+
+    for (name, value) in replacements {
+        message = message.replace(&format!(":{}", name), &value);
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(default_locale: &str) -> I18n {
+        I18n {
+            default_locale: default_locale.to_string(),
+            query: String::from("lang"),
+            cookie: String::from("locale"),
+            remember: true,
+        }
+    }
+
+    #[test]
+    fn each_hook_keeps_its_own_default_locale() {
+        add("i18n-test-xx", HashMap::from([(String::from("greeting"), String::from("Hi"))]));
+
+        let req = Request::fake("GET", "/");
+
+        assert_eq!(hook("fr").negotiate(&req), (String::from("fr"), false));
+        assert_eq!(hook("de").negotiate(&req), (String::from("de"), false));
+    }
+
+    #[test]
+    fn catalogs_are_flattened_into_dotted_keys() {
+        let mut out = HashMap::new();
+
+        flatten(&serde_json::json!({ "cart": { "items": { "one": "1 item" } }, "count": 2 }), String::new(), &mut out);
+
+        assert_eq!(out.get("cart.items.one").map(String::as_str), Some("1 item"));
+        assert_eq!(out.get("count").map(String::as_str), Some("2"));
+    }
+
+    #[test]
+    fn longer_placeholders_are_filled_first() {
+        let message = replace_placeholders(":values and :value", vec![
+            (String::from("value"), String::from("a")),
+            (String::from("values"), String::from("b")),
+        ]);
+
+        assert_eq!(message, "b and a");
+    }
+}
@@ -0,0 +1,77 @@
+/// CLDR cardinal plural category (`zero`, `one`, `two`, `few`, `many` or `other`) of `n` in
+/// `locale`, for the languages whose rules differ from English.
+pub fn category(locale: &str, n: f64) -> &'static str {
+    let language = locale.split(['-', '_']).next().unwrap_or(locale).to_lowercase();
+    let integer = n.fract() == 0.0;
+    let i = n.abs().trunc() as u64;
+    let (mod10, mod100) = (i % 10, i % 100);
+
+    match language.as_str() {
+        "ja" | "zh" | "ko" | "th" | "vi" | "id" | "ms" | "lo" | "my" => "other",
+        "fr" | "pt" | "hi" | "fa" | "bn" => {
+            if i <= 1 { "one" } else { "other" }
+        }
+        "ru" | "uk" | "be" => {
+            if !integer {
+                "other"
+            } else if mod10 == 1 && mod100 != 11 {
+                "one"
+            } else if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "pl" => {
+            if !integer {
+                "other"
+            } else if i == 1 {
+                "one"
+            } else if (2..=4).contains(&mod10) && !(12..=14).contains(&mod100) {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => {
+            if !integer {
+                "many"
+            } else if i == 1 {
+                "one"
+            } else if (2..=4).contains(&i) {
+                "few"
+            } else {
+                "other"
+            }
+        }
+        "ar" => {
+            if !integer {
+                "other"
+            } else if i == 0 {
+                "zero"
+            } else if i == 1 {
+                "one"
+            } else if i == 2 {
+                "two"
+            } else if (3..=10).contains(&mod100) {
+                "few"
+            } else if (11..=99).contains(&mod100) {
+                "many"
+            } else {
+                "other"
+            }
+        }
+        "he" => {
+            if integer && i == 1 {
+                "one"
+            } else if integer && i == 2 {
+                "two"
+            } else {
+                "other"
+            }
+        }
+        _ => {
+            if integer && i == 1 { "one" } else { "other" }
+        }
+    }
+}
@@ -5,17 +5,23 @@ use crate::{
     utils::server::{TlsPathConfig, get_tls_config, server_config}
 };
 
+pub mod auth;
 pub mod cookies;
 pub mod error;
+pub mod health;
 pub mod hooks;
+pub mod i18n;
 pub mod loggers;
 pub mod mail;
+pub mod metrics;
 pub mod request;
 pub mod response;
 pub mod routing;
 pub mod server;
 pub mod session;
 pub mod storage;
+#[cfg(feature = "otlp")]
+pub mod telemetry;
 pub mod types;
 pub mod utils;
 pub mod validation;
@@ -0,0 +1,222 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::Path,
+    sync::mpsc::{self, Sender},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use crate::{
+    loggers::{Completed, Event, Logger},
+    request::Request,
+};
+
+pub enum AccessLogFormat {
+    /// `127.0.0.1 - jane [10/Oct/2025:13:55:36 +0200] "GET /users?page=2 HTTP/1.1" 200 2326`
+    Common,
+    /// `Common` followed by the quoted `Referer` and `User-Agent`.
+    Combined,
+    /// One JSON object per line, adding the route pattern, latency and request ID.
+    Json,
+}
+
+/// Writes a line per answered request from a writer thread, to stdout by default.
+pub struct AccessLog {
+    format: AccessLogFormat,
+    lines: Sender<String>,
+}
+
+impl AccessLog {
+    pub fn new(format: AccessLogFormat) -> Self {
+        Self {
+            format,
+            lines: spawn_writer(Box::new(io::stdout())),
+        }
+    }
+
+    /// Appends to `path`, creating it when missing.
+    pub fn file(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open access log {}", path.display()))?;
+
+        Ok(self.writer(file))
+    }
+
+    /// Writes to `writer` instead; the previous writer thread stops once it is replaced.
+    pub fn writer(mut self, writer: impl Write + Send + 'static) -> Self {
+        self.lines = spawn_writer(Box::new(writer));
+        self
+    }
+
+    pub fn line(&self, completed: &Completed) -> String {
+        let req = &completed.request;
+        let res = &completed.response;
+
+        let user = req.identity().map(|identity| identity.id.clone()).filter(|id| !id.is_empty());
+        let bytes = res.content.len();
+
+        if let AccessLogFormat::Json = self.format {
+            return json!({
+                "time": completed.received_at.to_rfc3339(),
+                "ip": req.ip().to_string(),
+                "user": user,
+                "method": req.method,
+                "path": req.path,
+                "query": query_string(req),
+                "route": req.route(),
+                "protocol": req.protocol,
+                "status": res.status_code,
+                "bytes": bytes,
+                "duration_ms": completed.duration.as_secs_f64() * 1000.0,
+                "request_id": req.id(),
+                "referer": req.header("referer"),
+                "user_agent": req.header("user-agent"),
+            })
+            .to_string();
+        }
+
+        let query = query_string(req);
+        let target = if query.is_empty() { req.path.clone() } else { format!("{}?{}", req.path, query) };
+
+        let common = format!(
+            "{} - {} [{}] \"{} {} {}\" {} {}",
+            req.ip(),
+            user.unwrap_or_else(|| String::from("-")),
+            completed.received_at.format("%d/%b/%Y:%H:%M:%S %z"),
+            req.method,
+            target,
+            req.protocol,
+            res.status_code,
+            if bytes == 0 { String::from("-") } else { bytes.to_string() },
+        );
+
+        match self.format {
+            AccessLogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                common,
+                quoted(&req.header("referer")),
+                quoted(&req.header("user-agent")),
+            ),
+            _ => common,
+        }
+    }
+}
+
+impl Logger for AccessLog {
+    async fn event(&self, event: Event) {
+        let Event::Completed(completed) = event else {
+            return;
+        };
+
+        let _ = self.lines.send(self.line(&completed));
+    }
+
+    fn wants_completed(&self) -> bool {
+        true
+    }
+}
+
+/// Starts the thread that owns the output, running until every sender is dropped.
+fn spawn_writer(mut output: Box<dyn Write + Send>) -> Sender<String> {
+    let (lines, received) = mpsc::channel::<String>();
+
+    thread::Builder::new()
+        .name(String::from("flyer-access-log"))
+        .spawn(move || {
+            while let Ok(line) = received.recv() {
+                let _ = writeln!(output, "{}", line);
+
+                // Flush once the lines already queued are written, not after each of them.
+                for line in received.try_iter() {
+                    let _ = writeln!(output, "{}", line);
+                }
+
+                let _ = output.flush();
+            }
+        })
+        .expect("Failed to start the access log writer");
+
+    lines
+}
+
+fn query_string(req: &Request) -> String {
+    let mut pairs: Vec<(&String, &String)> = req.queries.iter().collect();
+    pairs.sort();
+
+    serde_urlencoded::to_string(pairs).unwrap_or_default()
+}
+
+/// `-` for a missing header, and quotes escaped so the line stays parseable.
+fn quoted(value: &str) -> String {
+    if value.is_empty() {
+        return String::from("-");
+    }
+
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+    use chrono::Local;
+
+    use super::*;
+    use crate::response::Response;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn completed(path: &str) -> Event {
+        Event::Completed(Completed {
+            request: Request::fake("GET", path),
+            response: Response::new(),
+            duration: Duration::from_millis(3),
+            received_at: Local::now(),
+        })
+    }
+
+    #[tokio::test]
+    async fn lines_are_written_by_the_writer_thread() {
+        let buffer = Buffer::default();
+        let log = AccessLog::new(AccessLogFormat::Common).writer(buffer.clone());
+
+        log.event(completed("/a")).await;
+        log.event(completed("/b")).await;
+
+        let started = Instant::now();
+
+        let written = loop {
+            let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+
+            if written.lines().count() == 2 || started.elapsed() > Duration::from_secs(5) {
+                break written;
+            }
+
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        };
+
+        let lines: Vec<&str> = written.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\"GET /a HTTP/1.1\" 200 -"));
+        assert!(lines[1].contains("\"GET /b HTTP/1.1\""));
+    }
+}
@@ -1,5 +1,6 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
+use chrono::{DateTime, Local};
 use futures::future::BoxFuture;
 
 use crate::{
@@ -9,15 +10,64 @@ use crate::{
     utils::future::SendFuture
 };
 
+pub mod access;
 pub mod sentry;
 
+/// Something a logger may record.
+#[derive(Clone)]
+pub enum Event {
+    /// A request was answered, errors included.
+    Completed(Completed),
+    /// A handler panicked or a view failed to render.
+    Error(Error, Request, Response),
+    /// Something worth noting that did not fail the request, see `Request::warn`. Background
+    /// work, such as the mail queue, sends these without a request.
+    Warning(String, Option<Request>),
+}
+
+/// Sends a warning that belongs to no request to the loggers of the server, or to `tracing`
+/// when no logger is registered.
+#[allow(static_mut_refs)]
+pub(crate) fn warn(message: impl Into<String>) {
+    let message = message.into();
+
+    match unsafe { crate::GLOBAL_SERVER.get() } {
+        Some(server) if !server.loggers.is_empty() => server.emit(Event::Warning(message, None)),
+        _ => tracing::warn!("{}", message),
+    }
+}
+
+#[derive(Clone)]
+pub struct Completed {
+    pub request: Request,
+    pub response: Response,
+    /// Time from receiving the request to having the response ready to send.
+    pub duration: Duration,
+    pub received_at: DateTime<Local>,
+}
+
 #[allow(async_fn_in_trait)]
 pub trait Logger: Send + Sync {
-    async fn call(&self, info: Error, req: Request, res: Response) -> ();
+    /// Called with panics and other errors answered with a `500`.
+    async fn call(&self, _info: Error, _req: Request, _res: Response) -> () {}
+
+    /// Called with every event. Errors are passed on to `call` unless this is overridden.
+    async fn event(&self, event: Event) -> () {
+        if let Event::Error(info, req, res) = event {
+            self.call(info, req, res).await;
+        }
+    }
+
+    /// Whether `event` should also get `Event::Completed`. Off by default, since every such
+    /// event holds a copy of the request and the response.
+    fn wants_completed(&self) -> bool {
+        false
+    }
 }
 
 pub(crate) trait LoggerErasure: Send + Sync {
-    fn call(&self, info: Error, req: Request, res: Response) -> BoxFuture<'static, ()>;
+    fn event(&self, event: Event) -> BoxFuture<'static, ()>;
+    fn wants_completed(&self) -> bool;
 }
 
 pub struct LoggerWrapper<T: Logger + 'static> {
@@ -33,11 +83,34 @@ impl <T: Logger + 'static>LoggerWrapper<T> {
 }
 
 impl<T: Logger + 'static> LoggerErasure for LoggerWrapper<T> {
-    fn call(&self, info: Error, req: Request, res: Response) -> BoxFuture<'static, ()> {
+    fn event(&self, event: Event) -> BoxFuture<'static, ()> {
         let instance = Arc::clone(&self.instance);
 
         return Box::pin(async move {
-            SendFuture(instance.call(info, req, res)).await
+            SendFuture(instance.event(event)).await
         });
     }
-}
\ No newline at end of file
+
+    fn wants_completed(&self) -> bool {
+        self.instance.wants_completed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loggers::access::{AccessLog, AccessLogFormat};
+
+    struct Errors;
+
+    impl Logger for Errors {}
+
+    #[test]
+    fn completed_requests_are_opt_in() {
+        let errors: Arc<dyn LoggerErasure> = Arc::new(LoggerWrapper::new(Errors));
+        let access: Arc<dyn LoggerErasure> = Arc::new(LoggerWrapper::new(AccessLog::new(AccessLogFormat::Common)));
+
+        assert!(!errors.wants_completed());
+        assert!(access.wants_completed());
+    }
+}
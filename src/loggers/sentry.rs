@@ -7,7 +7,7 @@ use sentry::{
 };
 
 use crate::{
-    error::Error, loggers::Logger, request::Request, response::Response,
+    error::Error, loggers::{self, Logger}, request::Request, response::Response,
 };
 
 pub struct Sentry {
@@ -35,16 +35,49 @@ impl Logger for Sentry {
         sentry::configure_scope(|scope| {
             scope.clear();
 
+            if let Some(id) = &req.id {
+                scope.set_tag("request_id", id);
+            }
+
             scope.set_extra("request", req.into());
             scope.set_extra("response", res.into());
         });
 
         sentry::capture_event(Event {
             level: Level::Error,
-            message: Some(format!("{}", error.error)),
+            message: Some(error.error.to_string()),
             ..Default::default()
         });
 
         self.guard.flush(Some(Duration::from_secs(2)));
     }
+
+    /// Errors as above, and warnings such as dead-lettered mail at the warning level.
+    async fn event(&self, event: loggers::Event) {
+        match event {
+            loggers::Event::Error(error, req, res) => self.call(error, req, res).await,
+            loggers::Event::Warning(message, req) => {
+                sentry::configure_scope(|scope| {
+                    scope.clear();
+
+                    if let Some(req) = req {
+                        if let Some(id) = &req.id {
+                            scope.set_tag("request_id", id);
+                        }
+
+                        scope.set_extra("request", req.into());
+                    }
+                });
+
+                sentry::capture_event(Event {
+                    level: Level::Warning,
+                    message: Some(message),
+                    ..Default::default()
+                });
+
+                self.guard.flush(Some(Duration::from_secs(2)));
+            }
+            loggers::Event::Completed(_) => {}
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use lettre::message::{Attachment as LettreAttachment, SinglePart, header::ContentType};
+use serde::{Deserialize, Serialize};
+
+use crate::storage;
+
+/// Where an attachment's content comes from; files are only read when the mail is built.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) enum Source {
+    Bytes(#[serde(with = "base64_bytes")] Bytes),
+    Path(PathBuf),
+    Storage { storage: String, filename: String },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Attachment {
+    pub(crate) name: String,
+    pub(crate) content_type: Option<String>,
+    pub(crate) source: Source,
+    /// Content ID of an inline image, referenced as `cid:<id>` from the HTML body.
+    pub(crate) content_id: Option<String>,
+}
+
+impl Attachment {
+    pub(crate) fn new(name: impl Into<String>, content_type: Option<String>, source: Source) -> Self {
+        Self {
+            name: name.into(),
+            content_type,
+            source,
+            content_id: None,
+        }
+    }
+
+    pub(crate) fn inline(mut self, content_id: impl Into<String>) -> Self {
+        self.content_id = Some(content_id.into());
+        self
+    }
+
+    pub(crate) async fn part(&self) -> Result<SinglePart> {
+        let (content, content_type) = self.content().await?;
+        let content_type = ContentType::parse(&content_type)
+            .with_context(|| format!("Invalid content type {content_type} for attachment {}", self.name))?;
+
+        let attachment = match &self.content_id {
+            Some(id) => LettreAttachment::new_inline_with_name(id.clone(), self.name.clone()),
+            None => LettreAttachment::new(self.name.clone()),
+        };
+
+        Ok(attachment.body(content.to_vec(), content_type))
+    }
+
+    /// The content and its type: the one given, else the source's, else guessed from the name.
+    pub(crate) async fn content(&self) -> Result<(Bytes, String)> {
+        let (content, mime) = self.load().await?;
+
+        let content_type = self
+            .content_type
+            .clone()
+            .or(mime)
+            .unwrap_or_else(|| mime_guess::from_path(&self.name).first_or_octet_stream().to_string());
+
+        Ok((content, content_type))
+    }
+
+    /// The content, and its mime type when the source knows it.
+    async fn load(&self) -> Result<(Bytes, Option<String>)> {
+        match &self.source {
+            Source::Bytes(bytes) => Ok((bytes.clone(), None)),
+            Source::Path(path) => {
+                let content = tokio::fs::read(path)
+                    .await
+                    .with_context(|| format!("Failed to read attachment {}", path.display()))?;
+
+                Ok((Bytes::from(content), None))
+            }
+            Source::Storage { storage, filename } => {
+                let file = storage::get(storage, filename.clone())
+                    .await
+                    .with_context(|| format!("Failed to read attachment {filename} from storage {storage}"))?;
+
+                Ok((file.content, Some(file.mime).filter(|mime| !mime.is_empty())))
+            }
+        }
+    }
+}
+
+/// Keeps queued attachments compact in JSON.
+mod base64_bytes {
+    use base64::{Engine, engine::general_purpose};
+    use bytes::Bytes;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&general_purpose::STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+
+        general_purpose::STANDARD
+            .decode(encoded)
+            .map(Bytes::from)
+            .map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use lettre::{
+    Message,
+    message::dkim::{DkimConfig, DkimSigningAlgorithm, DkimSigningKey},
+};
+
+use crate::mail::Mailer;
+
+/// A DKIM key, used to sign everything a mailer sends.
+pub struct Dkim {
+    config: DkimConfig,
+}
+
+impl Dkim {
+    /// `key` is a PKCS#1 PEM private key; the public key is published at
+    /// `<selector>._domainkey.<domain>`.
+    pub fn rsa(selector: impl Into<String>, domain: impl Into<String>, key: &str) -> Result<Self> {
+        Self::new(selector, domain, key, DkimSigningAlgorithm::Rsa)
+    }
+
+    /// `key` is the base64 encoded 32 byte Ed25519 private key.
+    pub fn ed25519(selector: impl Into<String>, domain: impl Into<String>, key: &str) -> Result<Self> {
+        Self::new(selector, domain, key, DkimSigningAlgorithm::Ed25519)
+    }
+
+    fn new(selector: impl Into<String>, domain: impl Into<String>, key: &str, algorithm: DkimSigningAlgorithm) -> Result<Self> {
+        let key = DkimSigningKey::new(key.trim(), algorithm).context("Invalid DKIM private key")?;
+
+        Ok(Self {
+            config: DkimConfig::default_config(selector.into(), domain.into(), key),
+        })
+    }
+
+    /// Wraps `mailer` so every message is signed before it is sent.
+    pub fn sign<M: Mailer>(self, mailer: M) -> Signed<M> {
+        Signed { dkim: self, mailer }
+    }
+}
+
+pub struct Signed<M: Mailer> {
+    dkim: Dkim,
+    mailer: M,
+}
+
+impl<M: Mailer> Mailer for Signed<M> {
+    async fn send(&self, mut message: Message) -> Result<()> {
+        message.sign(&self.dkim.config);
+
+        self.mailer.send(message).await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        self.mailer.ping().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mail::{Mail, Mailbox, memory::MemoryMailer};
+
+    const KEY: &str = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=";
+
+    #[tokio::test]
+    async fn sent_messages_are_signed() {
+        let outbox = MemoryMailer::new();
+        let mailer = Dkim::ed25519("mail", "example.com", KEY).unwrap().sign(outbox.clone());
+
+        let message = Mail::new()
+            .from("app@example.com", None)
+            .text("Hi")
+            .build(&Mailbox::new("jane@example.com", None))
+            .await
+            .unwrap();
+
+        mailer.send(message).await.unwrap();
+
+        let formatted = String::from_utf8(outbox.messages()[0].formatted()).unwrap();
+
+        assert!(formatted.contains("DKIM-Signature: v=1; a=ed25519-sha256;"));
+        assert!(formatted.contains("d=example.com") && formatted.contains("s=mail"));
+    }
+
+    #[test]
+    fn invalid_keys_are_rejected() {
+        let err = Dkim::rsa("mail", "example.com", "not a key").err().unwrap();
+
+        assert_eq!(err.to_string(), "Invalid DKIM private key");
+    }
+}
@@ -0,0 +1,256 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use base64::{Engine, engine::general_purpose};
+use lettre::Message;
+
+use crate::mail::Mailer;
+
+/// A sent message taken apart for assertions, with its bodies decoded.
+#[derive(Clone, Debug)]
+pub struct SentMail {
+    pub from: String,
+    /// Every address the message was delivered to, `Cc` and `Bcc` included.
+    pub to: Vec<String>,
+    pub subject: String,
+    pub text: Option<String>,
+    pub html: Option<String>,
+    /// File names of attachments and inline images.
+    pub attachments: Vec<String>,
+    pub headers: Vec<(String, String)>,
+}
+
+impl SentMail {
+    fn new(message: &Message) -> Self {
+        let formatted = String::from_utf8_lossy(&message.formatted()).to_string();
+        let head = formatted.split_once("\r\n\r\n").map(|(head, _)| head).unwrap_or_default();
+
+        let mut sent = Self {
+            from: message.headers().get_raw("From").unwrap_or_default().to_string(),
+            to: message.envelope().to().iter().map(|address| address.to_string()).collect(),
+            subject: message.headers().get_raw("Subject").unwrap_or_default().to_string(),
+            text: None,
+            html: None,
+            attachments: Vec::new(),
+            headers: mime_headers(head),
+        };
+
+        sent.read_part(&formatted);
+        sent
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn is_sent_to(&self, email: &str) -> bool {
+        self.to.iter().any(|to| to.eq_ignore_ascii_case(email))
+    }
+
+    /// Whether the text or the HTML body contains `needle`.
+    pub fn body_contains(&self, needle: &str) -> bool {
+        [&self.text, &self.html].iter().any(|body| body.as_deref().is_some_and(|body| body.contains(needle)))
+    }
+
+    #[track_caller]
+    pub fn assert_subject(&self, expected: &str) -> &Self {
+        assert_eq!(self.subject, expected, "Unexpected subject for mail to {:?}", self.to);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_subject_contains(&self, needle: &str) -> &Self {
+        assert!(self.subject.contains(needle), "Subject {:?} does not contain {:?}", self.subject, needle);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_body_contains(&self, needle: &str) -> &Self {
+        assert!(self.body_contains(needle), "Neither body of mail {:?} contains {:?}", self.subject, needle);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_text_contains(&self, needle: &str) -> &Self {
+        let text = self.text.as_deref().unwrap_or_default();
+        assert!(text.contains(needle), "Text body of mail {:?} does not contain {:?}:\n{}", self.subject, needle, text);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_html_contains(&self, needle: &str) -> &Self {
+        let html = self.html.as_deref().unwrap_or_default();
+        assert!(html.contains(needle), "HTML body of mail {:?} does not contain {:?}:\n{}", self.subject, needle, html);
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_attachment(&self, name: &str) -> &Self {
+        assert!(
+            self.attachments.iter().any(|attachment| attachment == name),
+            "Mail {:?} has no attachment {:?}, only {:?}",
+            self.subject,
+            name,
+            self.attachments
+        );
+        self
+    }
+
+    /// Collects the bodies and attachment names of a MIME part and its children.
+    fn read_part(&mut self, raw: &str) {
+        let (head, body) = raw.split_once("\r\n\r\n").unwrap_or((raw, ""));
+        let headers = mime_headers(head);
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+                .unwrap_or_default()
+        };
+
+        let content_type = header("Content-Type").to_lowercase();
+
+        if content_type.starts_with("multipart/") {
+            let Some(boundary) = parameter(header("Content-Type"), "boundary") else {
+                return;
+            };
+
+            for part in body.split(&format!("--{}", boundary)).skip(1) {
+                if part.starts_with("--") {
+                    break;
+                }
+
+                self.read_part(part.trim_start_matches("\r\n"));
+            }
+
+            return;
+        }
+
+        if let Some(filename) = parameter(header("Content-Disposition"), "filename") {
+            self.attachments.push(filename);
+            return;
+        }
+
+        let body = body.trim_end_matches("\r\n");
+        let decoded = match header("Content-Transfer-Encoding").to_lowercase().as_str() {
+            "base64" => general_purpose::STANDARD
+                .decode(body.split_whitespace().collect::<String>())
+                .unwrap_or_default(),
+            "quoted-printable" => quoted_printable::decode(body, quoted_printable::ParseMode::Robust).unwrap_or_default(),
+            _ => body.as_bytes().to_vec(),
+        };
+        let decoded = String::from_utf8_lossy(&decoded).to_string();
+
+        if content_type.starts_with("text/html") {
+            self.html = Some(decoded);
+        } else if content_type.is_empty() || content_type.starts_with("text/plain") {
+            self.text = Some(decoded);
+        }
+    }
+}
+
+/// Headers of a MIME part, folded lines joined.
+fn mime_headers(head: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+
+    for line in head.split("\r\n") {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    headers
+}
+
+/// A `key=value` parameter of a header such as `Content-Type`, unquoted.
+fn parameter(value: &str, key: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.trim().split_once('=')?;
+
+        name.trim()
+            .eq_ignore_ascii_case(key)
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Records messages instead of sending them, with assertions for tests.
+#[derive(Clone, Default)]
+pub struct FakeMailer {
+    sent: Arc<Mutex<Vec<SentMail>>>,
+}
+
+impl FakeMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sent(&self) -> Vec<SentMail> {
+        self.sent.lock().expect("Fake mailer lock poisoned").clone()
+    }
+
+    pub fn clear(&self) {
+        self.sent.lock().expect("Fake mailer lock poisoned").clear();
+    }
+
+    /// The last mail sent to `email`; panics when there is none.
+    #[track_caller]
+    pub fn assert_sent_to(&self, email: &str) -> SentMail {
+        let sent = self.sent();
+
+        match sent.iter().rev().find(|mail| mail.is_sent_to(email)) {
+            Some(mail) => mail.clone(),
+            None => panic!(
+                "No mail was sent to {:?}; recipients were {:?}",
+                email,
+                sent.iter().flat_map(|mail| mail.to.clone()).collect::<Vec<_>>()
+            ),
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_not_sent_to(&self, email: &str) {
+        if let Some(mail) = self.sent().iter().find(|mail| mail.is_sent_to(email)) {
+            panic!("Mail {:?} was sent to {:?}", mail.subject, email);
+        }
+    }
+
+    /// The last mail matching `predicate`; panics when there is none.
+    #[track_caller]
+    pub fn assert_sent(&self, predicate: impl Fn(&SentMail) -> bool) -> SentMail {
+        let sent = self.sent();
+
+        match sent.iter().rev().find(|mail| predicate(mail)) {
+            Some(mail) => mail.clone(),
+            None => panic!(
+                "No sent mail matches; subjects were {:?}",
+                sent.iter().map(|mail| mail.subject.clone()).collect::<Vec<_>>()
+            ),
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_sent_count(&self, count: usize) {
+        let sent = self.sent().len();
+        assert_eq!(sent, count, "Expected {} mails to be sent, {} were", count, sent);
+    }
+
+    #[track_caller]
+    pub fn assert_nothing_sent(&self) {
+        self.assert_sent_count(0);
+    }
+}
+
+impl Mailer for FakeMailer {
+    async fn send(&self, message: Message) -> Result<()> {
+        self.sent.lock().expect("Fake mailer lock poisoned").push(SentMail::new(&message));
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use lettre::Message;
+use tokio::fs;
+use ulid::Ulid;
+
+use crate::mail::Mailer;
+
+/// Writes messages to disk instead of sending them, to read them in development or tests.
+pub struct FileMailer {
+    directory: PathBuf,
+    maildir: bool,
+}
+
+impl FileMailer {
+    /// Writes each message to `<directory>/<ulid>.eml`.
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            maildir: false,
+        }
+    }
+
+    /// Delivers into the `new` folder of a Maildir, so any mail client pointed at it shows
+    /// the messages.
+    pub fn maildir(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+            maildir: true,
+        }
+    }
+
+    async fn write(&self, name: &str, content: Vec<u8>) -> Result<PathBuf> {
+        if !self.maildir {
+            let path = self.directory.join(format!("{}.eml", name));
+
+            fs::create_dir_all(&self.directory).await?;
+            fs::write(&path, content).await?;
+
+            return Ok(path);
+        }
+
+        for folder in ["tmp", "new", "cur"] {
+            fs::create_dir_all(self.directory.join(folder)).await?;
+        }
+
+        // Maildir readers must never see a partial file: write to `tmp`, then move to `new`.
+        let tmp = self.directory.join("tmp").join(name);
+        let path = self.directory.join("new").join(name);
+
+        fs::write(&tmp, content).await?;
+        fs::rename(&tmp, &path).await?;
+
+        Ok(path)
+    }
+}
+
+impl Mailer for FileMailer {
+    async fn send(&self, message: Message) -> Result<()> {
+        let name = match self.maildir {
+            true => {
+                let seconds = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                format!("{}.{}.flyer", seconds, Ulid::new())
+            }
+            false => Ulid::new().to_string(),
+        };
+
+        self.write(&name, message.formatted())
+            .await
+            .with_context(|| format!("Failed to write mail to {}", self.directory.display()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use lettre::Message;
+
+use crate::mail::Mailer;
+
+/// Keeps sent messages in memory; clones share the same outbox.
+#[derive(Clone, Default)]
+pub struct MemoryMailer {
+    messages: Arc<Mutex<Vec<Message>>>,
+}
+
+impl MemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn messages(&self) -> Vec<Message> {
+        self.messages.lock().expect("Memory mailer lock poisoned").clone()
+    }
+
+    pub fn clear(&self) {
+        self.messages.lock().expect("Memory mailer lock poisoned").clear();
+    }
+}
+
+impl Mailer for MemoryMailer {
+    async fn send(&self, message: Message) -> Result<()> {
+        self.messages.lock().expect("Memory mailer lock poisoned").push(message);
+        Ok(())
+    }
+}
@@ -1,49 +1,87 @@
-use std::sync::OnceLock;
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, SystemTime};
 
-use anyhow::{anyhow, Result};
-use lettre::message::header::ContentType;
-use lettre::message::{Mailbox as LettreMailBox, MessageBuilder};
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::transport::smtp::client::Tls;
-use lettre::{Message, SmtpTransport, Transport};
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use lettre::message::header::{HeaderName, HeaderValue};
+use lettre::message::{Mailbox as LettreMailBox, MultiPart, MultiPartBuilder, SinglePart};
+use lettre::Message;
+use serde::{Deserialize, Serialize};
 
+use crate::mail::attachment::{Attachment, Source};
+use crate::utils::future::SendFuture;
 use crate::view::{View, ViewData};
 
-static GLOBAL_MAILER: OnceLock<SmtpTransport> = OnceLock::new();
+pub(crate) mod attachment;
+pub mod dkim;
+pub mod fake;
+pub mod file;
+pub mod memory;
+pub mod preview;
+pub mod queue;
+pub mod sendmail;
+pub mod smtp;
 
-pub struct SMTP;
+pub const DEFAULT_MAILER: &str = "default";
 
-impl SMTP {
-    pub fn init(
-        host: impl Into<String>,
-        port: u16,
-        username: impl Into<String>,
-        password: impl Into<String>,
-        tls: bool
-    ) -> Result<()> {
-        let mut builder = SmtpTransport::relay(&host.into())?
-            .port(port)
-            .credentials(Credentials::new(username.into(), password.into()));
+static GLOBAL_MAILERS: LazyLock<RwLock<HashMap<String, Arc<dyn MailerErasure>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
 
-        if !tls {
-            builder = builder.tls(Tls::None);
-        }
+/// Delivers built messages. Registered by name with `Server::mailer`; `Mail` uses the
+/// `default` one unless told otherwise with `Mail::mailer`.
+#[allow(async_fn_in_trait)]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: Message) -> Result<()>;
+
+    /// Checks the transport can deliver, e.g. that the SMTP server accepts connections.
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+trait MailerErasure: Send + Sync {
+    fn send<'a>(&'a self, message: Message) -> BoxFuture<'a, Result<()>>;
+    fn ping<'a>(&'a self) -> BoxFuture<'a, Result<()>>;
+}
 
-        GLOBAL_MAILER
-            .set(builder.build())
-            .map_err(|_| anyhow!("Global mailer already initialized"))
+impl<T: Mailer + 'static> MailerErasure for T {
+    fn send<'a>(&'a self, message: Message) -> BoxFuture<'a, Result<()>> {
+        Box::pin(SendFuture(Mailer::send(self, message)))
     }
 
-    #[inline]
-    pub fn global() -> Result<&'static SmtpTransport> {
-        GLOBAL_MAILER
-            .get()
-            .ok_or_else(|| anyhow!("Mailer has not been initialized. Call SMTP::init first."))
+    fn ping<'a>(&'a self) -> BoxFuture<'a, Result<()>> {
+        Box::pin(SendFuture(Mailer::ping(self)))
     }
 }
 
-#[derive(Clone, Debug)]
+pub fn add(name: impl Into<String>, mailer: impl Mailer + 'static) {
+    GLOBAL_MAILERS
+        .write()
+        .expect("Mailer registry lock poisoned")
+        .insert(name.into(), Arc::new(mailer));
+}
+
+fn get_mailer(name: &str) -> Result<Arc<dyn MailerErasure>> {
+    GLOBAL_MAILERS
+        .read()
+        .expect("Mailer registry lock poisoned")
+        .get(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Mailer '{name}' not found"))
+}
+
+pub async fn send(mailer: &str, message: Message) -> Result<()> {
+    get_mailer(mailer)?.send(message).await
+}
+
+pub async fn ping(mailer: &str) -> Result<()> {
+    get_mailer(mailer)?.ping().await
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Mailbox {
     pub email: String,
     pub name: Option<String>,
@@ -58,14 +96,34 @@ impl Mailbox {
     }
 
     fn to_lettre(&self) -> Result<LettreMailBox> {
-        let address = self.email.parse()?;
+        let address = self
+            .email
+            .parse()
+            .with_context(|| format!("Invalid email address {}", self.email))?;
+
         Ok(LettreMailBox::new(self.name.clone(), address))
     }
 }
 
+/// A message being composed. It is plain data until it is sent, so it can be queued and
+/// stored; attachments from files and storages are only read at that point.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Mail {
-    builder: MessageBuilder,
-    body: Vec<u8>,
+    from: Option<Mailbox>,
+    reply_to: Option<Mailbox>,
+    sender: Option<Mailbox>,
+    to: Vec<Mailbox>,
+    cc: Vec<Mailbox>,
+    bcc: Vec<Mailbox>,
+    date: Option<SystemTime>,
+    subject: Option<String>,
+    text: Option<Vec<u8>>,
+    html: Option<Vec<u8>>,
+    headers: Vec<(String, String)>,
+    attachments: Vec<Attachment>,
+    mailer: String,
+    /// Why the template given to `view` failed to render, reported when the mail is sent.
+    error: Option<String>,
 }
 
 impl Default for Mail {
@@ -77,91 +135,398 @@ impl Default for Mail {
 impl Mail {
     pub fn new() -> Self {
         Self {
-            builder: Message::builder(),
-            body: Vec::new(),
+            from: None,
+            reply_to: None,
+            sender: None,
+            to: Vec::new(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            date: None,
+            subject: None,
+            text: None,
+            html: None,
+            headers: Vec::new(),
+            attachments: Vec::new(),
+            mailer: String::from(DEFAULT_MAILER),
+            error: None,
         }
     }
 
+    /// Sends through the mailer registered under `name` instead of `default`.
+    pub fn mailer(mut self, name: impl Into<String>) -> Self {
+        self.mailer = name.into();
+        self
+    }
+
     pub fn from(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
-        let mb = LettreMailBox::new(name.map(Into::into), email.into().parse().unwrap());
-        self.builder = self.builder.from(mb);
+        self.from = Some(Mailbox::new(email, name));
         self
     }
 
     pub fn reply_to(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
-        let mb = LettreMailBox::new(name.map(Into::into), email.into().parse().unwrap());
-        self.builder = self.builder.reply_to(mb);
+        self.reply_to = Some(Mailbox::new(email, name));
         self
     }
 
     pub fn sender(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
-        let mb = LettreMailBox::new(name.map(Into::into), email.into().parse().unwrap());
-        self.builder = self.builder.sender(mb);
+        self.sender = Some(Mailbox::new(email, name));
+        self
+    }
+
+    /// Adds a recipient for `queue` and `later`; `send` takes its recipients directly.
+    pub fn to(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
+        self.to.push(Mailbox::new(email, name));
         self
     }
 
     pub fn cc(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
-        let mb = LettreMailBox::new(name.map(Into::into), email.into().parse().unwrap());
-        self.builder = self.builder.cc(mb);
+        self.cc.push(Mailbox::new(email, name));
         self
     }
 
     pub fn bcc(mut self, email: impl Into<String>, name: Option<&str>) -> Self {
-        let mb = LettreMailBox::new(name.map(Into::into), email.into().parse().unwrap());
-        self.builder = self.builder.bcc(mb);
+        self.bcc.push(Mailbox::new(email, name));
         self
     }
 
     pub fn date(mut self) -> Self {
-        self.builder = self.builder.date_now();
+        self.date = Some(SystemTime::now());
         self
     }
 
     pub fn date_at(mut self, time: SystemTime) -> Self {
-        self.builder = self.builder.date(time);
+        self.date = Some(time);
         self
     }
 
     pub fn subject(mut self, subject: impl Into<String>) -> Self {
-        self.builder = self.builder.subject(subject);
+        self.subject = Some(subject.into());
         self
     }
 
+    /// Sets the plain text body; with an HTML body too, both are sent as alternatives.
     pub fn text(mut self, text: impl Into<Vec<u8>>) -> Self {
-        self.builder = self.builder.header(ContentType::TEXT_PLAIN);
-        self.body = text.into();
+        self.text = Some(text.into());
         self
     }
 
     pub fn html(mut self, html: impl Into<Vec<u8>>) -> Self {
-        self.builder = self.builder.header(ContentType::TEXT_HTML);
-        self.body = html.into();
+        self.html = Some(html.into());
+        self
+    }
+
+    /// Adds a header such as `List-Unsubscribe` or `X-Campaign`.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Attaches `content` as `name`, typed from its extension unless `content_type` is given.
+    pub fn attach(mut self, name: impl Into<String>, content_type: Option<&str>, content: impl Into<Bytes>) -> Self {
+        let attachment = Attachment::new(name, content_type.map(Into::into), Source::Bytes(content.into()));
+        self.attachments.push(attachment);
+        self
+    }
+
+    /// Attaches a file, read when the mail is sent.
+    pub fn attach_path(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = file_name(&path.to_string_lossy());
+
+        self.attachments.push(Attachment::new(name, None, Source::Path(path)));
+        self
+    }
+
+    /// Attaches a file from a storage registered with `Server::storage`, read when the mail
+    /// is sent.
+    pub fn attach_from_storage(mut self, storage: impl Into<String>, filename: impl Into<String>) -> Self {
+        let filename = filename.into();
+        let source = Source::Storage { storage: storage.into(), filename: filename.clone() };
+
+        self.attachments.push(Attachment::new(file_name(&filename), None, source));
         self
     }
 
-    pub fn view(self, path: impl Into<String>, template: impl Into<String>, data: Option<ViewData>) -> Self {
-        let view_bytes = View::render(path, template, data).unwrap();
-        self.html(view_bytes)
+    /// Embeds an image the HTML body shows with `<img src="cid:{content_id}">`.
+    pub fn embed(mut self, content_id: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = file_name(&path.to_string_lossy());
+
+        self.attachments.push(Attachment::new(name, None, Source::Path(path)).inline(content_id));
+        self
+    }
+
+    pub fn embed_bytes(mut self, content_id: impl Into<String>, content_type: &str, content: impl Into<Bytes>) -> Self {
+        let content_id = content_id.into();
+        let attachment = Attachment::new(content_id.clone(), Some(content_type.into()), Source::Bytes(content.into()));
+
+        self.attachments.push(attachment.inline(content_id));
+        self
+    }
+
+    /// Renders the HTML body from a template, which shows images added with `embed` through
+    /// `cid:` URLs. A template that fails to render makes sending the mail fail.
+    pub fn view(mut self, path: impl Into<String>, template: impl Into<String>, data: Option<ViewData>) -> Self {
+        match View::render(path, template, data) {
+            Ok(view_bytes) => self.html(view_bytes),
+            Err(err) => {
+                self.error = Some(format!("{:#}", err));
+                self
+            }
+        }
+    }
+
+    /// The message `recipient` would receive.
+    pub async fn build(&self, recipient: &Mailbox) -> Result<Message> {
+        self.message(recipient, true).await
+    }
+
+    /// The message for `recipient`, carrying the `Cc` and `Bcc` recipients when `copies` is set.
+    async fn message(&self, recipient: &Mailbox, copies: bool) -> Result<Message> {
+        if let Some(error) = &self.error {
+            bail!("Failed to render the mail view: {}", error);
+        }
+
+        let mut builder = Message::builder().to(recipient.to_lettre()?);
+
+        if let Some(from) = &self.from {
+            builder = builder.from(from.to_lettre()?);
+        }
+
+        if let Some(reply_to) = &self.reply_to {
+            builder = builder.reply_to(reply_to.to_lettre()?);
+        }
+
+        if let Some(sender) = &self.sender {
+            builder = builder.sender(sender.to_lettre()?);
+        }
+
+        if copies {
+            for cc in &self.cc {
+                builder = builder.cc(cc.to_lettre()?);
+            }
+
+            for bcc in &self.bcc {
+                builder = builder.bcc(bcc.to_lettre()?);
+            }
+        }
+
+        if let Some(date) = self.date {
+            builder = builder.date(date);
+        }
+
+        if let Some(subject) = &self.subject {
+            builder = builder.subject(subject.clone());
+        }
+
+        for (name, value) in &self.headers {
+            let header = HeaderName::new_from_ascii(name.clone()).with_context(|| format!("Invalid mail header name {name}"))?;
+            builder = builder.raw_header(HeaderValue::new(header, value.clone()));
+        }
+
+        let message = match self.body().await? {
+            Part::Single(part) => builder.singlepart(part)?,
+            Part::Multi(part) => builder.multipart(part)?,
+        };
+
+        Ok(message)
     }
 
-    pub fn send_to_many(&self, recipients: &[Mailbox]) -> Result<()> {
-        let transport = SMTP::global()?;
+    /// Text and HTML as alternatives, the HTML related to its inline images, and everything
+    /// mixed with the attachments.
+    async fn body(&self) -> Result<Part> {
+        let mut inline = Vec::new();
+        let mut attached = Vec::new();
+
+        for attachment in &self.attachments {
+            match attachment.content_id {
+                Some(_) => inline.push(attachment.part().await?),
+                None => attached.push(attachment.part().await?),
+            }
+        }
+
+        let html = self.html.clone().map(|html| {
+            let html = SinglePart::html(html);
+
+            match inline.is_empty() {
+                true => Part::Single(html),
+                false => Part::Multi(inline.into_iter().fold(MultiPart::related().singlepart(html), MultiPart::singlepart)),
+            }
+        });
+
+        let body = match (self.text.clone(), html) {
+            (Some(text), Some(html)) => Part::Multi(html.append_to(MultiPart::alternative().singlepart(SinglePart::plain(text)))),
+            (None, Some(html)) => html,
+            (text, None) => Part::Single(SinglePart::plain(text.unwrap_or_default())),
+        };
+
+        if attached.is_empty() {
+            return Ok(body);
+        }
+
+        Ok(Part::Multi(attached.into_iter().fold(body.start(MultiPart::mixed()), MultiPart::singlepart)))
+    }
 
-        for recipient in recipients {
-            let lettre_mb = recipient.to_lettre()?;
-            let message = self
-                .builder
-                .clone()
-                .to(lettre_mb)
-                .body(self.body.clone())?;
+    /// Sends a separate message to each recipient. Only the first one goes to the `Cc` and
+    /// `Bcc` recipients too, so they get a single copy.
+    pub async fn send_to_many(&self, recipients: &[Mailbox]) -> Result<()> {
+        let mailer = get_mailer(&self.mailer)?;
 
-            transport.send(&message)?;
+        for (i, recipient) in recipients.iter().enumerate() {
+            mailer.send(self.message(recipient, i == 0).await?).await?;
         }
 
         Ok(())
     }
 
-    pub fn send(&self, email: impl Into<String>, name: Option<&str>) -> Result<()> {
-        self.send_to_many(&[Mailbox::new(email, name)])
+    pub async fn send(&self, email: impl Into<String>, name: Option<&str>) -> Result<()> {
+        self.send_to_many(&[Mailbox::new(email, name)]).await
+    }
+
+    /// Hands the mail to the queue's background worker, one job per `to` recipient, and
+    /// returns once it is stored; failed deliveries are retried. See `MailQueue`.
+    pub async fn queue(&self) -> Result<()> {
+        queue::push(self, Duration::ZERO).await
+    }
+
+    /// Like `queue`, delivering once `delay` has passed.
+    pub async fn later(&self, delay: Duration) -> Result<()> {
+        queue::push(self, delay).await
+    }
+}
+
+enum Part {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+impl Part {
+    fn start(self, builder: MultiPartBuilder) -> MultiPart {
+        match self {
+            Part::Single(part) => builder.singlepart(part),
+            Part::Multi(part) => builder.multipart(part),
+        }
+    }
+
+    fn append_to(self, multipart: MultiPart) -> MultiPart {
+        match self {
+            Part::Single(part) => multipart.singlepart(part),
+            Part::Multi(part) => multipart.multipart(part),
+        }
+    }
+}
+
+fn file_name(path: &str) -> String {
+    path.rsplit(['/', '\\']).next().unwrap_or(path).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mail::memory::MemoryMailer;
+
+    #[tokio::test]
+    async fn copies_are_sent_once_for_many_recipients() {
+        let mailer = MemoryMailer::new();
+        add("mail-test-copies", mailer.clone());
+
+        let recipients = [Mailbox::new("jane@example.com", None), Mailbox::new("john@example.com", None)];
+
+        Mail::new()
+            .mailer("mail-test-copies")
+            .from("app@example.com", None)
+            .cc("boss@example.com", None)
+            .bcc("audit@example.com", None)
+            .text("Hi")
+            .send_to_many(&recipients)
+            .await
+            .unwrap();
+
+        let recipients = |message: &Message| {
+            message.envelope().to().iter().map(|address| address.to_string()).collect::<Vec<_>>()
+        };
+        let messages = mailer.messages();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(recipients(&messages[0]), ["jane@example.com", "boss@example.com", "audit@example.com"]);
+        assert_eq!(recipients(&messages[1]), ["john@example.com"]);
+    }
+
+    #[tokio::test]
+    async fn view_errors_fail_the_send() {
+        let mailer = MemoryMailer::new();
+        add("mail-test-view", mailer.clone());
+
+        let err = Mail::new()
+            .mailer("mail-test-view")
+            .from("app@example.com", None)
+            .view("views/missing", "welcome.html", None)
+            .send("jane@example.com", None)
+            .await
+            .unwrap_err();
+
+        assert!(err.to_string().starts_with("Failed to render the mail view"));
+        assert!(mailer.messages().is_empty());
+    }
+
+    fn formatted(message: &Message) -> String {
+        String::from_utf8(message.formatted()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn text_and_html_are_alternatives() {
+        let message = Mail::new()
+            .from("app@example.com", None)
+            .text("Hi Jane")
+            .html("<p>Hi Jane</p>")
+            .build(&Mailbox::new("jane@example.com", None))
+            .await
+            .unwrap();
+        let formatted = formatted(&message);
+
+        assert!(formatted.contains("Content-Type: multipart/alternative"));
+        assert!(formatted.find("Content-Type: text/plain") < formatted.find("Content-Type: text/html"));
+    }
+
+    #[tokio::test]
+    async fn attachments_and_inline_images_are_nested() {
+        let message = Mail::new()
+            .from("app@example.com", None)
+            .html(r#"<img src="cid:logo">"#)
+            .embed_bytes("logo", "image/png", &b"png"[..])
+            .attach("report.pdf", None, &b"pdf"[..])
+            .build(&Mailbox::new("jane@example.com", None))
+            .await
+            .unwrap();
+        let formatted = formatted(&message);
+
+        assert!(formatted.contains("Content-Type: multipart/mixed"));
+        assert!(formatted.find("multipart/related") < formatted.find("Content-ID: <logo>"));
+        assert!(formatted.contains("Content-Disposition: inline"));
+        assert!(formatted.contains("Content-Type: application/pdf"));
+        assert!(formatted.contains(r#"Content-Disposition: attachment; filename="report.pdf""#));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn custom_headers_are_added() {
+        let mail = Mail::new().from("app@example.com", None).text("Hi").header("X-Campaign", "launch");
+        let recipient = Mailbox::new("jane@example.com", None);
+
+        assert!(formatted(&mail.build(&recipient).await.unwrap()).contains("X-Campaign: launch"));
+
+        let err = mail.header("Bad Header", "value").build(&recipient).await.unwrap_err();
+        assert_eq!(err.to_string(), "Invalid mail header name Bad Header");
+    }
+
+    #[tokio::test]
+    async fn missing_attachment_files_fail_the_build() {
+        let err = Mail::new()
+            .from("app@example.com", None)
+            .attach_path("missing/report.pdf")
+            .build(&Mailbox::new("jane@example.com", None))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "Failed to read attachment missing/report.pdf");
+    }
+}
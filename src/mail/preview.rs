@@ -0,0 +1,255 @@
+use std::sync::{Arc, LazyLock, RwLock};
+
+use crate::{
+    mail::{Mail, Mailbox},
+    response::Response,
+    view::debug::escape,
+};
+
+pub const DEFAULT_PREVIEW_PATH: &str = "/_mail";
+
+pub type Preview = dyn Fn() -> Mail + Send + Sync;
+
+type Previews = Vec<(String, Arc<Preview>)>;
+
+static GLOBAL_PREVIEWS: LazyLock<RwLock<Previews>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
+/// Lists a mail on the preview page, built with sample data by `mail`.
+pub fn add<F>(name: impl Into<String>, mail: F)
+where
+    F: Fn() -> Mail + Send + Sync + 'static,
+{
+    GLOBAL_PREVIEWS
+        .write()
+        .expect("Mail preview registry lock poisoned")
+        .push((name.into(), Arc::new(mail)));
+}
+
+fn find(name: &str) -> Option<Mail> {
+    let preview = GLOBAL_PREVIEWS
+        .read()
+        .expect("Mail preview registry lock poisoned")
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, preview)| Arc::clone(preview))?;
+
+    Some(preview())
+}
+
+pub(crate) fn index(base: &str, res: Response) -> Response {
+    let items: String = GLOBAL_PREVIEWS
+        .read()
+        .expect("Mail preview registry lock poisoned")
+        .iter()
+        .map(|(name, _)| format!(r#"<li><a href="{}/{}">{}</a></li>"#, base, escape(name), escape(name)))
+        .collect();
+
+    let items = if items.is_empty() {
+        String::from(r#"<p class="empty">No mail previews, add them with <code>mail::preview::add</code>.</p>"#)
+    } else {
+        format!("<ul>{}</ul>", items)
+    };
+
+    res.html(page("Mail previews", &items))
+}
+
+/// The headers, the text body and a frame showing the HTML body of the preview `name`.
+pub(crate) fn show(base: &str, name: &str, res: Response) -> Response {
+    let Some(mail) = find(name) else {
+        return res.status_code(404).html(page("Mail preview not found", &format!("<p>No preview named {}.</p>", escape(name))));
+    };
+
+    let mailboxes = |mailboxes: Vec<&Mailbox>| {
+        mailboxes
+            .iter()
+            .map(|mailbox| match &mailbox.name {
+                Some(name) => format!("{} <{}>", name, mailbox.email),
+                None => mailbox.email.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let rows = [
+        ("From", mailboxes(mail.from.iter().collect())),
+        ("Reply-To", mailboxes(mail.reply_to.iter().collect())),
+        ("To", mailboxes(mail.to.iter().collect())),
+        ("Cc", mailboxes(mail.cc.iter().collect())),
+        ("Bcc", mailboxes(mail.bcc.iter().collect())),
+        ("Subject", mail.subject.clone().unwrap_or_default()),
+        ("Mailer", mail.mailer.clone()),
+        ("View error", mail.error.clone().unwrap_or_default()),
+        (
+            "Attachments",
+            mail.attachments
+                .iter()
+                .filter(|attachment| attachment.content_id.is_none())
+                .map(|attachment| attachment.name.clone())
+                .collect::<Vec<_>>()
+                .join(", "),
+        ),
+    ];
+
+    let table: String = rows
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("<tr><td>{}</td><td>{}</td></tr>", key, escape(value)))
+        .collect();
+
+    let html = match mail.html {
+        Some(_) => format!(r#"<iframe src="{}/{}/html"></iframe>"#, base, escape(name)),
+        None => String::from(r#"<p class="empty">No HTML body</p>"#),
+    };
+
+    let text = match &mail.text {
+        Some(text) => format!("<pre>{}</pre>", escape(&String::from_utf8_lossy(text))),
+        None => String::from(r#"<p class="empty">No text body</p>"#),
+    };
+
+    let content = format!(
+        r#"<p><a href="{base}">&larr; All previews</a></p>
+        <table>{table}</table>
+        <h2>HTML</h2>
+        {html}
+        <h2>Text</h2>
+        {text}"#,
+    );
+
+    res.html(page(name, &content))
+}
+
+/// The HTML body alone, with `cid:` images pointing at the preview's inline images.
+pub(crate) fn html(base: &str, name: &str, res: Response) -> Response {
+    let Some(html) = find(name).and_then(|mail| mail.html) else {
+        return res.status_code(404);
+    };
+
+    let html = String::from_utf8_lossy(&html).replace("cid:", &format!("{}/{}/cid/", base, name));
+
+    res.html(html)
+}
+
+pub(crate) async fn inline(name: &str, content_id: &str, res: Response) -> Response {
+    let attachment = find(name).and_then(|mail| {
+        mail.attachments
+            .into_iter()
+            .find(|attachment| attachment.content_id.as_deref() == Some(content_id))
+    });
+
+    let Some(attachment) = attachment else {
+        return res.status_code(404);
+    };
+
+    match attachment.content().await {
+        Ok((content, content_type)) => res.set_header("Content-Type", content_type).body(content),
+        Err(err) => res.status_code(500).body(format!("{:#}", err)),
+    }
+}
+
+fn page(title: &str, content: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>{title}</title>
+    <style>
+        body {{ font-family: -apple-system, "Segoe UI", sans-serif; margin: 0; background: #f6f7f9; color: #1f2328; }}
+        header {{ background: #1f2328; color: #fff; padding: 20px 32px; }}
+        header h1 {{ margin: 0; font-size: 20px; }}
+        main {{ padding: 24px 32px; }}
+        h2 {{ font-size: 15px; margin: 24px 0 8px; }}
+        a {{ color: #0969da; }}
+        li {{ margin: 6px 0; }}
+        pre {{ background: #fff; border: 1px solid #d0d7de; border-radius: 6px; padding: 12px; overflow: auto; font-size: 13px; line-height: 1.5; white-space: pre-wrap; }}
+        iframe {{ width: 100%; height: 640px; background: #fff; border: 1px solid #d0d7de; border-radius: 6px; }}
+        table {{ border-collapse: collapse; width: 100%; background: #fff; font-size: 13px; }}
+        td {{ border: 1px solid #d0d7de; padding: 6px 10px; vertical-align: top; }}
+        td:first-child {{ width: 15%; font-weight: 600; }}
+        .empty {{ color: #8c959f; }}
+    </style>
+</head>
+<body>
+    <header>
+        <h1>{title}</h1>
+    </header>
+    <main>
+        {content}
+    </main>
+</body>
+</html>"#,
+        title = escape(title),
+        content = content,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(res: &Response) -> String {
+        String::from_utf8_lossy(&res.content).to_string()
+    }
+
+    #[test]
+    fn index_lists_the_previews() {
+        add("preview-test-<index>", Mail::new);
+
+        let res = index("/_mail", Response::new());
+
+        assert!(body(&res).contains(r#"<a href="/_mail/preview-test-&lt;index&gt;">"#));
+    }
+
+    #[test]
+    fn show_renders_the_headers_and_bodies() {
+        add("preview-test-show", || {
+            Mail::new()
+                .from("app@example.com", Some("App"))
+                .subject("Welcome!")
+                .text("Hi <Jane>")
+                .html("<p>Hi</p>")
+                .attach("terms.pdf", None, &b"pdf"[..])
+        });
+
+        let res = show("/_mail", "preview-test-show", Response::new());
+        let body = body(&res);
+
+        assert_eq!(res.status_code, 200);
+        assert!(body.contains("<tr><td>From</td><td>App &lt;app@example.com&gt;</td></tr>"));
+        assert!(body.contains("<tr><td>Attachments</td><td>terms.pdf</td></tr>"));
+        assert!(!body.contains("<td>Cc</td>"));
+        assert!(body.contains(r#"<iframe src="/_mail/preview-test-show/html"></iframe>"#));
+        assert!(body.contains("<pre>Hi &lt;Jane&gt;</pre>"));
+
+        assert_eq!(show("/_mail", "preview-test-missing", Response::new()).status_code, 404);
+    }
+
+    #[test]
+    fn show_reports_view_errors() {
+        add("preview-test-view", || Mail::new().view("views/missing", "welcome.html", None));
+
+        let body = body(&show("/_mail", "preview-test-view", Response::new()));
+
+        assert!(body.contains("<tr><td>View error</td>"));
+        assert!(body.contains("No HTML body"));
+    }
+
+    #[tokio::test]
+    async fn html_points_inline_images_at_the_preview() {
+        add("preview-test-inline", || {
+            Mail::new()
+                .html(r#"<img src="cid:logo">"#)
+                .embed_bytes("logo", "image/png", &b"png"[..])
+        });
+
+        let res = html("/_mail", "preview-test-inline", Response::new());
+        assert_eq!(body(&res), r#"<img src="/_mail/preview-test-inline/cid/logo">"#);
+
+        let res = inline("preview-test-inline", "logo", Response::new()).await;
+        assert_eq!(res.header("Content-Type"), "image/png");
+        assert_eq!(body(&res), "png");
+
+        assert_eq!(inline("preview-test-inline", "missing", Response::new()).await.status_code, 404);
+        assert_eq!(html("/_mail", "preview-test-missing", Response::new()).status_code, 404);
+    }
+}
@@ -0,0 +1,64 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use anyhow::Result;
+
+use crate::mail::queue::{Job, MailStore};
+
+/// Keeps queued mail in process memory; anything not yet delivered is lost on restart.
+#[derive(Default)]
+pub struct MemoryQueue {
+    jobs: Mutex<HashMap<String, Job>>,
+}
+
+impl MemoryQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MailStore for MemoryQueue {
+    async fn save(&self, job: &Job) -> Result<()> {
+        self.jobs.lock().expect("Mail queue lock poisoned").insert(job.id.clone(), job.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        self.jobs.lock().expect("Mail queue lock poisoned").remove(id);
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Job>> {
+        Ok(self.jobs.lock().expect("Mail queue lock poisoned").get(id).cloned())
+    }
+
+    async fn due(&self, now: i64, limit: usize) -> Result<Vec<Job>> {
+        let mut jobs: Vec<Job> = self
+            .jobs
+            .lock()
+            .expect("Mail queue lock poisoned")
+            .values()
+            .filter(|job| !job.dead && job.available_at <= now)
+            .cloned()
+            .collect();
+
+        jobs.sort_by_key(|job| job.available_at);
+        jobs.truncate(limit);
+
+        Ok(jobs)
+    }
+
+    async fn dead(&self) -> Result<Vec<Job>> {
+        let mut jobs: Vec<Job> = self
+            .jobs
+            .lock()
+            .expect("Mail queue lock poisoned")
+            .values()
+            .filter(|job| job.dead)
+            .cloned()
+            .collect();
+
+        jobs.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(jobs)
+    }
+}
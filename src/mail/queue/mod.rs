@@ -0,0 +1,520 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, LazyLock, Mutex, OnceLock, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Result, anyhow, bail};
+use futures::{StreamExt, future::BoxFuture, stream};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+use ulid::Ulid;
+
+use crate::{
+    loggers,
+    mail::{Mail, Mailbox, queue::memory::MemoryQueue},
+    utils::future::SendFuture,
+};
+
+pub mod memory;
+pub mod sqlite;
+
+static GLOBAL_QUEUE: LazyLock<RwLock<Option<Arc<MailQueue>>>> = LazyLock::new(|| RwLock::new(None));
+
+/// A message waiting to be delivered to one recipient.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: String,
+    pub mail: Mail,
+    pub recipient: Mailbox,
+    pub attempts: u32,
+    /// Unix time in milliseconds before which the job is not tried.
+    pub available_at: i64,
+    pub last_error: Option<String>,
+    /// Set once the job ran out of attempts; dead jobs are kept until retried or discarded.
+    pub dead: bool,
+}
+
+/// Where queued mail is kept between attempts, so it survives restarts with a persistent store.
+#[allow(async_fn_in_trait)]
+pub trait MailStore: Send + Sync {
+    /// Inserts `job`, or replaces the job with the same ID.
+    async fn save(&self, job: &Job) -> Result<()>;
+    async fn delete(&self, id: &str) -> Result<()>;
+    async fn get(&self, id: &str) -> Result<Option<Job>>;
+    /// Live jobs available at `now`, the earliest first, at most `limit` of them.
+    async fn due(&self, now: i64, limit: usize) -> Result<Vec<Job>>;
+    async fn dead(&self) -> Result<Vec<Job>>;
+}
+
+trait MailStoreErasure: Send + Sync {
+    fn save<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<()>>;
+    fn delete<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<()>>;
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Job>>>;
+    fn due<'a>(&'a self, now: i64, limit: usize) -> BoxFuture<'a, Result<Vec<Job>>>;
+    fn dead<'a>(&'a self) -> BoxFuture<'a, Result<Vec<Job>>>;
+}
+
+impl<T: MailStore + 'static> MailStoreErasure for T {
+    fn save<'a>(&'a self, job: &'a Job) -> BoxFuture<'a, Result<()>> {
+        Box::pin(SendFuture(MailStore::save(self, job)))
+    }
+
+    fn delete<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(SendFuture(MailStore::delete(self, id)))
+    }
+
+    fn get<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Result<Option<Job>>> {
+        Box::pin(SendFuture(MailStore::get(self, id)))
+    }
+
+    fn due<'a>(&'a self, now: i64, limit: usize) -> BoxFuture<'a, Result<Vec<Job>>> {
+        Box::pin(SendFuture(MailStore::due(self, now, limit)))
+    }
+
+    fn dead<'a>(&'a self) -> BoxFuture<'a, Result<Vec<Job>>> {
+        Box::pin(SendFuture(MailStore::dead(self)))
+    }
+}
+
+/// Delivers queued mail from a background worker, retrying failures with exponential backoff.
+/// Run a single worker per store, or several would deliver the same jobs.
+pub struct MailQueue {
+    store: Arc<dyn MailStoreErasure>,
+    max_attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    concurrency: usize,
+    poll_interval: Duration,
+    wake: Notify,
+    worker: OnceLock<()>,
+    /// Jobs delivered but not yet deleted from the store, which must not be sent again.
+    sent: Mutex<HashSet<String>>,
+}
+
+impl Default for MailQueue {
+    fn default() -> Self {
+        Self::new(MemoryQueue::new())
+    }
+}
+
+impl MailQueue {
+    pub fn new(store: impl MailStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            max_attempts: 5,
+            backoff: Duration::from_secs(30),
+            max_backoff: Duration::from_secs(60 * 60),
+            concurrency: 4,
+            poll_interval: Duration::from_secs(1),
+            wake: Notify::new(),
+            worker: OnceLock::new(),
+            sent: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Attempts before a job is moved to the dead letters.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait after the first failure, doubled after each further one up to `max`.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    async fn push(self: &Arc<Self>, mail: &Mail, delay: Duration) -> Result<()> {
+        if mail.to.is_empty() {
+            bail!("Queued mail has no recipients, add them with Mail::to");
+        }
+
+        if let Some(error) = &mail.error {
+            bail!("Failed to render the mail view: {}", error);
+        }
+
+        let available_at = now() + delay.as_millis() as i64;
+
+        // The `Cc` and `Bcc` recipients get a copy with the first job only.
+        let mut copy = mail.clone();
+
+        for recipient in &mail.to {
+            let job = Job {
+                id: Ulid::new().to_string(),
+                mail: copy.clone(),
+                recipient: recipient.clone(),
+                attempts: 0,
+                available_at,
+                last_error: None,
+                dead: false,
+            };
+
+            self.store.save(&job).await?;
+
+            copy.cc.clear();
+            copy.bcc.clear();
+        }
+
+        self.start();
+        self.wake.notify_one();
+
+        Ok(())
+    }
+
+    /// Spawns the worker unless it is running. Must be called inside the runtime.
+    pub(crate) fn start(self: &Arc<Self>) {
+        self.worker.get_or_init(|| {
+            let queue = Arc::clone(self);
+
+            tokio::spawn(async move { queue.work().await });
+        });
+    }
+
+    async fn work(&self) {
+        loop {
+            if self.poll().await {
+                continue;
+            }
+
+            tokio::select! {
+                _ = self.wake.notified() => {},
+                _ = tokio::time::sleep(self.poll_interval) => {},
+            }
+        }
+    }
+
+    /// Delivers the jobs that are due, returning whether to look for more right away.
+    async fn poll(&self) -> bool {
+        let jobs = match self.store.due(now(), self.concurrency * 8).await {
+            Ok(jobs) => jobs,
+            Err(err) => {
+                loggers::warn(format!("Failed to read the mail queue: {:#}", err));
+                return false;
+            }
+        };
+
+        if jobs.is_empty() {
+            return false;
+        }
+
+        let (sent, jobs): (Vec<Job>, Vec<Job>) = jobs.into_iter().partition(|job| self.is_sent(&job.id));
+
+        let mut stored = true;
+
+        for job in sent {
+            stored &= self.forget(&job.id).await;
+        }
+
+        let delivered: Vec<bool> = stream::iter(jobs)
+            .map(|job| self.deliver(job))
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+
+        stored && delivered.into_iter().all(|stored| stored)
+    }
+
+    async fn deliver(&self, mut job: Job) -> bool {
+        let err = match job.mail.send_to_many(std::slice::from_ref(&job.recipient)).await {
+            Ok(()) => {
+                self.sent.lock().expect("Mail queue lock poisoned").insert(job.id.clone());
+                return self.forget(&job.id).await;
+            }
+            Err(err) => err,
+        };
+
+        job.attempts += 1;
+        job.last_error = Some(format!("{:#}", err));
+
+        if job.attempts >= self.max_attempts {
+            job.dead = true;
+            loggers::warn(format!("Giving up on mail {} to {} after {} attempts: {:#}", job.id, job.recipient.email, job.attempts, err));
+        } else {
+            job.available_at = now() + self.delay(job.attempts).as_millis() as i64;
+        }
+
+        match self.store.save(&job).await {
+            Ok(()) => true,
+            Err(err) => {
+                loggers::warn(format!("Failed to update mail {} in the queue: {:#}", job.id, err));
+                false
+            }
+        }
+    }
+
+    /// Deletes a delivered job; until that succeeds it stays in `sent` and is skipped.
+    async fn forget(&self, id: &str) -> bool {
+        match self.store.delete(id).await {
+            Ok(()) => {
+                self.sent.lock().expect("Mail queue lock poisoned").remove(id);
+                true
+            }
+            Err(err) => {
+                loggers::warn(format!("Failed to remove sent mail {} from the queue: {:#}", id, err));
+                false
+            }
+        }
+    }
+
+    fn is_sent(&self, id: &str) -> bool {
+        self.sent.lock().expect("Mail queue lock poisoned").contains(id)
+    }
+
+    async fn retry(self: &Arc<Self>, id: &str) -> Result<()> {
+        let mut job = self
+            .store
+            .get(id)
+            .await?
+            .ok_or_else(|| anyhow!("Queued mail '{id}' not found"))?;
+
+        job.attempts = 0;
+        job.dead = false;
+        job.available_at = now();
+
+        self.store.save(&job).await?;
+        self.start();
+        self.wake.notify_one();
+
+        Ok(())
+    }
+
+    async fn discard(&self, id: &str) -> Result<()> {
+        self.store.delete(id).await?;
+        self.sent.lock().expect("Mail queue lock poisoned").remove(id);
+
+        Ok(())
+    }
+
+    fn delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Uses `queue` for mail queued from now on and starts its worker.
+pub(crate) fn install(queue: MailQueue) -> Arc<MailQueue> {
+    let queue = Arc::new(queue);
+
+    *GLOBAL_QUEUE.write().expect("Mail queue lock poisoned") = Some(Arc::clone(&queue));
+
+    queue
+}
+
+fn global() -> Arc<MailQueue> {
+    if let Some(queue) = GLOBAL_QUEUE.read().expect("Mail queue lock poisoned").as_ref() {
+        return Arc::clone(queue);
+    }
+
+    GLOBAL_QUEUE
+        .write()
+        .expect("Mail queue lock poisoned")
+        .get_or_insert_with(|| Arc::new(MailQueue::default()))
+        .clone()
+}
+
+pub(crate) async fn push(mail: &Mail, delay: Duration) -> Result<()> {
+    global().push(mail, delay).await
+}
+
+/// Jobs that ran out of attempts, with the last error of each.
+pub async fn dead_letters() -> Result<Vec<Job>> {
+    global().store.dead().await
+}
+
+/// Gives a dead job a fresh set of attempts, starting now.
+pub async fn retry(id: &str) -> Result<()> {
+    global().retry(id).await
+}
+
+pub async fn discard(id: &str) -> Result<()> {
+    global().discard(id).await
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+    use crate::mail::{self, memory::MemoryMailer};
+
+    /// A memory store whose deletes fail while `failing` is set.
+    #[derive(Default)]
+    struct FlakyStore {
+        jobs: MemoryQueue,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl MailStore for FlakyStore {
+        async fn save(&self, job: &Job) -> Result<()> {
+            MailStore::save(&self.jobs, job).await
+        }
+
+        async fn delete(&self, id: &str) -> Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                bail!("database is locked");
+            }
+
+            MailStore::delete(&self.jobs, id).await
+        }
+
+        async fn get(&self, id: &str) -> Result<Option<Job>> {
+            MailStore::get(&self.jobs, id).await
+        }
+
+        async fn due(&self, now: i64, limit: usize) -> Result<Vec<Job>> {
+            MailStore::due(&self.jobs, now, limit).await
+        }
+
+        async fn dead(&self) -> Result<Vec<Job>> {
+            MailStore::dead(&self.jobs).await
+        }
+    }
+
+    fn queue(store: impl MailStore + 'static) -> Arc<MailQueue> {
+        let queue = Arc::new(MailQueue::new(store).max_attempts(2));
+
+        // The tests drive the queue themselves instead of through the worker.
+        let _ = queue.worker.set(());
+
+        queue
+    }
+
+    fn mail(mailer: &str) -> Mail {
+        Mail::new()
+            .mailer(mailer)
+            .from("app@example.com", None)
+            .to("jane@example.com", None)
+            .subject("Hello")
+            .text("Hi Jane")
+    }
+
+    async fn only_job(queue: &MailQueue) -> Job {
+        let mut jobs = queue.store.due(i64::MAX, 10).await.unwrap();
+        jobs.extend(queue.store.dead().await.unwrap());
+
+        assert_eq!(jobs.len(), 1);
+        jobs.remove(0)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let queue = MailQueue::default().backoff(Duration::from_secs(30), Duration::from_secs(100));
+
+        assert_eq!(queue.delay(1), Duration::from_secs(30));
+        assert_eq!(queue.delay(2), Duration::from_secs(60));
+        assert_eq!(queue.delay(3), Duration::from_secs(100));
+        assert_eq!(queue.delay(40), Duration::from_secs(100));
+    }
+
+    #[tokio::test]
+    async fn failed_mail_is_dead_lettered_after_max_attempts() {
+        let queue = queue(MemoryQueue::new());
+        queue.push(&mail("queue-test-missing"), Duration::ZERO).await.unwrap();
+
+        assert!(queue.deliver(only_job(&queue).await).await);
+
+        let job = only_job(&queue).await;
+        assert_eq!(job.attempts, 1);
+        assert!(!job.dead);
+        assert!(job.available_at > now());
+        assert!(job.last_error.as_deref().unwrap().contains("not found"));
+
+        assert!(queue.deliver(job).await);
+
+        let dead = queue.store.dead().await.unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn dead_letters_can_be_retried_or_discarded() {
+        let queue = queue(MemoryQueue::new());
+        queue.push(&mail("queue-test-missing"), Duration::ZERO).await.unwrap();
+
+        let mut job = only_job(&queue).await;
+        job.dead = true;
+        job.attempts = 2;
+        job.available_at = now() + 60_000;
+        queue.store.save(&job).await.unwrap();
+
+        queue.retry(&job.id).await.unwrap();
+
+        let retried = only_job(&queue).await;
+        assert!(!retried.dead);
+        assert_eq!(retried.attempts, 0);
+        assert_eq!(queue.store.due(now(), 10).await.unwrap().len(), 1);
+
+        queue.discard(&job.id).await.unwrap();
+
+        assert!(queue.store.get(&job.id).await.unwrap().is_none());
+        assert!(queue.retry(&job.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn copies_go_with_the_first_job_only() {
+        let queue = queue(MemoryQueue::new());
+        let mail = mail("queue-test-missing").to("john@example.com", None).cc("boss@example.com", None);
+
+        queue.push(&mail, Duration::ZERO).await.unwrap();
+
+        let jobs = queue.store.due(i64::MAX, 10).await.unwrap();
+        let copies: Vec<usize> = jobs.iter().map(|job| job.mail.cc.len()).collect();
+
+        assert_eq!(jobs.len(), 2);
+        assert!(copies.contains(&1) && copies.contains(&0));
+    }
+
+    #[tokio::test]
+    async fn later_mail_waits_for_its_time() {
+        let queue = queue(MemoryQueue::new());
+        queue.push(&mail("queue-test-missing"), Duration::from_secs(60)).await.unwrap();
+
+        assert!(queue.store.due(now(), 10).await.unwrap().is_empty());
+        assert_eq!(queue.store.due(now() + 61_000, 10).await.unwrap().len(), 1);
+        assert!(!queue.poll().await);
+    }
+
+    #[tokio::test]
+    async fn sent_mail_is_not_resent_when_the_store_fails() {
+        let mailer = MemoryMailer::new();
+        mail::add("queue-test-sent", mailer.clone());
+
+        let store = FlakyStore::default();
+        let failing = Arc::clone(&store.failing);
+        failing.store(true, Ordering::SeqCst);
+
+        let queue = queue(store);
+        queue.push(&mail("queue-test-sent"), Duration::ZERO).await.unwrap();
+
+        // The delete fails, so the worker would wait a poll interval before the next round.
+        assert!(!queue.poll().await);
+        assert!(!queue.poll().await);
+        assert_eq!(mailer.messages().len(), 1);
+
+        failing.store(false, Ordering::SeqCst);
+
+        assert!(queue.poll().await);
+        assert!(queue.store.due(i64::MAX, 10).await.unwrap().is_empty());
+        assert_eq!(mailer.messages().len(), 1);
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result, anyhow};
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+
+use crate::mail::queue::{Job, MailStore};
+
+/// Keeps queued mail in a SQLite table, so it is delivered after a restart.
+pub struct SqliteQueue {
+    connection: Arc<Mutex<Connection>>,
+    table: String,
+}
+
+impl SqliteQueue {
+    pub fn new(path: impl Into<String>) -> Result<Self> {
+        Self::from_connection(Connection::open(path.into())?)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        let store = Self {
+            connection: Arc::new(Mutex::new(connection)),
+            table: "mail_queue".into(),
+        };
+
+        store.migrate()?;
+
+        Ok(store)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        self.connection
+            .lock()
+            .map_err(|_| anyhow!("Mail queue database lock poisoned"))?
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id TEXT PRIMARY KEY,
+                    payload TEXT NOT NULL,
+                    available_at INTEGER NOT NULL,
+                    dead INTEGER NOT NULL DEFAULT 0
+                );
+                CREATE INDEX IF NOT EXISTS {table}_due ON {table} (dead, available_at);",
+                table = self.table
+            ))
+            .context("Failed to create mail queue table")
+    }
+
+    async fn blocking<T, F>(&self, callback: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection, &str) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        let table = self.table.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| anyhow!("Mail queue database lock poisoned"))?;
+
+            callback(&connection, &table).map_err(|err| err.into())
+        })
+        .await?
+    }
+
+    async fn query(&self, sql: &'static str, values: Vec<i64>) -> Result<Vec<Job>> {
+        let payloads = self
+            .blocking(move |conn, table| {
+                let mut statement = conn.prepare(&sql.replace("{table}", table))?;
+                let rows = statement.query_map(params_from_iter(values), |row| row.get::<_, String>(0))?;
+
+                rows.collect::<rusqlite::Result<Vec<String>>>()
+            })
+            .await?;
+
+        payloads
+            .iter()
+            .map(|payload| serde_json::from_str(payload).context("Invalid queued mail"))
+            .collect()
+    }
+}
+
+impl MailStore for SqliteQueue {
+    async fn save(&self, job: &Job) -> Result<()> {
+        let id = job.id.clone();
+        let payload = serde_json::to_string(job)?;
+        let available_at = job.available_at;
+        let dead = job.dead;
+
+        self.blocking(move |conn, table| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO {table} (id, payload, available_at, dead) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(id) DO UPDATE SET payload = excluded.payload, available_at = excluded.available_at, dead = excluded.dead"
+                ),
+                params![id, payload, available_at, dead],
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+
+        self.blocking(move |conn, table| {
+            conn.execute(&format!("DELETE FROM {table} WHERE id = ?1"), params![id])
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Job>> {
+        let id = id.to_string();
+        let payload = self
+            .blocking(move |conn, table| {
+                conn.query_row(
+                    &format!("SELECT payload FROM {table} WHERE id = ?1"),
+                    params![id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+            })
+            .await?;
+
+        payload
+            .map(|payload| serde_json::from_str(&payload).context("Invalid queued mail"))
+            .transpose()
+    }
+
+    async fn due(&self, now: i64, limit: usize) -> Result<Vec<Job>> {
+        self.query(
+            "SELECT payload FROM {table} WHERE dead = 0 AND available_at <= ?1 ORDER BY available_at LIMIT ?2",
+            vec![now, limit as i64],
+        )
+        .await
+    }
+
+    async fn dead(&self) -> Result<Vec<Job>> {
+        self.query("SELECT payload FROM {table} WHERE dead = 1 ORDER BY id", Vec::new()).await
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use lettre::{AsyncSendmailTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::mail::Mailer;
+
+/// Hands messages to the local `sendmail` binary.
+pub struct SendmailMailer {
+    transport: AsyncSendmailTransport<Tokio1Executor>,
+}
+
+impl Default for SendmailMailer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SendmailMailer {
+    /// Runs `sendmail` from the `PATH`.
+    pub fn new() -> Self {
+        Self {
+            transport: AsyncSendmailTransport::new(),
+        }
+    }
+
+    /// Runs another compatible binary, e.g. `/usr/sbin/sendmail` or `msmtp`.
+    pub fn command(command: impl Into<String>) -> Self {
+        Self {
+            transport: AsyncSendmailTransport::new_with_command(command.into()),
+        }
+    }
+}
+
+impl Mailer for SendmailMailer {
+    async fn send(&self, message: Message) -> Result<()> {
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send mail with sendmail")
+    }
+}
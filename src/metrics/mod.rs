@@ -0,0 +1,206 @@
+use std::{
+    future::Future,
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+    core::Collector,
+};
+
+use crate::{request::Request, response::Response};
+
+pub const DEFAULT_METRICS_PATH: &str = "/metrics";
+
+/// Route label of requests no route matched, so unknown paths do not each get a series.
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+static BUILTIN: LazyLock<Builtin> = LazyLock::new(|| Builtin::register().expect("Failed to register the built-in metrics"));
+
+struct Builtin {
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    connections: IntGaugeVec,
+    session_duration: HistogramVec,
+    storage_duration: HistogramVec,
+}
+
+impl Builtin {
+    fn register() -> Result<Self> {
+        let builtin = Self {
+            requests: IntCounterVec::new(
+                Opts::new("flyer_http_requests_total", "HTTP requests answered."),
+                &["method", "route", "status"],
+            )?,
+            request_duration: HistogramVec::new(
+                HistogramOpts::new("flyer_http_request_duration_seconds", "Time to answer HTTP requests."),
+                &["method", "route", "status"],
+            )?,
+            connections: IntGaugeVec::new(
+                Opts::new("flyer_active_connections", "Connections currently open."),
+                &["protocol"],
+            )?,
+            session_duration: HistogramVec::new(
+                HistogramOpts::new("flyer_session_store_duration_seconds", "Time spent in session store operations."),
+                &["store", "operation"],
+            )?,
+            storage_duration: HistogramVec::new(
+                HistogramOpts::new("flyer_storage_duration_seconds", "Time spent in storage operations."),
+                &["storage", "operation", "result"],
+            )?,
+        };
+
+        REGISTRY.register(Box::new(builtin.requests.clone()))?;
+        REGISTRY.register(Box::new(builtin.request_duration.clone()))?;
+        REGISTRY.register(Box::new(builtin.connections.clone()))?;
+        REGISTRY.register(Box::new(builtin.session_duration.clone()))?;
+        REGISTRY.register(Box::new(builtin.storage_duration.clone()))?;
+
+        Ok(builtin)
+    }
+}
+
+/// Keeps a connection counted in `flyer_active_connections` until dropped.
+pub(crate) struct ConnectionGuard {
+    protocol: &'static str,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        BUILTIN.connections.with_label_values(&[self.protocol]).dec();
+    }
+}
+
+/// Counts an open connection of `protocol` (`http1`, `http2`, `http3` or `websocket`).
+pub(crate) fn connection(protocol: &'static str) -> ConnectionGuard {
+    BUILTIN.connections.with_label_values(&[protocol]).inc();
+
+    ConnectionGuard { protocol }
+}
+
+pub(crate) fn record_request(req: &Request, res: &Response, duration: Duration) {
+    // The pattern carries the method too; the label keeps only the path.
+    let route = req
+        .route
+        .as_deref()
+        .map(|route| route.split_once(' ').map(|(_, path)| path).unwrap_or(route))
+        .unwrap_or(UNMATCHED_ROUTE);
+
+    let status = res.status_code.to_string();
+    let labels = [req.method.as_str(), route, status.as_str()];
+
+    BUILTIN.requests.with_label_values(&labels).inc();
+    BUILTIN.request_duration.with_label_values(&labels).observe(duration.as_secs_f64());
+}
+
+pub(crate) async fn time_session<T>(store: &str, operation: &str, future: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let output = future.await;
+
+    BUILTIN
+        .session_duration
+        .with_label_values(&[store, operation])
+        .observe(started.elapsed().as_secs_f64());
+
+    output
+}
+
+pub(crate) async fn time_storage<T>(storage: &str, operation: &str, future: impl Future<Output = Result<T>>) -> Result<T> {
+    let started = Instant::now();
+    let output = future.await;
+    let result = if output.is_ok() { "ok" } else { "error" };
+
+    BUILTIN
+        .storage_duration
+        .with_label_values(&[storage, operation, result])
+        .observe(started.elapsed().as_secs_f64());
+
+    output
+}
+
+/// The registry `/metrics` exposes, for collectors built by hand.
+pub fn registry() -> &'static Registry {
+    &REGISTRY
+}
+
+pub fn register(collector: impl Collector + 'static) -> Result<()> {
+    REGISTRY
+        .register(Box::new(collector))
+        .context("Failed to register metric")
+}
+
+/// Creates and registers a counter; registering a name twice fails.
+pub fn counter(name: &str, help: &str, labels: &[&str]) -> Result<IntCounterVec> {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels)?;
+    register(counter.clone())?;
+
+    Ok(counter)
+}
+
+pub fn gauge(name: &str, help: &str, labels: &[&str]) -> Result<GaugeVec> {
+    let gauge = GaugeVec::new(Opts::new(name, help), labels)?;
+    register(gauge.clone())?;
+
+    Ok(gauge)
+}
+
+/// Creates and registers a histogram, with Prometheus' default buckets when `buckets` is empty.
+pub fn histogram(name: &str, help: &str, labels: &[&str], buckets: Vec<f64>) -> Result<HistogramVec> {
+    let mut opts = HistogramOpts::new(name, help);
+
+    if !buckets.is_empty() {
+        opts = opts.buckets(buckets);
+    }
+
+    let histogram = HistogramVec::new(opts, labels)?;
+    register(histogram.clone())?;
+
+    Ok(histogram)
+}
+
+pub fn render() -> Result<String> {
+    LazyLock::force(&BUILTIN);
+
+    TextEncoder::new()
+        .encode_to_string(&REGISTRY.gather())
+        .context("Failed to encode metrics")
+}
+
+pub fn content_type() -> String {
+    TextEncoder::new().format_type().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_are_counted_by_route_pattern() {
+        let mut req = Request::fake("GET", "/metrics-test/42");
+        req.route = Some("GET /metrics-test/:id".to_string());
+
+        record_request(&req, &Response::new(), Duration::from_millis(5));
+        record_request(&Request::fake("GET", "/metrics-test-missing"), &Response::new().status_code(404), Duration::ZERO);
+
+        let text = render().unwrap();
+
+        assert!(text.contains(r#"flyer_http_requests_total{method="GET",route="/metrics-test/:id",status="200"} 1"#));
+        assert!(text.contains(r#"flyer_http_request_duration_seconds_count{method="GET",route="/metrics-test/:id",status="200"} 1"#));
+        assert!(text.contains(r#"route="unmatched",status="404""#));
+        assert!(!text.contains("/metrics-test-missing"));
+    }
+
+    #[test]
+    fn app_metrics_are_rendered_and_registered_once() {
+        let counter = counter("metrics_test_orders_total", "Orders placed.", &["plan"]).unwrap();
+        counter.with_label_values(&["pro"]).inc();
+
+        assert!(render().unwrap().contains(r#"metrics_test_orders_total{plan="pro"} 1"#));
+        assert!(gauge("metrics_test_orders_total", "Again.", &[]).is_err());
+        assert!(content_type().starts_with("text/plain"));
+    }
+}
@@ -1,7 +1,13 @@
-use std::collections::HashMap;
+use std::{collections::{BTreeMap, HashMap}, fmt::{Display, Write}};
 
 use anyhow::Result;
 use bytes::Bytes;
+use serde::{
+    Deserializer,
+    de::{DeserializeOwned, DeserializeSeed, Error as _, MapAccess, SeqAccess, Visitor, value::BorrowedStrDeserializer},
+    forward_to_deserialize_any,
+};
+use serde_json::Value;
 
 use crate::{
     storage::{DEFAULT_STORAGE, save, save_as},
@@ -15,12 +21,39 @@ pub struct File {
     pub name: String,
     pub mime: String,
     pub content: Bytes,
+    /// Set when the upload was streamed straight into a storage backend; `content` is empty then.
+    pub stored: Option<StoredFile>,
+}
+
+/// Handle to an upload written to storage while the request was parsed.
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub storage: String,
+    pub path: String,
+    pub size: u64,
+}
+
+/// A field name the form refuses to build, answered with a `400`.
+#[derive(Debug)]
+pub struct FormError(String);
+
+impl Display for FormError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
+impl std::error::Error for FormError {}
+
+/// Submitted fields, both as the structured tree the client sent (`data`) and flattened into
+/// `values` under `name`, `user[name]` and `tags[0]` style keys, which the `Validator` reads.
 #[derive(Debug, Clone, Default)]
 pub struct Form {
     pub values: Values,
     pub files: Files,
+    pub(crate) data: Value,
+    /// Set for JSON bodies, where a string stays a string even when it looks like a number.
+    pub(crate) typed: bool,
 }
 
 impl Form {
@@ -28,6 +61,76 @@ impl Form {
         return Self {
             values: values,
             files: files,
+            data: Value::Null,
+            typed: false,
+        }
+    }
+
+    /// Builds the form from `key=value` pairs, where `user[name]` nests an object, `tags[]` or a
+    /// repeated key collects an array and `tags[2]` sets an index. Values stay strings. Indices
+    /// may arrive in any order or with gaps, and the list keeps their order without the gaps.
+    pub(crate) fn from_pairs(pairs: Vec<(String, String)>) -> Result<Self, FormError> {
+        let mut root = Node::Map(BTreeMap::new());
+        let mut plain = Vec::new();
+
+        for (key, value) in pairs.into_iter().filter(|(key, _)| !key.is_empty()) {
+            if !key.contains('[') {
+                plain.push((key.clone(), value.clone()));
+            }
+
+            root.insert(&parse_path(&key), value)
+                .map_err(|index| FormError(format!("Index {} of '{}' is out of range", index, key)))?;
+        }
+
+        let mut form = Self::from_value(root.into_value());
+        form.typed = false;
+
+        // A repeated `tags` is listed as `tags[0]`, `tags[1]`, and `tags` keeps the last value.
+        form.values.extend(plain);
+
+        Ok(form)
+    }
+
+    /// Builds the form from a JSON body, keeping its types. Also handy to run a `Validator`
+    /// over a payload that did not come from the request body.
+    pub fn from_value(data: Value) -> Self {
+        let mut values = Values::new();
+        let mut path = String::with_capacity(32);
+
+        match &data {
+            Value::Array(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    path.clear();
+                    let _ = write!(path, "{}", i);
+                    flatten(item, &mut path, &mut values);
+                }
+            }
+            Value::Object(fields) => {
+                for (key, item) in fields {
+                    path.clear();
+                    path.push_str(key);
+                    flatten(item, &mut path, &mut values);
+                }
+            }
+            _ => {}
+        }
+
+        Self {
+            values,
+            files: Files::new(),
+            data,
+            typed: true,
+        }
+    }
+
+    /// Adds the fields of `other`, keeping files already parsed.
+    pub(crate) fn merge(&mut self, other: Form) {
+        self.values.extend(other.values);
+        self.typed |= other.typed;
+
+        match (&mut self.data, other.data) {
+            (Value::Object(current), Value::Object(fields)) => current.extend(fields),
+            (current, data) => *current = data,
         }
     }
 }
@@ -47,6 +150,336 @@ impl Form {
             .get(&k.into())
             .map(|f| f.clone())
     }
+
+    /// The whole submitted tree.
+    pub fn data(&self) -> &Value {
+        &self.data
+    }
+
+    /// Node at a `user.address.city` or `user[address][city]` path.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut node = &self.data;
+
+        for segment in parse_path(path).iter().flat_map(|segment| segment.split('.')) {
+            node = match node {
+                Value::Object(fields) => fields.get(segment)?,
+                Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+
+        Some(node)
+    }
+
+    /// Every value of a field that may repeat, such as `tags[]` or a multi-select.
+    pub fn values_of(&self, path: &str) -> Vec<String> {
+        match self.get(path) {
+            Some(Value::Array(items)) => items.iter().filter_map(scalar).collect(),
+            Some(value) => scalar(value).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Every file uploaded under one field name, in the order they were sent.
+    pub fn files_of(&self, name: &str) -> Vec<&File> {
+        if let Some(file) = self.files.get(name) {
+            return vec![file];
+        }
+
+        let mut indexed: Vec<(usize, &File)> = self
+            .files
+            .iter()
+            .filter_map(|(key, file)| {
+                let index = key.strip_prefix(name)?.strip_prefix('[')?.strip_suffix(']')?;
+                Some((index.parse().ok()?, file))
+            })
+            .collect();
+
+        indexed.sort_by_key(|(index, _)| *index);
+
+        indexed.into_iter().map(|(_, file)| file).collect()
+    }
+
+    /// Deserializes the submitted tree. Values from urlencoded and multipart bodies are all
+    /// strings, so numeric and boolean fields are parsed from them, an empty string is `None`
+    /// and a single value fills a one item `Vec`.
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, serde_json::Error> {
+        T::deserialize(Lenient(&self.data))
+    }
+}
+
+/// Deserializer over the form tree that accepts the strings HTML forms send for typed fields.
+struct Lenient<'de>(&'de Value);
+
+macro_rules! parse_from_string {
+    ($($method:ident => $ty:ty, $visit:ident;)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                match self.0 {
+                    Value::String(s) => match s.trim().parse::<$ty>() {
+                        Ok(parsed) => visitor.$visit(parsed),
+                        Err(_) => Err(serde_json::Error::custom(format!("invalid number `{}`", s))),
+                    },
+                    value => value.$method(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Lenient<'de> {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(items) => visitor.visit_seq(Seq(items.iter())),
+            Value::Object(fields) => visitor.visit_map(Fields { fields: fields.iter(), value: None }),
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::String(s) => match s.to_lowercase().as_str() {
+                "true" | "1" | "on" | "yes" => visitor.visit_bool(true),
+                "false" | "0" | "off" | "no" => visitor.visit_bool(false),
+                _ => Err(serde_json::Error::custom(format!("invalid boolean `{}`", s))),
+            },
+            value => value.deserialize_bool(visitor),
+        }
+    }
+
+    parse_from_string! {
+        deserialize_i8 => i64, visit_i64;
+        deserialize_i16 => i64, visit_i64;
+        deserialize_i32 => i64, visit_i64;
+        deserialize_i64 => i64, visit_i64;
+        deserialize_u8 => u64, visit_u64;
+        deserialize_u16 => u64, visit_u64;
+        deserialize_u32 => u64, visit_u64;
+        deserialize_u64 => u64, visit_u64;
+        deserialize_f32 => f64, visit_f64;
+        deserialize_f64 => f64, visit_f64;
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            Value::String(s) if s.is_empty() => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Array(items) => visitor.visit_seq(Seq(items.iter())),
+            value => visitor.visit_seq(Seq(std::slice::from_ref(value).iter())),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct Seq<'de>(std::slice::Iter<'de, Value>);
+
+impl<'de> SeqAccess<'de> for Seq<'de> {
+    type Error = serde_json::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error> {
+        self.0.next().map(|item| seed.deserialize(Lenient(item))).transpose()
+    }
+}
+
+struct Fields<'de> {
+    fields: serde_json::map::Iter<'de>,
+    value: Option<&'de Value>,
+}
+
+impl<'de> MapAccess<'de> for Fields<'de> {
+    type Error = serde_json::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.fields.next() else {
+            return Ok(None);
+        };
+
+        self.value = Some(value);
+
+        seed.deserialize(BorrowedStrDeserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Self::Error> {
+        let value = self.value.take().ok_or_else(|| serde_json::Error::custom("value is missing"))?;
+
+        seed.deserialize(Lenient(value))
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Splits `user[address][city]` or `tags[]` into segments; `[]` becomes an empty segment
+/// meaning "append".
+pub(crate) fn parse_path(key: &str) -> Vec<String> {
+    let (head, rest) = match key.find('[') {
+        Some(i) => (&key[..i], &key[i..]),
+        None => (key, ""),
+    };
+
+    let mut segments = vec![head.to_string()];
+
+    for part in rest.split('[').skip(1) {
+        segments.push(part.trim_end_matches(']').to_string());
+    }
+
+    segments
+}
+
+/// Highest list index a field name may use, so `tags[99999999999]` is refused.
+const MAX_INDEX: usize = 10_000;
+
+/// A field while the pairs are read. Lists stay keyed by the indices the client sent until
+/// `into_value` compacts them.
+enum Node {
+    Text(String),
+    List(BTreeMap<usize, Node>),
+    Map(BTreeMap<String, Node>),
+}
+
+impl Node {
+    /// Sets `value` at `segments`, failing with the index when it is above `MAX_INDEX`.
+    fn insert(&mut self, segments: &[String], value: String) -> Result<(), usize> {
+        let Some((head, rest)) = segments.split_first() else {
+            return Ok(());
+        };
+
+        if head.is_empty() || (head.parse::<usize>().is_ok() && !matches!(self, Node::Map(_))) {
+            if !matches!(self, Node::List(_)) {
+                *self = Node::List(BTreeMap::new());
+            }
+
+            let Node::List(items) = self else { unreachable!() };
+
+            let index = head.parse::<usize>().unwrap_or_else(|_| next_index(items));
+
+            if index > MAX_INDEX {
+                return Err(index);
+            }
+
+            return match rest.is_empty() {
+                true => {
+                    items.insert(index, Node::Text(value));
+                    Ok(())
+                }
+                false => items.entry(index).or_insert_with(|| Node::List(BTreeMap::new())).insert(rest, value),
+            };
+        }
+
+        let fields = match std::mem::replace(self, Node::Text(String::new())) {
+            Node::Map(fields) => fields,
+            // `items[0]=a&items[id]=b` keeps the items already set under their index.
+            Node::List(items) => items.into_iter().map(|(index, item)| (index.to_string(), item)).collect(),
+            Node::Text(_) => BTreeMap::new(),
+        };
+
+        *self = Node::Map(fields);
+
+        let Node::Map(fields) = self else { unreachable!() };
+
+        if !rest.is_empty() {
+            return fields.entry(head.clone()).or_insert_with(|| Node::List(BTreeMap::new())).insert(rest, value);
+        }
+
+        // A key sent more than once collects all of its values rather than keeping the last.
+        match fields.get_mut(head) {
+            Some(Node::List(items)) => {
+                let index = next_index(items);
+
+                if index > MAX_INDEX {
+                    return Err(index);
+                }
+
+                items.insert(index, Node::Text(value));
+            }
+            Some(existing) => {
+                let first = std::mem::replace(existing, Node::Text(String::new()));
+                *existing = Node::List(BTreeMap::from([(0, first), (1, Node::Text(value))]));
+            }
+            None => {
+                fields.insert(head.clone(), Node::Text(value));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Node::Text(text) => Value::String(text),
+            Node::List(items) => Value::Array(items.into_values().map(Node::into_value).collect()),
+            Node::Map(fields) => Value::Object(fields.into_iter().map(|(key, item)| (key, item.into_value())).collect()),
+        }
+    }
+}
+
+fn next_index(items: &BTreeMap<usize, Node>) -> usize {
+    items.last_key_value().map_or(0, |(last, _)| last + 1)
+}
+
+fn flatten(value: &Value, path: &mut String, out: &mut Values) {
+    match value {
+        Value::Null => {
+            out.insert(path.clone(), "null".into());
+        }
+        Value::Bool(b) => {
+            out.insert(path.clone(), b.to_string());
+        }
+        Value::Number(num) => {
+            out.insert(path.clone(), num.to_string());
+        }
+        Value::String(s) => {
+            out.insert(path.clone(), s.clone());
+        }
+        Value::Array(items) => {
+            let base_len = path.len();
+            for (i, item) in items.iter().enumerate() {
+                path.truncate(base_len);
+                let _ = write!(path, "[{}]", i);
+                flatten(item, path, out);
+            }
+            path.truncate(base_len);
+        }
+        Value::Object(fields) => {
+            let base_len = path.len();
+            for (key, item) in fields {
+                path.truncate(base_len);
+                let _ = write!(path, "[{}]", key);
+                flatten(item, path, out);
+            }
+            path.truncate(base_len);
+        }
+    }
 }
 
 impl File {
@@ -59,6 +492,7 @@ impl File {
                 .unwrap_or(mime_guess::mime::APPLICATION_OCTET_STREAM)
                 .to_string(), 
             content: content,
+            stored: None,
         }
     }
 
@@ -67,9 +501,14 @@ impl File {
             name: name.into(),
             mime: mime.into(),
             content: content,
+            stored: None,
         }
     }
 
+    pub fn is_stored(&self) -> bool {
+        self.stored.is_some()
+    }
+
     #[allow(static_mut_refs)]
     pub async fn save_as(&self, folder: impl Into<String>, name: impl Into<String>,) -> Result<String> {
         save_as(DEFAULT_STORAGE, folder, name, self.clone()).await
@@ -80,3 +519,77 @@ impl File {
         save(DEFAULT_STORAGE, folder, self.clone()).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn form(query: &str) -> Result<Form, FormError> {
+        Form::from_pairs(serde_urlencoded::from_str(query).unwrap())
+    }
+
+    #[test]
+    fn nested_fields_build_a_tree() {
+        let form = form("user[name]=ada&user[roles][]=admin&user[roles][]=dev&items[0][id]=1&items[0][qty]=2&items[1][id]=3").unwrap();
+
+        assert_eq!(form.data, json!({
+            "user": { "name": "ada", "roles": ["admin", "dev"] },
+            "items": [{ "id": "1", "qty": "2" }, { "id": "3" }],
+        }));
+        assert_eq!(form.value("user[roles][1]"), "dev");
+        assert_eq!(form.value("items[0][qty]"), "2");
+        assert_eq!(form.get("items.1.id"), Some(&json!("3")));
+    }
+
+    #[test]
+    fn repeated_keys_keep_the_plain_key() {
+        let form = form("tags=a&tags=b&tags=c").unwrap();
+
+        assert_eq!(form.values_of("tags"), vec!["a", "b", "c"]);
+        assert_eq!(form.value("tags[2]"), "c");
+        assert_eq!(form.value("tags"), "c");
+    }
+
+    #[test]
+    fn sparse_indices_are_compacted() {
+        let form = form("items[0]=a&items[2]=c").unwrap();
+
+        assert_eq!(form.data, json!({ "items": ["a", "c"] }));
+        assert_eq!(form.value("items[1]"), "c");
+    }
+
+    #[test]
+    fn out_of_order_indices_keep_their_order() {
+        let form = form("tags[1]=b&tags[0]=a&tags[0]=c").unwrap();
+
+        assert_eq!(form.values_of("tags"), vec!["c", "b"]);
+    }
+
+    #[test]
+    fn deleted_rows_leave_no_gaps() {
+        let form = form("rows[0][name]=a&rows[3][name]=d&rows[5][name]=f&rows[3][qty]=2").unwrap();
+
+        assert_eq!(form.data, json!({
+            "rows": [{ "name": "a" }, { "name": "d", "qty": "2" }, { "name": "f" }],
+        }));
+        assert_eq!(form.value("rows[1][qty]"), "2");
+    }
+
+    #[test]
+    fn huge_indices_are_rejected() {
+        let err = form("a[99999999999]=x").err().unwrap();
+        assert_eq!(err.to_string(), "Index 99999999999 of 'a[99999999999]' is out of range");
+
+        assert!(form("items[0][ids][10001]=x").is_err());
+        assert!(form("items[10000]=x").is_ok());
+    }
+
+    #[test]
+    fn empty_keys_are_skipped() {
+        let form = form("=x&name=ada").unwrap();
+
+        assert_eq!(form.values.len(), 1);
+    }
+}
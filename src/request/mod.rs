@@ -1,13 +1,16 @@
 use std::net::{IpAddr, SocketAddr};
 
-use bytes::Bytes;
+use anyhow::Result;
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, TryStreamExt};
 use serde::{de::DeserializeOwned};
 
 use crate::{
-    cookies::Cookies, request::form::{File, Files, Form}, server::Server, session::Session, utils::{Values, http::Headers, mem::Instance}
+    auth::{Identity, gate::Gate}, cookies::Cookies, loggers::Event, request::{form::{File, Files, Form}, multipart::{MultipartBody, extract_boundary, is_multipart}}, server::Server, session::Session, utils::{Values, http::Headers, mem::Instance}
 };
 
 pub mod form;
+pub mod multipart;
 
 #[derive(Clone, Debug)]
 pub struct Request {
@@ -22,8 +25,13 @@ pub struct Request {
     pub(crate) cookies: Cookies,
     pub(crate) session: Session,
     pub(crate) body: Bytes,
+    pub(crate) multipart: MultipartBody,
     pub(crate) parameters: Values,
     pub(crate) form: Form,
+    pub(crate) identity: Option<Identity>,
+    pub(crate) locale: Option<String>,
+    pub(crate) route: Option<String>,
+    pub(crate) id: Option<String>,
 }
 
 impl Into<serde_json::Value> for Request {
@@ -39,6 +47,10 @@ impl Into<serde_json::Value> for Request {
             "cookies": &self.cookies,
             "session": &self.session,
             "parameters": &self.parameters,
+            "identity": &self.identity,
+            "locale": &self.locale,
+            "route": &self.route,
+            "id": &self.id,
         })
     }
 }
@@ -79,6 +91,21 @@ impl Request {
             .clone()
     }
 
+    /// The request ID given by the `RequestId` hook, empty when it is not enabled.
+    pub fn id(&self) -> String {
+        self
+            .id
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// Pattern of the matched route, like `GET /users/{id}`.
+    pub fn route(&self) -> Option<&str> {
+        self
+            .route
+            .as_deref()
+    }
+
     pub fn is_json(&self) -> bool {
         self.header("content-type")
             .split(';')
@@ -87,6 +114,11 @@ impl Request {
             .unwrap_or(false)
     }
 
+    /// Whether the client sent JSON or asked for a JSON response.
+    pub fn wants_json(&self) -> bool {
+        self.is_json() || self.header("accept").to_ascii_lowercase().contains("application/json")
+    }
+
     pub fn parameter(&self, key: impl Into<String>) -> String {
         self
             .parameters
@@ -167,9 +199,24 @@ impl Request {
     pub fn header(&self, name: &str) -> String {
         self
             .headers
-            .get(&String::from(name))
-            .unwrap_or(&String::new())
-            .into()
+            .get(name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Every value sent for a header that may repeat, in the order received.
+    pub fn header_all(&self, name: &str) -> Vec<String> {
+        self
+            .headers
+            .get_all(name)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[inline]
+    pub fn headers(&self) -> &Headers {
+        &self.headers
     }
 
     pub fn cookie(&self, k: impl Into<String>) -> String {
@@ -180,14 +227,134 @@ impl Request {
             .into()
     }
 
+    /// Value of a signed cookie, `None` when it is missing or its signature does not match.
+    pub fn signed_cookie(&self, k: &str) -> Option<String> {
+        self
+            .cookies
+            .signed(k)
+            .map(|v| v.to_string())
+    }
+
+    /// Value of an encrypted cookie, `None` when it is missing or cannot be decrypted.
+    pub fn encrypted_cookie(&self, k: &str) -> Option<String> {
+        self
+            .cookies
+            .encrypted(k)
+            .map(|v| v.to_string())
+    }
+
     pub fn cookies(&self) -> &Cookies {
         &self.cookies
     }
 
+    #[inline]
+    pub fn identity(&self) -> Option<&Identity> {
+        self
+            .identity
+            .as_ref()
+    }
+
+    #[inline]
+    pub fn is_authenticated(&self) -> bool {
+        self
+            .identity
+            .is_some()
+    }
+
+    pub fn set_identity(&mut self, identity: Identity) {
+        self.identity = Some(identity);
+    }
+
+    /// Locale picked for this request by the `I18n` hook, or `en` without it.
+    pub fn locale(&self) -> String {
+        self
+            .locale
+            .clone()
+            .unwrap_or_else(|| crate::i18n::DEFAULT_LOCALE.to_string())
+    }
+
+    pub fn set_locale(&mut self, locale: impl Into<String>) {
+        self.locale = Some(locale.into());
+    }
+
+    pub fn can<T: 'static>(&self, ability: &str, resource: &T) -> bool {
+        self
+            .identity
+            .as_ref()
+            .map(|identity| Gate::allows(identity, ability, resource))
+            .unwrap_or(false)
+    }
+
+    #[inline]
+    pub fn cannot<T: 'static>(&self, ability: &str, resource: &T) -> bool {
+        !self.can(ability, resource)
+    }
+
     #[inline]
     pub fn server(&self) -> &mut Server {
         self
             .server
             .as_mut()
     }
-}
\ No newline at end of file
+
+    /// Takes the body from the connection. Multipart bodies are left to be parsed as they
+    /// arrive, so uploads are never held in memory whole; any other body is buffered.
+    pub(crate) async fn read_body<S>(&mut self, chunks: S) -> Result<()>
+    where
+        S: Stream<Item = Result<Bytes>> + Send + 'static,
+    {
+        let content_type = self.header("content-type");
+
+        if is_multipart(&content_type) && let Ok(boundary) = extract_boundary(&content_type) {
+            self.multipart = MultipartBody::new(chunks, boundary);
+
+            return Ok(());
+        }
+
+        let body = chunks
+            .try_fold(BytesMut::new(), |mut body, chunk| async move {
+                body.extend_from_slice(&chunk);
+                Ok(body)
+            })
+            .await?;
+
+        self.body = body.freeze();
+
+        Ok(())
+    }
+
+    /// Sends a warning about this request to the loggers.
+    pub fn warn(&self, message: impl Into<String>) {
+        self
+            .server
+            .as_ref()
+            .emit(Event::Warning(message.into(), Some(self.clone())));
+    }
+}
+
+#[cfg(test)]
+impl Request {
+    /// A request outside any server, for unit tests.
+    pub(crate) fn fake(method: &str, path: &str) -> Self {
+        Self {
+            server: Instance(std::ptr::null_mut()),
+            addr: SocketAddr::from(([127, 0, 0, 1], 0)),
+            protocol: "HTTP/1.1".to_string(),
+            method: method.to_string(),
+            path: path.to_string(),
+            queries: Values::new(),
+            headers: Headers::new(),
+            host: "localhost".to_string(),
+            cookies: Cookies::default(),
+            session: Session::default(),
+            body: Bytes::new(),
+            multipart: MultipartBody::default(),
+            parameters: Values::new(),
+            form: Form::default(),
+            identity: None,
+            locale: None,
+            route: None,
+            id: None,
+        }
+    }
+}
@@ -0,0 +1,623 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}},
+};
+
+use anyhow::{Context, Result, bail};
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt, TryStreamExt, stream};
+use multer::{Field, Multipart};
+
+use crate::{
+    request::{Request, form::{File, Form, StoredFile}},
+    response::{HTTP_BAD_REQUEST, HTTP_CONTENT_TOO_LARGE, HTTP_UNSUPPORTED_MEDIA_TYPE, Response},
+    storage,
+};
+
+/// Largest text field read from a multipart body, in bytes.
+const MAX_TEXT_SIZE: usize = 1024 * 1024;
+
+/// Most text fields read ahead of the first file, before the route and its limits are known.
+const MAX_LEADING_FIELDS: usize = 1000;
+
+/// A multipart body still arriving from the connection. Clones of a request share it and it is
+/// parsed once: the form hook reads the text fields ahead of the first file, and the matched
+/// route's `Upload` reads the rest after its middleware passed.
+#[derive(Clone, Default)]
+pub(crate) struct MultipartBody(Arc<Mutex<Option<Parser>>>);
+
+impl MultipartBody {
+    pub(crate) fn new<S>(chunks: S, boundary: &str) -> Self
+    where
+        S: Stream<Item = Result<Bytes>> + Send + 'static,
+    {
+        let parser = Parser {
+            multipart: Multipart::new(chunks, boundary),
+            pending: None,
+            fields: 0,
+            failed: None,
+        };
+
+        Self(Arc::new(Mutex::new(Some(parser))))
+    }
+
+    fn take(&self) -> Option<Parser> {
+        self.0.lock().ok()?.take()
+    }
+
+    fn put(&self, parser: Parser) {
+        if let Ok(mut slot) = self.0.lock() {
+            *slot = Some(parser);
+        }
+    }
+}
+
+impl std::fmt::Debug for MultipartBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MultipartBody")
+    }
+}
+
+struct Parser {
+    multipart: Multipart<'static>,
+    /// The first file part, held back by the form hook for the route's `Upload`.
+    pending: Option<Field<'static>>,
+    /// Text fields read so far.
+    fields: usize,
+    /// Why the form hook stopped reading, reported once the route is known.
+    failed: Option<UploadError>,
+}
+
+impl Parser {
+    async fn next_field(&mut self) -> Result<Option<Field<'static>>, UploadError> {
+        if let Some(field) = self.pending.take() {
+            return Ok(Some(field));
+        }
+
+        self.multipart.next_field().await.map_err(malformed)
+    }
+}
+
+/// Reads the text fields ahead of the first file part, so `_method` and the values are known to
+/// routing and middleware. Nothing is uploaded here.
+pub(crate) async fn read_leading_fields(req: &mut Request) {
+    let Some(mut parser) = req.multipart.take() else {
+        return;
+    };
+
+    let mut pairs = Vec::new();
+
+    let result: Result<(), UploadError> = async {
+        while let Some(field) = parser.next_field().await? {
+            if field.file_name().is_some() {
+                parser.pending = Some(field);
+                break;
+            }
+
+            parser.fields += 1;
+
+            if parser.fields > MAX_LEADING_FIELDS {
+                return Err(UploadError::TooManyFields);
+            }
+
+            pairs.push(read_text(field).await?);
+        }
+
+        Ok(())
+    }
+    .await;
+
+    match result.and_then(|_| Form::from_pairs(pairs).map_err(|err| UploadError::Malformed(err.to_string()))) {
+        Ok(form) => req.form.merge(form),
+        Err(err) => parser.failed = Some(err),
+    }
+
+    req.multipart.put(parser);
+}
+
+async fn read_text(mut field: Field<'static>) -> Result<(String, String), UploadError> {
+    let name = field.name().unwrap_or_default().to_string();
+    let mut text = BytesMut::new();
+
+    while let Some(chunk) = field.chunk().await.map_err(malformed)? {
+        if text.len() + chunk.len() > MAX_TEXT_SIZE {
+            return Err(UploadError::FieldTooLarge(name));
+        }
+
+        text.extend_from_slice(&chunk);
+    }
+
+    Ok((name, String::from_utf8_lossy(&text).into_owned()))
+}
+
+fn malformed(err: multer::Error) -> UploadError {
+    UploadError::Malformed(err.to_string())
+}
+
+/// Limits and destination for the files of a multipart request, set per route with
+/// `Route::upload`. Routes without one keep every file in memory with no limits.
+#[derive(Clone, Debug, Default)]
+pub struct Upload {
+    max_file_size: Option<u64>,
+    max_files: Option<usize>,
+    max_fields: Option<usize>,
+    allowed_mimes: Vec<String>,
+    storage: Option<(String, String)>,
+}
+
+#[derive(Debug)]
+pub enum UploadError {
+    FileTooLarge(String),
+    FieldTooLarge(String),
+    TooManyFiles,
+    TooManyFields,
+    MimeNotAllowed(String, String),
+    Malformed(String),
+}
+
+impl Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadError::FileTooLarge(field) => write!(f, "The file in '{}' is too large", field),
+            UploadError::FieldTooLarge(field) => write!(f, "The field '{}' is too large", field),
+            UploadError::TooManyFiles => write!(f, "Too many files"),
+            UploadError::TooManyFields => write!(f, "Too many fields"),
+            UploadError::MimeNotAllowed(field, mime) => write!(f, "The file in '{}' has an unsupported type '{}'", field, mime),
+            UploadError::Malformed(reason) => write!(f, "Malformed multipart body: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+impl UploadError {
+    pub fn status_code(&self) -> u16 {
+        match self {
+            UploadError::MimeNotAllowed(..) => HTTP_UNSUPPORTED_MEDIA_TYPE,
+            UploadError::Malformed(_) => HTTP_BAD_REQUEST,
+            _ => HTTP_CONTENT_TOO_LARGE,
+        }
+    }
+
+    pub(crate) fn reject(&self, res: Response, is_json: bool) -> Response {
+        crate::auth::reject(res, self.status_code(), &self.to_string(), is_json)
+    }
+}
+
+impl Upload {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Largest accepted file, in bytes.
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = Some(count);
+        self
+    }
+
+    /// Most non-file fields accepted.
+    pub fn max_fields(mut self, count: usize) -> Self {
+        self.max_fields = Some(count);
+        self
+    }
+
+    /// Accepted content types; `image/*` matches any image and `*/*` anything.
+    pub fn allowed_mimes(mut self, mimes: Vec<&str>) -> Self {
+        self.allowed_mimes = mimes.into_iter().map(|mime| mime.to_ascii_lowercase()).collect();
+        self
+    }
+
+    /// Streams every file into `folder` of the named storage backend instead of memory; the
+    /// request then carries a `StoredFile` handle for each of them.
+    pub fn store(mut self, storage: impl Into<String>, folder: impl Into<String>) -> Self {
+        self.storage = Some((storage.into(), folder.into()));
+        self
+    }
+
+    fn allows(&self, mime: &str) -> bool {
+        if self.allowed_mimes.is_empty() {
+            return true;
+        }
+
+        let mime = mime.to_ascii_lowercase();
+
+        self.allowed_mimes.iter().any(|allowed| {
+            match allowed.strip_suffix("/*") {
+                Some("*") => true,
+                Some(kind) => mime.split('/').next() == Some(kind),
+                None => *allowed == mime,
+            }
+        })
+    }
+
+    /// Parses the rest of a multipart body, from the first file part on, into `req.form`. Runs
+    /// once the route's middleware passed, so rejected requests never reach storage.
+    pub(crate) async fn parse(&self, req: &mut Request) -> Result<(), UploadError> {
+        let Some(mut parser) = req.multipart.take() else {
+            return Ok(());
+        };
+
+        if let Some(err) = parser.failed.take() {
+            return Err(err);
+        }
+
+        if self.max_fields.is_some_and(|max| parser.fields > max) {
+            return Err(UploadError::TooManyFields);
+        }
+
+        let mut raw_files: Vec<(String, File)> = Vec::new();
+        let mut field_file_counts: HashMap<String, usize> = HashMap::new();
+        let mut listed_fields: HashSet<String> = HashSet::new();
+        let mut pairs = Vec::new();
+
+        let result: Result<(), UploadError> = async {
+            while let Some(field) = parser.next_field().await? {
+                let name = field.name().unwrap_or_default().to_string();
+
+                let Some(filename) = field.file_name().map(String::from) else {
+                    parser.fields += 1;
+
+                    if self.max_fields.is_some_and(|max| parser.fields > max) {
+                        return Err(UploadError::TooManyFields);
+                    }
+
+                    pairs.push(read_text(field).await?);
+
+                    continue;
+                };
+
+                let mime = field
+                    .content_type()
+                    .map(|mime| mime.as_ref())
+                    .unwrap_or("application/octet-stream")
+                    .to_string();
+
+                let mut field = field;
+
+                // An empty file input still sends a part; it is not an upload.
+                let first = loop {
+                    match field.chunk().await.map_err(malformed)? {
+                        Some(chunk) if chunk.is_empty() => continue,
+                        chunk => break chunk,
+                    }
+                };
+
+                let Some(first) = first else {
+                    continue;
+                };
+
+                if self.max_files.is_some_and(|max| raw_files.len() >= max) {
+                    return Err(UploadError::TooManyFiles);
+                }
+
+                if !self.allows(&mime) {
+                    return Err(UploadError::MimeNotAllowed(name, mime));
+                }
+
+                let file = self.read_file(&name, File::create(&filename, &mime, Bytes::new()), first, field).await?;
+
+                // `photos[]` inputs are listed like repeated `photos` inputs.
+                let (name, listed) = match name.strip_suffix("[]") {
+                    Some(base) => (base.to_string(), true),
+                    None => (name, false),
+                };
+
+                if listed {
+                    listed_fields.insert(name.clone());
+                }
+
+                *field_file_counts.entry(name.clone()).or_default() += 1;
+                raw_files.push((name, file));
+            }
+
+            Ok(())
+        }
+        .await;
+
+        let form = match result.and_then(|_| Form::from_pairs(pairs).map_err(|err| UploadError::Malformed(err.to_string()))) {
+            Ok(form) => form,
+            Err(err) => {
+                for (_, file) in &raw_files {
+                    discard(file).await;
+                }
+
+                return Err(err);
+            }
+        };
+
+        req.form.merge(form);
+
+        let mut field_file_indices: HashMap<String, usize> = HashMap::new();
+
+        for (name, file) in raw_files {
+            let total_count = field_file_counts.get(&name).copied().unwrap_or(0);
+
+            if total_count > 1 || listed_fields.contains(&name) {
+                let idx = field_file_indices.entry(name.clone()).or_default();
+                req.form.files.insert(format!("{}[{}]", name, idx), file);
+                *idx += 1;
+            } else {
+                req.form.files.insert(name, file);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads one file part into memory or storage, enforcing the size limit as chunks arrive.
+    async fn read_file(&self, name: &str, mut file: File, first: Bytes, field: Field<'static>) -> Result<File, UploadError> {
+        let limit = self.max_file_size;
+        let field_name = name.to_string();
+        let mut size = 0u64;
+
+        let chunks = stream::once(async move { Ok(first) })
+            .chain(field)
+            .map_err(anyhow::Error::from)
+            .and_then(move |chunk| {
+                size += chunk.len() as u64;
+
+                let exceeded = limit.is_some_and(|limit| size > limit);
+                let field_name = field_name.clone();
+
+                async move {
+                    if exceeded {
+                        bail!(UploadError::FileTooLarge(field_name));
+                    }
+
+                    Ok(chunk)
+                }
+            });
+
+        let failed = |err: anyhow::Error| match err.downcast::<UploadError>() {
+            Ok(err) => err,
+            Err(err) => UploadError::Malformed(format!("{:#}", err)),
+        };
+
+        let Some((storage_name, folder)) = &self.storage else {
+            let mut content = BytesMut::new();
+            let mut chunks = Box::pin(chunks);
+
+            while let Some(chunk) = chunks.next().await {
+                content.extend_from_slice(&chunk.map_err(failed)?);
+            }
+
+            file.content = content.freeze();
+
+            return Ok(file);
+        };
+
+        let written = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&written);
+        let chunks = chunks.inspect_ok(move |chunk| {
+            counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        });
+
+        let path = storage::save_stream(storage_name, folder.clone(), file.clone(), chunks.boxed())
+            .await
+            .map_err(failed)?;
+
+        file.stored = Some(StoredFile {
+            storage: storage_name.clone(),
+            path,
+            size: written.load(Ordering::Relaxed),
+        });
+
+        Ok(file)
+    }
+}
+
+/// Removes an already stored upload when a later part of the same request is rejected.
+async fn discard(file: &File) {
+    if let Some(stored) = &file.stored {
+        let _ = storage::delete(&stored.storage, stored.path.clone()).await;
+    }
+}
+
+pub(crate) fn is_multipart(content_type: &str) -> bool {
+    content_type
+        .split(';')
+        .next()
+        .map(|mime| mime.trim().eq_ignore_ascii_case("multipart/form-data"))
+        .unwrap_or(false)
+}
+
+pub(crate) fn extract_boundary(header: &str) -> Result<&str> {
+    header
+        .split(';')
+        .map(str::trim)
+        .find_map(|part| {
+            if part.len() >= 9 && part[..9].eq_ignore_ascii_case("boundary=") {
+                let boundary = part[9..].trim_matches('"');
+                if !boundary.is_empty() {
+                    return Some(boundary);
+                }
+            }
+            None
+        })
+        .context("Multipart boundary parameter missing from Content-Type header")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "X-BOUNDARY";
+
+    enum Part<'a> {
+        Text(&'a str, &'a str),
+        File(&'a str, &'a str, &'a str, &'a [u8]),
+    }
+
+    fn body(parts: &[Part<'_>]) -> Vec<u8> {
+        let mut body = Vec::new();
+
+        for part in parts {
+            body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+
+            match part {
+                Part::Text(name, value) => {
+                    body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n{}", name, value).as_bytes());
+                }
+                Part::File(name, filename, mime, content) => {
+                    body.extend_from_slice(format!("Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n", name, filename, mime).as_bytes());
+                    body.extend_from_slice(content);
+                }
+            }
+
+            body.extend_from_slice(b"\r\n");
+        }
+
+        body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+        body
+    }
+
+    /// A request whose body arrives in `chunk`-sized pieces, as from a connection.
+    async fn request(parts: &[Part<'_>], chunk: usize) -> Request {
+        let mut req = Request::fake("POST", "/upload");
+        req.headers.append("content-type".to_string(), format!("multipart/form-data; boundary={}", BOUNDARY));
+
+        let chunks: Vec<Result<Bytes>> = body(parts).chunks(chunk).map(|piece| Ok(Bytes::copy_from_slice(piece))).collect();
+        req.read_body(stream::iter(chunks)).await.unwrap();
+
+        req
+    }
+
+    async fn upload(upload: Upload, parts: &[Part<'_>]) -> Result<Request, UploadError> {
+        let mut req = request(parts, 7).await;
+
+        read_leading_fields(&mut req).await;
+        upload.parse(&mut req).await.map(|_| req)
+    }
+
+    #[tokio::test]
+    async fn body_is_parsed_once_across_hook_and_route() {
+        let mut req = request(&[
+            Part::Text("_method", "PUT"),
+            Part::Text("name", "flyer"),
+            Part::File("avatar", "a.png", "image/png", b"png-bytes"),
+            Part::Text("after", "file"),
+        ], 5).await;
+
+        assert!(req.body.is_empty());
+
+        read_leading_fields(&mut req).await;
+
+        assert_eq!(req.value("name"), "flyer");
+        assert_eq!(req.value("_method"), "PUT");
+        assert_eq!(req.value("after"), "");
+        assert!(req.files().is_empty());
+
+        Upload::default().parse(&mut req).await.unwrap();
+
+        assert_eq!(req.file("avatar").unwrap().content, Bytes::from_static(b"png-bytes"));
+        assert_eq!(req.value("after"), "file");
+        assert_eq!(req.value("name"), "flyer");
+    }
+
+    #[tokio::test]
+    async fn clones_share_the_parser() {
+        let mut req = request(&[Part::File("doc", "a.txt", "text/plain", b"hello")], 64).await;
+        let clone = req.clone();
+
+        Upload::default().parse(&mut req).await.unwrap();
+
+        let mut clone = clone;
+        Upload::default().parse(&mut clone).await.unwrap();
+
+        assert!(req.file("doc").is_some());
+        assert!(clone.file("doc").is_none());
+    }
+
+    #[tokio::test]
+    async fn repeated_and_listed_files_are_indexed() {
+        let req = upload(Upload::default(), &[
+            Part::File("photos[]", "a.png", "image/png", b"a"),
+            Part::File("docs", "b.txt", "text/plain", b"b"),
+            Part::File("docs", "c.txt", "text/plain", b"c"),
+            Part::File("empty", "", "application/octet-stream", b""),
+        ]).await.unwrap();
+
+        assert!(req.file("photos[0]").is_some());
+        assert_eq!(req.file("docs[1]").unwrap().content, Bytes::from_static(b"c"));
+        assert!(req.file("empty").is_none());
+    }
+
+    #[tokio::test]
+    async fn file_limits_are_enforced() {
+        let large = upload(Upload::new().max_file_size(4), &[
+            Part::File("doc", "a.txt", "text/plain", b"12345"),
+        ]).await;
+        assert!(matches!(large, Err(UploadError::FileTooLarge(field)) if field == "doc"));
+
+        let exact = upload(Upload::new().max_file_size(5), &[
+            Part::File("doc", "a.txt", "text/plain", b"12345"),
+        ]).await;
+        assert!(exact.is_ok());
+
+        let many = upload(Upload::new().max_files(1), &[
+            Part::File("a", "a.txt", "text/plain", b"a"),
+            Part::File("b", "b.txt", "text/plain", b"b"),
+        ]).await;
+        assert!(matches!(many, Err(UploadError::TooManyFiles)));
+
+        let mime = upload(Upload::new().allowed_mimes(vec!["image/*"]), &[
+            Part::File("doc", "a.txt", "text/plain", b"a"),
+        ]).await;
+        let err = mime.unwrap_err();
+        assert!(matches!(err, UploadError::MimeNotAllowed(..)));
+        assert_eq!(err.status_code(), HTTP_UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn max_fields_counts_fields_on_both_sides_of_a_file() {
+        let parts = [
+            Part::Text("a", "1"),
+            Part::File("doc", "a.txt", "text/plain", b"a"),
+            Part::Text("b", "2"),
+        ];
+
+        assert!(matches!(upload(Upload::new().max_fields(1), &parts).await, Err(UploadError::TooManyFields)));
+        assert!(upload(Upload::new().max_fields(2), &parts).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn oversized_text_fields_fail_once_the_route_is_known() {
+        let value = "x".repeat(MAX_TEXT_SIZE + 1);
+        let mut req = request(&[Part::Text("bio", &value)], 64 * 1024).await;
+
+        read_leading_fields(&mut req).await;
+        assert_eq!(req.value("bio"), "");
+
+        let err = Upload::default().parse(&mut req).await.unwrap_err();
+        assert!(matches!(&err, UploadError::FieldTooLarge(field) if field == "bio"));
+        assert_eq!(err.status_code(), HTTP_CONTENT_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn truncated_bodies_are_malformed() {
+        let mut req = Request::fake("POST", "/upload");
+        req.headers.append("content-type".to_string(), format!("multipart/form-data; boundary={}", BOUNDARY));
+
+        let mut body = body(&[Part::File("doc", "a.txt", "text/plain", b"hello")]);
+        body.truncate(body.len() - 20);
+        req.read_body(stream::iter(vec![Ok(Bytes::from(body))])).await.unwrap();
+
+        read_leading_fields(&mut req).await;
+
+        assert!(matches!(Upload::default().parse(&mut req).await, Err(UploadError::Malformed(_))));
+    }
+
+    #[test]
+    fn boundaries_are_read_from_the_content_type() {
+        assert!(is_multipart("Multipart/Form-Data; boundary=abc"));
+        assert!(!is_multipart("application/json"));
+        assert_eq!(extract_boundary("multipart/form-data; BOUNDARY=\"abc\"").unwrap(), "abc");
+        assert!(extract_boundary("multipart/form-data").is_err());
+    }
+}
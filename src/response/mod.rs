@@ -3,6 +3,7 @@ use serde::Serialize;
 
 use crate::{
     cookies::{Cookies, cookie::Cookie},
+    error::Error,
     request::Request,
     routing::next::Next,
     session::Session,
@@ -85,6 +86,7 @@ pub struct Response {
     pub(crate) cookies: Cookies,
     pub(crate) session: Session,
     pub(crate) view: Option<ViewBag>,
+    pub(crate) error: Option<Error>,
     is_next: bool,
 }
 
@@ -111,6 +113,7 @@ impl Default for Response {
             cookies: Default::default(),
             session: Default::default(),
             view: None,
+            error: None,
             is_next: false,
         }
     }
@@ -143,16 +146,27 @@ impl Response {
             .unwrap_or_default()
     }
 
+    #[inline]
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Sets a header, replacing any value it already had.
     #[inline]
     pub fn set_header(mut self, k: impl Into<String>, v: impl Into<String>) -> Self {
-        self.headers.insert(k.into(), v.into());
+        self.headers.insert(k, v);
+        self
+    }
+
+    /// Adds another value to a header that may repeat, such as `Vary` or `Link`.
+    #[inline]
+    pub fn append_header(mut self, k: impl Into<String>, v: impl Into<String>) -> Self {
+        self.headers.append(k, v);
         self
     }
 
     pub fn set_headers(mut self, headers: Headers) -> Self {
-        for (k, v) in headers {
-            self.headers.insert(k, v);
-        }
+        self.headers.merge(headers);
         self
     }
 
@@ -166,6 +180,16 @@ impl Response {
         self.cookies.set(k, v)
     }
 
+    #[inline]
+    pub fn set_signed_cookie(&mut self, k: impl Into<String>, v: impl Into<String>) -> &mut Cookie {
+        self.cookies.set_signed(k, v)
+    }
+
+    #[inline]
+    pub fn set_encrypted_cookie(&mut self, k: impl Into<String>, v: impl Into<String>) -> &mut Cookie {
+        self.cookies.set_encrypted(k, v)
+    }
+
     #[inline]
     pub fn remove_cookie(mut self, k: impl Into<String>) -> Self {
         self.cookies.remove(k);
@@ -189,6 +213,18 @@ impl Response {
         self
     }
 
+    #[inline]
+    pub fn regenerate_session(mut self) -> Self {
+        self.session.regenerate();
+        self
+    }
+
+    #[inline]
+    pub fn invalidate_session(mut self) -> Self {
+        self.session.invalidate();
+        self
+    }
+
     #[inline]
     pub fn set_flash(mut self, k: impl Into<String>, v: impl Into<String>) -> Self {
         self.session.set_flash(k, v);
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use crate::{
-    request::Request,
+    request::{Request, multipart::Upload},
     response::Response,
     routing::next::Next,
     server::Server,
@@ -14,7 +14,8 @@ pub struct Route<H> {
     pub(crate) subdomain: String,
     pub(crate) path: Vec<String>,
     pub(crate) handler: H,
-    pub(crate) middlewares: HashSet<String>
+    pub(crate) middlewares: HashSet<String>,
+    pub(crate) upload: Option<Upload>,
 }
 
 impl <H>Route<H> {
@@ -25,10 +26,25 @@ impl <H>Route<H> {
     {
         middleware_resolver(
             self.server.clone(),
+            std::any::type_name::<C>(),
             Box::new(move |req, res, next| Box::pin(callback(req, res, next))),
             &mut self.middlewares
         );
 
         return self;
     }
-}
\ No newline at end of file
+
+    /// The method and path the route was declared with, like `GET /users/{id}`.
+    pub(crate) fn pattern(&self) -> String {
+        let subdomain = if self.subdomain.is_empty() { String::new() } else { format!("{}.", self.subdomain) };
+
+        format!("{} {}/{}", self.method.to_uppercase(), subdomain, self.path.join("/"))
+    }
+
+    /// Limits, and optionally a storage backend, for the files uploaded to this route.
+    pub fn upload(&mut self, upload: Upload) -> &mut Self {
+        self.upload = Some(upload);
+
+        self
+    }
+}
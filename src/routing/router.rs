@@ -104,6 +104,7 @@ impl Router {
             path: vec::merge(self.path.clone(), url::clean(path)),
             handler: Box::new(move |req, res| Box::pin(callback(req, res))),
             middlewares: self.middlewares.clone(),
+            upload: None,
         });
 
         self.http.last_mut().unwrap()
@@ -129,6 +130,7 @@ impl Router {
             path: vec::merge(self.path.clone(), url::clean(path)),
             handler: Box::new(move |req, ws| Box::pin(callback(req, ws))),
             middlewares: self.middlewares.clone(),
+            upload: None,
         });
 
         self.websocket.last_mut().unwrap()
@@ -161,6 +163,7 @@ impl Router {
     {
         middleware_resolver(
             self.server.clone(),
+            std::any::type_name::<C>(),
             Box::new(move |req, res, next| Box::pin(callback(req, res, next))),
             &mut self.middlewares,
         );
@@ -200,6 +203,7 @@ impl GroupRouter {
     {
         middleware_resolver(
             self.server.clone(),
+            std::any::type_name::<C>(),
             Box::new(move |req, res, next| Box::pin(callback(req, res, next))),
             &mut self.middlewares,
         );
@@ -2,19 +2,20 @@ use std::collections::{HashMap, HashSet};
 use url_domain_parse::Url;
 
 use crate::{
-    error::Error,
-    request::Request,
+    error::{Error, debug},
+    request::{Request, multipart::Upload},
     response::{HTTP_INTERNAL_SERVER_ERROR, HTTP_NOT_FOUND, Response},
     routing::{
         HttpErrorHandler, HttpHandler, Middlewares, WebsocketHandler, next::Next, route::Route,
     },
-    utils::{Values, url},
+    utils::{Values, env::is_development, url},
 };
 
 pub struct Routes {
     pub(crate) http: Vec<Route<HttpHandler>>,
     pub(crate) websocket: Vec<Route<WebsocketHandler>>,
     pub(crate) middlewares: Middlewares,
+    pub(crate) middleware_names: HashMap<String, &'static str>,
     pub(crate) errors: Vec<HttpErrorHandler>,
     pub(crate) not_found_callback: Option<HttpHandler>,
 }
@@ -31,6 +32,7 @@ impl Routes {
             http: Vec::new(),
             websocket: Vec::new(),
             middlewares: HashMap::new(),
+            middleware_names: HashMap::new(),
             errors: Vec::new(),
             not_found_callback: None,
         }
@@ -129,14 +131,28 @@ impl Routes {
 
             if matches {
                 req.parameters = params;
+                req.route = Some(route.pattern());
 
-                let (resolved, req, res) =
+                if is_development() {
+                    debug::record(route, &req, &self.middleware_names);
+                }
+
+                let (resolved, mut req, res) =
                     self.resolve_middleware(req, res, &route.middlewares).await;
 
                 if !resolved {
                     return (req, res, None);
                 }
 
+                // Files are only read once the middleware let the request through.
+                let default = Upload::default();
+                let upload = route.upload.as_ref().unwrap_or(&default);
+
+                if let Err(err) = upload.parse(&mut req).await {
+                    let is_json = req.wants_json();
+                    return (req, err.reject(res, is_json), None);
+                }
+
                 return (req, res, Some(route));
             }
         }
@@ -259,3 +275,79 @@ impl Routes {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::Path};
+
+    use bytes::Bytes;
+    use futures::{FutureExt, stream};
+
+    use super::*;
+    use crate::{routing::route::Route, storage::{self, local::LocalStorage}, utils::mem::Instance};
+
+    const BODY: &str = "--B\r\nContent-Disposition: form-data; name=\"token\"\r\n\r\nsecret\r\n--B\r\nContent-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n--B--\r\n";
+
+    fn routes(folder: &Path, storage: &str) -> Routes {
+        storage::add(storage, LocalStorage::new(folder.to_string_lossy()));
+
+        let mut routes = Routes::new();
+
+        routes.middlewares.insert("token".to_string(), Box::new(Box::new(|req: Request, res: Response, next: Next| {
+            async move {
+                if req.value("token") != "secret" {
+                    return res.status_code(403);
+                }
+
+                next.handle(req, res)
+            }
+            .boxed()
+        })));
+
+        routes.http.push(Route {
+            server: Instance(std::ptr::null_mut()),
+            method: "POST".to_string(),
+            subdomain: String::new(),
+            path: vec!["upload".to_string()],
+            handler: Box::new(|_, res| async move { res }.boxed()),
+            middlewares: HashSet::from(["token".to_string()]),
+            upload: Some(Upload::new().store(storage, "files")),
+        });
+
+        routes
+    }
+
+    async fn request(body: String) -> Request {
+        let mut req = Request::fake("POST", "/upload");
+        req.headers.append("content-type".to_string(), "multipart/form-data; boundary=B".to_string());
+        req.read_body(stream::iter(vec![Ok(Bytes::from(body))])).await.unwrap();
+
+        crate::request::multipart::read_leading_fields(&mut req).await;
+
+        req
+    }
+
+    fn stored(folder: &Path) -> usize {
+        std::fs::read_dir(folder.join("files")).map(|entries| entries.count()).unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn files_are_stored_only_after_middleware_passes() {
+        let folder = std::env::temp_dir().join(format!("flyer-routes-{}", ulid::Ulid::new()));
+        let routes = routes(&folder, "routes-test-uploads");
+
+        let (req, res) = routes.handle_http(request(BODY.replace("secret", "wrong")).await, Response::new()).await;
+
+        assert_eq!(res.status_code, 403);
+        assert!(req.file("doc").is_none());
+        assert_eq!(stored(&folder), 0);
+
+        let (req, res) = routes.handle_http(request(BODY.to_string()).await, Response::new()).await;
+
+        assert_eq!(res.status_code, 200);
+        assert_eq!(req.file("doc").unwrap().stored.as_ref().unwrap().size, 5);
+        assert_eq!(stored(&folder), 1);
+
+        let _ = std::fs::remove_dir_all(folder);
+    }
+}
@@ -1,25 +1,31 @@
+use std::backtrace::Backtrace;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::future::Future;
 use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
+use chrono::Local;
 use futures::future::{join, BoxFuture};
 use futures::FutureExt;
 use once_cell::sync::OnceCell;
 use rustls::ServerConfig;
 use serde::Serialize;
 use tokio::runtime::Builder;
+use tracing::{Instrument, debug_span, field::Empty};
 use serde_json::Value;
 
 use crate::cookies::Cookies;
-use crate::error::Error;
-use crate::loggers::{Logger, LoggerErasure, LoggerWrapper};
+use crate::error::{Error, debug::{self, GLOBAL_PANIC_TRACE}};
+use crate::health::{self, Health};
+use crate::loggers::{Completed, Event, Logger, LoggerErasure, LoggerWrapper};
 use crate::hooks::form::FormHook;
-use crate::hooks::{Hook, HookErasure, HookWrapper};
-use crate::mail;
+use crate::hooks::{Hook, HookErasure, HookWrapper, request_id::RequestId};
+use crate::mail::{self, Mailer, preview, queue::MailQueue};
+use crate::metrics;
 use crate::request::Request;
 use crate::response::Response;
 use crate::routing::next::Next;
@@ -29,8 +35,9 @@ use crate::routing::routes::Routes;
 use crate::server::protocol::{tcp::Tcp, udp::Udp, ServerHandler};
 use crate::session::local::LocalSession;
 use crate::storage::{self, Storage};
+use crate::utils::env::is_development;
 use crate::utils::mem::Instance;
-use crate::view::View;
+use crate::view::{self, TemplateEngine, View, ViewData};
 use crate::websocket::Websocket;
 
 pub(crate) mod protocol;
@@ -41,6 +48,24 @@ tokio::task_local! {
 
 pub(crate) static GLOBAL_PANIC_IS_SET: OnceCell<()> = OnceCell::new();
 
+/// Requests being handled, waited for on shutdown.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+struct InFlight;
+
+impl InFlight {
+    fn start() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 pub(crate) type InitCallback = dyn Fn() -> BoxFuture<'static, ()> + Send + Sync;
 
 pub struct Server {
@@ -53,11 +78,14 @@ pub struct Server {
     pub(crate) view: Arc<dyn HookErasure>,
     pub(crate) multipart_form: Arc<dyn HookErasure>,
     pub(crate) hooks: Vec<Arc<dyn HookErasure>>,
+    pub(crate) request_id: Option<Arc<HookWrapper<RequestId>>>,
     pub(crate) server_config: Option<ServerConfig>,
     pub(crate) loggers: Vec<Arc<dyn LoggerErasure + Send + Sync>>,
     pub(crate) init_callbacks: Vec<Arc<InitCallback>>,
     pub(crate) before_hooks: Vec<Arc<dyn HookErasure>>,
     pub(crate) after_hooks: Vec<Arc<dyn HookErasure>>,
+    pub(crate) shutdown_delay: Duration,
+    pub(crate) shutdown_timeout: Duration,
 }
 
 impl Debug for Server {
@@ -96,11 +124,14 @@ impl Server {
             view: Arc::new(HookWrapper::new(View::new(None::<String>))),
             multipart_form: Arc::new(HookWrapper::new(FormHook::new())),
             hooks: Vec::new(),
+            request_id: None,
             server_config,
             loggers: Vec::new(),
             init_callbacks: Vec::new(),
             before_hooks: Vec::new(),
             after_hooks: Vec::new(),
+            shutdown_delay: Duration::ZERO,
+            shutdown_timeout: Duration::from_secs(30),
         }
     }
 
@@ -114,6 +145,16 @@ impl Server {
         self.routers.last_mut().unwrap()
     }
 
+    /// Panics when the cookie keys cannot seal and open cookies, e.g. an empty secret.
+    pub fn cookies(&mut self, cookies: Cookies) -> &mut Self {
+        if let Err(err) = cookies.check() {
+            panic!("Invalid cookie configuration: {:#}", err);
+        }
+
+        self.cookies = Arc::new(HookWrapper::new(cookies));
+        self
+    }
+
     pub fn session<H: Hook + 'static>(&mut self, hook: H) -> &mut Self {
         self.session = Arc::new(HookWrapper::new(hook));
         self
@@ -124,6 +165,21 @@ impl Server {
         self
     }
 
+    /// Renders views with another engine than the default Tera one.
+    pub fn view_engine<E: TemplateEngine + 'static>(&mut self, engine: E) -> &mut Self {
+        self.view = Arc::new(HookWrapper::new(View::with_engine(engine)));
+        self
+    }
+
+    /// Shares data with every view matching `views`; see `view::composer`.
+    pub fn view_composer<C>(&mut self, views: impl Into<String>, composer: C) -> &mut Self
+    where
+        C: Fn(&Request, &mut ViewData) + Send + Sync + 'static,
+    {
+        view::composer(views, composer);
+        self
+    }
+
     pub fn listen(&mut self) {
         Builder::new_multi_thread()
             .enable_all()
@@ -133,22 +189,28 @@ impl Server {
     }
 
     async fn setup(&mut self) {
+        if is_development() {
+            self.setup_global_panic_hook();
+        }
+
         self.prepare_hooks();
         Resolver::new(self);
         self.run().await;
     }
 
     fn prepare_hooks(&mut self) {
-        let extra = self.hooks.len();
+        let extra = self.hooks.len() + 1;
 
-        let mut before = Vec::with_capacity(4 + extra);
+        let mut before: Vec<Arc<dyn HookErasure>> = Vec::with_capacity(4 + extra);
+        before.extend(self.request_id.iter().map(|hook| Arc::clone(hook) as Arc<dyn HookErasure>));
         before.push(Arc::clone(&self.cookies));
         before.push(Arc::clone(&self.session));
         before.push(Arc::clone(&self.multipart_form));
         before.extend(self.hooks.iter().cloned());
         before.push(Arc::clone(&self.view));
 
-        let mut after = Vec::with_capacity(4 + extra);
+        let mut after: Vec<Arc<dyn HookErasure>> = Vec::with_capacity(4 + extra);
+        after.extend(self.request_id.iter().map(|hook| Arc::clone(hook) as Arc<dyn HookErasure>));
         after.push(Arc::clone(&self.multipart_form));
         after.extend(self.hooks.iter().cloned());
         after.push(Arc::clone(&self.session));
@@ -175,29 +237,120 @@ impl Server {
         self
     }
 
+    /// Gives every request an ID, assigned before any other hook runs; see `RequestId`.
+    pub fn request_id(&mut self, hook: RequestId) -> &mut Self {
+        self.request_id = Some(Arc::new(HookWrapper::new(hook)));
+        self
+    }
+
     pub fn logger<L: Logger + 'static>(&mut self, logger: L) -> &mut Self {
         self.setup_global_panic_hook();
         self.loggers.push(Arc::new(LoggerWrapper::new(logger)));
         self
     }
 
+    /// Serves every registered metric at `path` (usually `metrics::DEFAULT_METRICS_PATH`) in
+    /// the Prometheus text format; see the `metrics` module.
+    pub fn metrics(&mut self, path: impl Into<String>) -> &mut Self {
+        self.router().get(path, |_req, res| async move {
+            match metrics::render() {
+                Ok(body) => res.set_header("Content-Type", metrics::content_type()).body(body),
+                Err(err) => res.status_code(500).body(format!("{:#}", err)),
+            }
+        });
+        self
+    }
+
+    /// Serves the liveness and readiness probes of `health`, by default at `/healthz` and
+    /// `/readyz`; see `Health`.
+    pub fn health(&mut self, health: Health) -> &mut Self {
+        let health = Arc::new(health);
+        let (liveness, readiness) = (Arc::clone(&health), health);
+
+        self.router().get(liveness.liveness_path(), move |_req, res| {
+            let health = Arc::clone(&liveness);
+            async move { health.liveness(res).await }
+        });
+
+        self.router().get(readiness.readiness_path(), move |_req, res| {
+            let health = Arc::clone(&readiness);
+            async move { health.readiness(res).await }
+        });
+
+        self
+    }
+
+    /// How long to keep serving after a shutdown signal before waiting for the remaining
+    /// requests, so load balancers see the readiness probe fail first. Defaults to none.
+    pub fn shutdown_delay(&mut self, delay: Duration) -> &mut Self {
+        self.shutdown_delay = delay;
+        self
+    }
+
+    /// How long requests still running at shutdown may take before the server exits anyway.
+    /// Defaults to 30 seconds.
+    pub fn shutdown_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
     pub fn storage<S: Storage + 'static>(&mut self, name: impl Into<String>, storage: S) -> &mut Self {
         storage::add(name, storage);
         self
     }
 
-    pub fn mailer(
-        &mut self,
-        host: impl Into<String>,
-        port: u16,
-        username: impl Into<String>,
-        password: impl Into<String>,
-        tls: bool,
-    ) -> &mut Self {
-        mail::SMTP::init(host, port, username, password, tls).unwrap();
+    /// Registers a mailer under `name`; `Mail` sends through `default` unless told otherwise.
+    pub fn mailer<M: Mailer + 'static>(&mut self, name: impl Into<String>, mailer: M) -> &mut Self {
+        mail::add(name, mailer);
         self
     }
 
+    /// Serves the mails added with `mail::preview::add` at `path` (usually
+    /// `mail::preview::DEFAULT_PREVIEW_PATH`), in development only.
+    pub fn mail_previews(&mut self, path: impl Into<String>) -> &mut Self {
+        if !is_development() {
+            return self;
+        }
+
+        let base = format!("/{}", path.into().trim_matches('/'));
+        let router = self.router();
+
+        let index = base.clone();
+        router.get(base.clone(), move |_req, res| {
+            let base = index.clone();
+            async move { preview::index(&base, res) }
+        });
+
+        let show = base.clone();
+        router.get(format!("{}/{{mail}}", base), move |req, res| {
+            let base = show.clone();
+            async move { preview::show(&base, &req.parameter("mail"), res) }
+        });
+
+        let html = base.clone();
+        router.get(format!("{}/{{mail}}/html", base), move |req, res| {
+            let base = html.clone();
+            async move { preview::html(&base, &req.parameter("mail"), res) }
+        });
+
+        router.get(format!("{}/{{mail}}/cid/{{id}}", base), |req, res| async move {
+            preview::inline(&req.parameter("mail"), &req.parameter("id"), res).await
+        });
+
+        self
+    }
+
+    /// Delivers mail sent with `Mail::queue` and `Mail::later` through `queue`, starting its
+    /// worker with the server so mail stored before a restart goes out too.
+    pub fn mail_queue(&mut self, queue: MailQueue) -> &mut Self {
+        let queue = mail::queue::install(queue);
+
+        self.init(move || {
+            let queue = Arc::clone(&queue);
+            async move { queue.start() }
+        })
+    }
+
     pub fn init<C, Fut>(&mut self, callback: C) -> &mut Self
     where
         C: Fn() -> Fut + Send + Sync + 'static,
@@ -216,11 +369,48 @@ impl Server {
             });
         }
 
-        join(
+        let listeners = join(
             Udp::listen(self.get_instance()),
             Tcp::listen(self.get_instance()),
-        )
-        .await;
+        );
+
+        tokio::select! {
+            _ = listeners => {},
+            _ = self.shutdown() => {},
+        }
+    }
+
+    /// Waits for Ctrl+C or `SIGTERM`, then fails readiness and lets running requests finish
+    /// while the listeners keep serving.
+    async fn shutdown(&self) {
+        Self::shutdown_signal().await;
+        health::begin_shutdown();
+
+        tokio::time::sleep(self.shutdown_delay).await;
+
+        let deadline = Instant::now() + self.shutdown_timeout;
+
+        while IN_FLIGHT.load(Ordering::Relaxed) > 0 && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    async fn shutdown_signal() {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {},
+                    _ = terminate.recv() => {},
+                }
+
+                return;
+            }
+        }
+
+        let _ = tokio::signal::ctrl_c().await;
     }
 
     pub(crate) fn get_instance(&mut self) -> Instance<Server> {
@@ -257,34 +447,98 @@ impl Server {
         (req, res)
     }
 
-    pub(crate) async fn on_http(&self, req: Request, mut res: Response) -> (Request, Response) {
-        GLOBAL_PANIC_CONTEXT.scope(RefCell::new(Error::default()), async move {
+    pub(crate) async fn on_http(&self, mut req: Request, mut res: Response) -> (Request, Response) {
+        let received_at = Local::now();
+        let started = Instant::now();
+        let _in_flight = InFlight::start();
+
+        let span = tracing::info_span!(
+            "request",
+            otel.name = %req.method,
+            http.request.method = %req.method,
+            url.path = %req.path,
+            network.protocol.name = %req.protocol,
+            http.route = Empty,
+            http.response.status_code = Empty,
+            request_id = Empty,
+        );
+
+        let (req, res) = async {
+            if let Some(hook) = &self.request_id {
+                hook.instance.assign(&mut req, &mut res);
+            }
+
+            self.handle_http(req, res).await
+        }
+        .instrument(span.clone())
+        .await;
+
+        span.record("http.response.status_code", res.status_code);
+
+        if let Some(route) = &req.route {
+            span.record("http.route", route.as_str());
+            span.record("otel.name", route.as_str());
+        }
+
+        let duration = started.elapsed();
+        metrics::record_request(&req, &res, duration);
+
+        if self.loggers.iter().any(|logger| logger.wants_completed()) {
+            self.emit(Event::Completed(Completed {
+                request: req.clone(),
+                response: res.clone(),
+                duration,
+                received_at,
+            }));
+        }
+
+        (req, res)
+    }
+
+    async fn handle_http(&self, req: Request, mut res: Response) -> (Request, Response) {
+        GLOBAL_PANIC_CONTEXT.scope(RefCell::new(Error::default()), GLOBAL_PANIC_TRACE.scope(RefCell::new(None), async move {
             res.referer = req.header("referer");
 
             let req_backup = req.clone();
             let res_backup = res.clone();
 
             let result = AssertUnwindSafe(async {
-                let (next, req, res) = self.call_before_hooks(req, res).await;
+                let (next, req, res) = self.call_before_hooks(req, res).instrument(debug_span!("before_hooks")).await;
                 if !next {
                     return (req, res);
                 }
 
-                let (req, res) = self.routes.handle_http(req, res).await;
-                self.call_after_hooks(req, res).await
+                let (req, res) = self.routes.handle_http(req, res).instrument(debug_span!("handler")).await;
+                self.call_after_hooks(req, res).instrument(debug_span!("after_hooks")).await
             })
             .catch_unwind()
             .await;
 
             match result {
-                Ok(out) => out,
+                Ok((req, mut res)) => match res.error.take() {
+                    Some(error) => {
+                        self.on_logger(error.clone(), req.clone(), res.clone()).await;
+                        self.routes.handle_error(error, req, res).await
+                    }
+                    None => (req, res),
+                },
                 Err(_) => {
                     let error = GLOBAL_PANIC_CONTEXT.with(|cell| cell.borrow().clone());
                     self.on_logger(error.clone(), req_backup.clone(), res_backup.clone()).await;
-                    self.routes.handle_error(error, req_backup, res_backup).await
+
+                    if !is_development() {
+                        return self.routes.handle_error(error, req_backup, res_backup).await;
+                    }
+
+                    // Error handlers may still replace the page.
+                    let trace = GLOBAL_PANIC_TRACE.with(|cell| cell.borrow_mut().take());
+                    let hooks = self.before_hooks.iter().map(|hook| hook.name()).collect();
+                    let res = debug::render(res_backup, &error, &req_backup, trace, hooks);
+
+                    self.routes.handle_error(error, req_backup, res).await
                 }
             }
-        }).await
+        })).await
     }
 
     pub(crate) async fn on_websocket(&self, req: Request, res: Response) -> Option<Websocket> {
@@ -320,14 +574,20 @@ impl Server {
     }
 
     pub(crate) async fn on_logger(&self, info: Error, req: Request, res: Response) {
-        for logger in &self.loggers {
+        self.emit(Event::Error(info, req, res));
+    }
+
+    /// Hands `event` to every logger, without waiting for them. Completed requests only go to
+    /// the loggers that want them.
+    pub fn emit(&self, event: Event) {
+        let completed = matches!(event, Event::Completed(_));
+
+        for logger in self.loggers.iter().filter(|logger| !completed || logger.wants_completed()) {
             let logger = Arc::clone(logger);
-            let info = info.clone();
-            let req = req.clone();
-            let res = res.clone();
+            let event = event.clone();
 
             tokio::spawn(async move {
-                logger.call(info, req, res).await;
+                logger.event(event).await;
             });
         }
     }
@@ -336,10 +596,16 @@ impl Server {
         GLOBAL_PANIC_IS_SET.get_or_init(|| {
             panic::set_hook(Box::new(|info| {
                 let _ = GLOBAL_PANIC_CONTEXT.try_with(|cell| {
-                    *cell.borrow_mut() = Error::new(
+                    let mut error = Error::new(
                         info.to_string(),
                         info.payload_as_str().unwrap_or("").into(),
                     );
+
+                    if is_development() {
+                        error.backtrace = Some(Backtrace::force_capture().to_string());
+                    }
+
+                    *cell.borrow_mut() = error;
                 });
             }));
         });
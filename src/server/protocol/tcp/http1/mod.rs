@@ -2,9 +2,11 @@ use std::io::{Error, ErrorKind, Write};
 use std::net::SocketAddr;
 
 use anyhow::Result;
-use bytes::{Buf, BytesMut};
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{StreamExt, stream::{self, BoxStream}};
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 
+use crate::metrics;
 use crate::request::form::Form;
 use crate::request::Request;
 use crate::response::Response;
@@ -19,6 +21,21 @@ use crate::utils::Values;
 pub mod ws;
 
 const MAX_HEADER_LENGTH: usize = 8192; // 8KB standard cap
+const READ_SIZE: usize = 64 * 1024;
+
+/// How the length of a request body is given.
+enum BodyLength {
+    Fixed(u64),
+    Chunked,
+}
+
+/// Where a chunked body is at between reads.
+struct Chunked<R> {
+    reader: R,
+    buffer: BytesMut,
+    /// Bytes of the current chunk not yet read.
+    remaining: usize,
+}
 
 pub struct Http1 {
     server: Instance<Server>,
@@ -34,8 +51,8 @@ impl TcpHandler for Http1 {
     where
         RW: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
     {
-        let req = match self.deserialize(&mut rw).await {
-            Ok(req) => req,
+        let (mut req, leftover, length) = match self.deserialize(&mut rw).await {
+            Ok(parsed) => parsed,
             Err(_) => return Ok(()),
         };
 
@@ -43,18 +60,28 @@ impl TcpHandler for Http1 {
             return Ws::new(self.server.clone(), self.addr).handle(rw, req).await;
         }
 
+        let (reader, mut writer) = tokio::io::split(rw);
+
+        if req.read_body(Self::body(reader, leftover, length)).await.is_err() {
+            return Ok(());
+        }
+
+        let _connection = metrics::connection("http1");
+
         let (_, res) = self.server.as_mut().on_http(req, Response::new()).await;
         let serialized_res = Self::serialize(&res);
 
-        rw.write_all(&serialized_res).await?;
-        rw.flush().await?;
+        writer.write_all(&serialized_res).await?;
+        writer.flush().await?;
 
         Ok(())
     }
 }
 
 impl Http1 {
-    async fn deserialize<RW>(&mut self, rw: &mut BufReader<RW>) -> Result<Request>
+    /// Parses the request head; the body is left on the connection, with whatever part of it
+    /// was read along with the head.
+    async fn deserialize<RW>(&mut self, rw: &mut BufReader<RW>) -> Result<(Request, BytesMut, BodyLength)>
     where
         RW: AsyncRead + AsyncWrite + Unpin + Send + Sync,
     {
@@ -107,14 +134,13 @@ impl Http1 {
                 is_chunked = true;
             }
 
-            headers.insert(name_lower, val_str.to_string());
+            headers.append(name_lower, val_str.to_string());
         }
 
-        let body = if is_chunked {
-            self.read_chunked_body(rw, leftover_body).await?
+        let length = if is_chunked {
+            BodyLength::Chunked
         } else {
-            self.read_fixed_body(rw, leftover_body, content_length)
-                .await?
+            BodyLength::Fixed(content_length)
         };
 
         let raw_url = parsed_req.path.unwrap_or("");
@@ -125,113 +151,126 @@ impl Http1 {
 
         let host = headers.get("host").cloned().unwrap_or_default();
 
-        Ok(Request {
+        let req = Request {
             server: self.server.clone(),
             addr: self.addr,
             protocol: "HTTP/1.1".to_string(),
             method: parsed_req.method.unwrap_or("GET").to_string(),
             path: path.to_string(),
-            queries: queries,
-            host: host,
-            headers: headers,
+            queries,
+            host,
+            headers,
             parameters: Values::new(),
             cookies: Default::default(),
             session: Default::default(),
-            body: body.into(),
+            body: Default::default(),
+            multipart: Default::default(),
             form: Form::default(),
-        })
+            identity: None,
+            locale: None,
+            route: None,
+            id: None,
+        };
+
+        Ok((req, leftover_body, length))
     }
 
-    async fn read_fixed_body<RW>(
-        &mut self,
-        rw: &mut BufReader<RW>,
-        mut leftover: BytesMut,
-        content_length: u64,
-    ) -> Result<Vec<u8>>
+    /// Reads the body as it is consumed rather than all at once.
+    fn body<R>(reader: R, leftover: BytesMut, length: BodyLength) -> BoxStream<'static, Result<Bytes>>
     where
-        RW: AsyncRead + Unpin + Send + Sync,
+        R: AsyncRead + Unpin + Send + 'static,
     {
-        if content_length == 0 {
-            return Ok(Vec::new());
+        match length {
+            BodyLength::Fixed(length) => Self::fixed_body(reader, leftover, length).boxed(),
+            BodyLength::Chunked => Self::chunked_body(reader, leftover).boxed(),
         }
+    }
 
-        let cl_usize = content_length as usize;
-
-        // If leftover buffer already contains the entire body (or more)
-        if leftover.len() >= cl_usize {
-            let body_bytes = leftover.split_to(cl_usize);
-            return Ok(body_bytes.to_vec());
-        }
+    fn fixed_body<R>(reader: R, leftover: BytesMut, length: u64) -> impl futures::Stream<Item = Result<Bytes>>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        stream::try_unfold((reader, leftover, length), |(mut reader, mut buffer, remaining)| async move {
+            if remaining == 0 {
+                return Ok(None);
+            }
 
-        // Pre-allocate required capacity once up front
-        let mut body = Vec::with_capacity(cl_usize);
-        let leftover_len = leftover.len();
-        body.extend_from_slice(&leftover);
+            if buffer.is_empty() {
+                Self::fill(&mut reader, &mut buffer, "Truncated body").await?;
+            }
 
-        let remaining = cl_usize - leftover_len;
-        let mut limited = rw.take(remaining as u64);
-        limited.read_to_end(&mut body).await?;
+            let chunk = buffer.split_to(buffer.len().min(remaining as usize)).freeze();
+            let remaining = remaining - chunk.len() as u64;
 
-        Ok(body)
+            Ok(Some((chunk, (reader, buffer, remaining))))
+        })
     }
 
-    async fn read_chunked_body<RW>(
-        &mut self,
-        rw: &mut BufReader<RW>,
-        mut buf: BytesMut,
-    ) -> Result<Vec<u8>>
+    fn chunked_body<R>(reader: R, buffer: BytesMut) -> impl futures::Stream<Item = Result<Bytes>>
     where
-        RW: AsyncRead + Unpin + Send + Sync,
+        R: AsyncRead + Unpin + Send + 'static,
     {
-        let mut body = Vec::new();
-
-        loop {
-            // Find CRLF line boundary for chunk size
-            let line_end = loop {
-                if let Some(pos) = buf.windows(2).position(|w| w == b"\r\n") {
-                    break pos;
-                }
-                if rw.read_buf(&mut buf).await? == 0 {
-                    return Err(
-                        Error::new(ErrorKind::UnexpectedEof, "Truncated chunk size").into(),
-                    );
-                }
-            };
-
-            let size_bytes = buf.split_to(line_end);
-            buf.advance(2); // Skip \r\n
-
-            let size_str = std::str::from_utf8(&size_bytes)
-                .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid UTF-8 in chunk size"))?;
-            let hex_str = size_str.split(';').next().unwrap_or("").trim();
-            let chunk_size = usize::from_str_radix(hex_str, 16)
-                .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid hex chunk size"))?;
-
-            if chunk_size == 0 {
-                // Read trailing CRLF for end chunk if needed
-                while buf.len() < 2 {
-                    if rw.read_buf(&mut buf).await? == 0 {
-                        break;
+        let state = Chunked { reader, buffer, remaining: 0 };
+
+        stream::try_unfold(state, |mut state| async move {
+            if state.remaining == 0 {
+                // Find CRLF line boundary for chunk size
+                let line_end = loop {
+                    if let Some(pos) = state.buffer.windows(2).position(|w| w == b"\r\n") {
+                        break pos;
+                    }
+                    if state.buffer.len() >= MAX_HEADER_LENGTH {
+                        return Err(Error::new(ErrorKind::InvalidData, "Chunk size line too long").into());
                     }
+                    Self::fill(&mut state.reader, &mut state.buffer, "Truncated chunk size").await?;
+                };
+
+                let size_bytes = state.buffer.split_to(line_end);
+                state.buffer.advance(2); // Skip \r\n
+
+                let size_str = std::str::from_utf8(&size_bytes)
+                    .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid UTF-8 in chunk size"))?;
+                let hex_str = size_str.split(';').next().unwrap_or("").trim();
+                let chunk_size = usize::from_str_radix(hex_str, 16)
+                    .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid hex chunk size"))?;
+
+                if chunk_size == 0 {
+                    return Ok(None);
                 }
-                break;
+
+                state.remaining = chunk_size;
             }
 
-            // Ensure full chunk payload + trailing CRLF (2 bytes) is buffered
-            let total_needed = chunk_size + 2;
-            while buf.len() < total_needed {
-                if rw.read_buf(&mut buf).await? == 0 {
-                    return Err(
-                        Error::new(ErrorKind::UnexpectedEof, "Truncated chunk body").into(),
-                    );
+            if state.buffer.is_empty() {
+                Self::fill(&mut state.reader, &mut state.buffer, "Truncated chunk body").await?;
+            }
+
+            let chunk = state.buffer.split_to(state.buffer.len().min(state.remaining)).freeze();
+            state.remaining -= chunk.len();
+
+            if state.remaining == 0 {
+                // Skip the CRLF closing the chunk data
+                while state.buffer.len() < 2 {
+                    Self::fill(&mut state.reader, &mut state.buffer, "Truncated chunk body").await?;
                 }
+                state.buffer.advance(2);
             }
 
-            body.extend_from_slice(&buf[..chunk_size]);
-            buf.advance(total_needed); // Advance past chunk data + CRLF
+            Ok(Some((chunk, state)))
+        })
+    }
+
+    async fn fill<R>(reader: &mut R, buffer: &mut BytesMut, truncated: &'static str) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        buffer.reserve(READ_SIZE);
+
+        if reader.read_buf(buffer).await? == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, truncated).into());
         }
 
-        Ok(body)
+        Ok(())
     }
 
     fn serialize(res: &Response) -> Vec<u8> {
@@ -254,4 +293,33 @@ impl Http1 {
 
         serialized
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    async fn read(rest: &'static [u8], leftover: &[u8], length: BodyLength) -> Result<Vec<u8>> {
+        let chunks: Vec<Bytes> = Http1::body(rest, BytesMut::from(leftover), length).try_collect().await?;
+
+        Ok(chunks.concat())
+    }
+
+    #[tokio::test]
+    async fn fixed_bodies_stop_at_the_content_length() {
+        assert_eq!(read(b"llo world", b"he", BodyLength::Fixed(5)).await.unwrap(), b"hello");
+        assert_eq!(read(b"", b"hello!", BodyLength::Fixed(5)).await.unwrap(), b"hello");
+        assert!(read(b"", b"", BodyLength::Fixed(0)).await.unwrap().is_empty());
+        assert!(read(b"lo", b"hel", BodyLength::Fixed(6)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn chunked_bodies_are_decoded_across_reads() {
+        let body = read(b"lo\r\n6;ext=1\r\n world\r\n0\r\n\r\n", b"5\r\nhel", BodyLength::Chunked).await.unwrap();
+
+        assert_eq!(body, b"hello world");
+        assert!(read(b"hel", b"5\r\n", BodyLength::Chunked).await.is_err());
+        assert!(read(b"zz\r\n", b"", BodyLength::Chunked).await.is_err());
+    }
+}
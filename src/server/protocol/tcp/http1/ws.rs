@@ -11,6 +11,7 @@ use tokio_tungstenite::{
     WebSocketStream,
 };
 
+use crate::metrics;
 use crate::request::Request;
 use crate::response::Response;
 use crate::server::protocol::tcp::http1::Http1;
@@ -74,6 +75,8 @@ impl Ws {
     where
         RW: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
     {
+        let _connection = metrics::connection("websocket");
+
         Self::handshake(&mut rw, &mut req).await?;
         
         let result = self.server.as_mut().on_websocket(req, Response::new()).await;
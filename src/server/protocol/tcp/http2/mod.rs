@@ -1,12 +1,14 @@
 use std::net::SocketAddr;
 
 use anyhow::{Context, Result};
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
+use futures::{Stream, stream};
 use h2::server::{self, SendResponse};
 use h2::RecvStream;
 use tokio::io::{AsyncRead, AsyncWrite, BufReader};
 
 use crate::cookies::Cookies;
+use crate::metrics;
 use crate::request::form::Form;
 use crate::request::Request;
 use crate::response::Response;
@@ -34,6 +36,8 @@ impl TcpHandler for Http2 {
     where
         RW: AsyncRead + AsyncWrite + Unpin + Send + Sync,
     {
+        let _connection = metrics::connection("http2");
+
         let mut connection = server::handshake(rw)
             .await
             .map_err(|err| anyhow::anyhow!("H2 Handshake error: {err}"))?;
@@ -78,8 +82,7 @@ impl Http2 {
         request: http::Request<RecvStream>,
     ) -> Result<Request> {
         let (parts, body_stream) = request.into_parts();
-        
-        let body = Self::read_full_body(body_stream).await?;
+
         let host = parts
             .uri
             .authority()
@@ -97,41 +100,51 @@ impl Http2 {
 
         for (k, v) in parts.headers.iter() {
             if let Ok(val_str) = v.to_str() {
-                headers.insert(k.as_str().to_string(), val_str.to_string());
+                headers.append(k.as_str().to_string(), val_str.to_string());
             }
         }
 
         let path = parts.uri.path().to_string();
         let queries = parse_query(parts.uri.query().unwrap_or(""));
 
-        Ok(Request {
-            server: server,
-            addr: addr,
+        let mut req = Request {
+            server,
+            addr,
             protocol: "HTTP/2.0".into(),
             method: parts.method.to_string(),
-            path: path,
-            queries: queries,
-            host: host,
-            headers: headers,
+            path,
+            queries,
+            host,
+            headers,
             cookies: Cookies::new(),
             session: Session::new(),
-            body: body,
+            body: Bytes::new(),
+            multipart: Default::default(),
             parameters: Values::new(),
             form: Form::new(Default::default(), Default::default()),
-        })
+            identity: None,
+            locale: None,
+            route: None,
+            id: None,
+        };
+
+        req.read_body(Self::body(body_stream)).await?;
+
+        Ok(req)
     }
 
-    async fn read_full_body(mut stream: RecvStream) -> Result<Bytes> {
-        let mut buf = BytesMut::new();
+    /// Reads the body as it is consumed, releasing flow control capacity chunk by chunk.
+    fn body(stream: RecvStream) -> impl Stream<Item = Result<Bytes>> + Send + 'static {
+        stream::try_unfold(stream, |mut stream| async move {
+            let Some(chunk) = stream.data().await else {
+                return Ok(None);
+            };
 
-        while let Some(chunk) = stream.data().await {
             let chunk = chunk.context("Error reading H2 request body stream")?;
             let _ = stream.flow_control().release_capacity(chunk.len());
 
-            buf.extend_from_slice(&chunk);
-        }
-
-        Ok(buf.freeze())
+            Ok(Some((chunk, stream)))
+        })
     }
 
     pub async fn write(mut send_response: SendResponse<Bytes>, res: Response) -> Result<()> {
@@ -1,12 +1,13 @@
 use std::net::SocketAddr;
 
 use anyhow::{Context, Result};
-use bytes::{Buf, Bytes, BytesMut};
-use h3::server::RequestStream;
-use h3_quinn::BidiStream;
+use bytes::{Buf, Bytes};
+use futures::{Stream, stream};
+use h3::{quic::{RecvStream, SendStream}, server::RequestStream};
 
 use crate::{
     cookies::Cookies,
+    metrics,
     request::{form::Form, Request},
     response::Response,
     server::{protocol::UdpHandler, Server},
@@ -25,6 +26,8 @@ impl UdpHandler for Http3 {
     }
 
     async fn handle(&mut self, mut server: h3::server::Connection<h3_quinn::Connection, Bytes>) -> Result<()> {
+        let _connection = metrics::connection("http3");
+
         while let Ok(Some(resolver)) = server.accept().await {
             let server = self.server.clone();
             let addr = self.addr;
@@ -46,29 +49,30 @@ impl Http3 {
         addr: SocketAddr,
         resolver: h3::server::RequestResolver<h3_quinn::Connection, Bytes>,
     ) -> Result<()> {
-        let (request, mut stream) = resolver
+        let (request, stream) = resolver
             .resolve_request()
             .await
             .context("Failed to resolve HTTP/3 request")?;
 
-        let req = Self::deserialize(server.clone(), addr, &request, &mut stream).await?;
+        let (mut send, recv) = stream.split();
+        let req = Self::deserialize(server.clone(), addr, &request, recv).await?;
 
         let (_, res) = server.as_mut().on_http(req, Response::new()).await;
 
-        Self::write(&mut stream, res).await
+        Self::write(&mut send, res).await
     }
 
     async fn deserialize(
         server: Instance<Server>,
         addr: SocketAddr,
         request: &http::Request<()>,
-        stream: &mut RequestStream<BidiStream<Bytes>, Bytes>,
+        recv: RequestStream<impl RecvStream + Send + 'static, Bytes>,
     ) -> Result<Request> {
         let mut headers = Headers::new();
 
         for (k, v) in request.headers() {
             if let Ok(val_str) = v.to_str() {
-                headers.insert(k.as_str().to_string(), val_str.to_string());
+                headers.append(k.as_str().to_string(), val_str.to_string());
             }
         }
 
@@ -85,43 +89,54 @@ impl Http3 {
             .unwrap_or_default()
             .to_string();
 
-        let body = Self::read_body(stream).await.unwrap_or_default();
-
         let path = request.uri().path().to_string();
         let queries = parse_query(request.uri().query().unwrap_or(""));
 
-        Ok(Request {
-            server: server,
-            addr: addr,
+        let mut req = Request {
+            server,
+            addr,
             protocol: "HTTP/3.0".into(),
             method: request.method().as_str().to_string(),
-            path: path,
-            queries: queries,
-            host: host,
-            headers: headers,
+            path,
+            queries,
+            host,
+            headers,
             cookies: Cookies::new(),
             session: Session::new(),
-            body: body,
+            body: Bytes::new(),
+            multipart: Default::default(),
             parameters: Values::new(),
             form: Form::new(Default::default(), Default::default()),
-        })
-    }
+            identity: None,
+            locale: None,
+            route: None,
+            id: None,
+        };
 
-    async fn read_body(stream: &mut RequestStream<BidiStream<Bytes>, Bytes>) -> Result<Bytes> {
-        let mut buf = BytesMut::new();
+        // A body that fails to arrive is read as empty.
+        let _ = req.read_body(Self::body(recv)).await;
 
-        while let Some(mut chunk) = stream.recv_data().await? {
-            while chunk.has_remaining() {
-                let slice = chunk.chunk();
-                buf.extend_from_slice(slice);
-                chunk.advance(slice.len());
-            }
-        }
+        Ok(req)
+    }
 
-        Ok(buf.freeze())
+    /// Reads the body as it is consumed rather than all at once.
+    fn body<S>(recv: RequestStream<S, Bytes>) -> impl Stream<Item = Result<Bytes>> + Send + 'static
+    where
+        S: RecvStream + Send + 'static,
+    {
+        stream::try_unfold(recv, |mut recv| async move {
+            let Some(mut chunk) = recv.recv_data().await? else {
+                return Ok(None);
+            };
+
+            Ok(Some((chunk.copy_to_bytes(chunk.remaining()), recv)))
+        })
     }
 
-    pub async fn write(stream: &mut RequestStream<BidiStream<Bytes>, Bytes>, res: Response) -> Result<()> {
+    pub async fn write<S>(stream: &mut RequestStream<S, Bytes>, res: Response) -> Result<()>
+    where
+        S: SendStream<Bytes>,
+    {
         let mut builder = http::Response::builder().status(res.status_code);
 
         for (k, v) in &res.headers {
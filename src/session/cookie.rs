@@ -1,119 +1,331 @@
 use std::time::Duration;
 
-use aes_gcm::{
-    AeadCore,
-    Aes256Gcm,
-    Key,
-    Nonce,
-    aead::{Aead, KeyInit, OsRng}
-};
 use anyhow::Result;
-use base64::{Engine, engine::general_purpose};
 
 use crate::{
+    cookies::SameSite,
+    error::Error,
     hooks::Hook,
     request::Request,
     response::Response,
     routing::next::Next,
-    session::Session
+    session::Session,
+    utils::crypto::{self, Key}
 };
 
+const KEY_CONTEXT: &str = "flyer.session.cookie";
+
+/// Browsers drop cookies over 4096 bytes including name and attributes, so payloads are
+/// split into chunks that leave room for both.
+const CHUNK_SIZE: usize = 3800;
+
+const MAX_CHUNKS: usize = 8;
+
+const CHUNK_MARKER: &str = "chunks.";
+
+/// Keeps the whole session in an encrypted (AES-256-GCM) cookie, so no server side storage
+/// is needed. Sessions larger than one cookie are split over `name.0`, `name.1`, ... cookies.
 pub struct CookieSession {
     cookie_name: String,
-    encryption_key: String,
+    keys: Vec<Key>,
     duration: Duration,
+    absolute: Option<Duration>,
+    secure: bool,
+    http_only: bool,
+    same_site: SameSite,
 }
 
 impl CookieSession {
-    pub fn new(cookie_name: impl Into<String>, encryption_key: impl Into<String>, expires: Duration) -> Self {
+    pub fn new(cookie_name: impl Into<String>, secret: impl Into<String>, expires: Duration) -> Self {
         return Self {
             cookie_name: cookie_name.into(),
-            encryption_key:  Self::string_fixed_length(&encryption_key.into(), 32),
+            keys: vec![crypto::derive_key(&secret.into(), KEY_CONTEXT)],
             duration: expires,
+            absolute: None,
+            secure: true,
+            http_only: true,
+            same_site: SameSite::Lax,
         };
     }
+
+    /// Secrets the session was encrypted with before the current one. Cookies sealed with them
+    /// are still accepted and re-encrypted with the current secret on the next response.
+    pub fn previous_secrets<T: Into<String>>(mut self, secrets: Vec<T>) -> Self {
+        self.keys.truncate(1);
+        self.keys.extend(secrets.into_iter().map(|secret| crypto::derive_key(&secret.into(), KEY_CONTEXT)));
+        self
+    }
+
+    /// Expires sessions this long after they were created, however active they are.
+    pub fn absolute_timeout(mut self, lifetime: Duration) -> Self {
+        self.absolute = Some(lifetime);
+        self
+    }
+
+    /// Sends the cookie over HTTPS only. Enabled by default; disable for plain HTTP in development.
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
 }
 
 impl Hook for CookieSession {
-    async fn before(&self, mut req: Request, res: Response, next: Next) -> Response {
-        let hash = req.cookie(self.cookie_name.clone());
+    async fn before(&self, mut req: Request, mut res: Response, next: Next) -> Response {
+        let payload = self.read_payload(&req);
 
-        if hash.is_empty() {
+        if payload.is_empty() {
             return next.handle(req, res);
         }
 
-        let result = self.decrypt(&hash);
+        let session = self
+            .decrypt(&payload)
+            .ok()
+            .and_then(|data| serde_json::from_str::<Session>(&data).ok());
 
-        if result.is_err() {
+        let Some(session) = session else {
             return next.handle(req, res);
-        }
-
-        let result = serde_json::from_str::<Session>(&result.unwrap());
+        };
 
-        if result.is_err() {
+        if session.is_expired(self.duration, self.absolute) {
             return next.handle(req, res);
         }
 
-        req.session = result.unwrap();
+        req.session = session;
+        res.session.resume(&req.session);
 
         return next.handle(req, res);
     }
-    
+
     async fn after(&self, req: Request, mut res: Response, next: Next) -> Response {
-        let data = serde_json::to_string(&res.session)
-            .unwrap();
-        let payload = self
-            .encrypt(&data)
-            .unwrap();
-        let cookie = res
-            .set_cookie(self.cookie_name.clone(), payload)
-            .set_expires(self.duration)
-            .set_same_site(crate::cookies::SameSite::Lax)
-            .set_path("/")
-            .parse();
+        let previous_chunks = self.chunk_count(&req.cookie(self.cookie_name.clone())).unwrap_or(0);
+
+        if res.session.invalidated && res.session.session.is_empty() {
+            res.session.invalidated = false;
+            res.session.regenerate = false;
+
+            self.remove(&mut res, self.cookie_name.clone());
+            self.remove_chunks(&mut res, 0, previous_chunks);
 
-        return next.handle(req, res.set_header("Set-Cookie", cookie));
+            return next.handle(req, res);
+        }
+
+        // Every write is sealed with a fresh nonce, so regenerating needs no extra work here.
+        res.session.regenerate = false;
+        res.session.invalidated = false;
+        res.session.touch();
+
+        let payload = serde_json::to_string(&res.session)
+            .map_err(anyhow::Error::from)
+            .and_then(|data| self.encrypt(&data));
+
+        let payload = match payload {
+            Ok(payload) => payload,
+            Err(err) => {
+                res.error = Some(Error::new("Failed to seal session cookie".to_string(), format!("{:#}", err)));
+
+                return next.handle(req, res);
+            }
+        };
+
+        if payload.len() <= CHUNK_SIZE {
+            self.write(&mut res, self.cookie_name.clone(), payload);
+            self.remove_chunks(&mut res, 0, previous_chunks);
+
+            return next.handle(req, res);
+        }
+
+        let count = payload.len().div_ceil(CHUNK_SIZE);
+
+        // The client keeps its previous cookie, so only this request's session changes are lost.
+        if count > MAX_CHUNKS {
+            let message = format!(
+                "Session cookie '{}' needs {} bytes but at most {} fit in cookies; store less in the session or use a StoreSession",
+                self.cookie_name,
+                payload.len(),
+                CHUNK_SIZE * MAX_CHUNKS
+            );
+            res.error = Some(Error::new("Session too large for cookies".to_string(), message));
+
+            return next.handle(req, res);
+        }
+
+        for (index, chunk) in payload.as_bytes().chunks(CHUNK_SIZE).enumerate() {
+            // Payloads are base64url, so every chunk boundary falls on a char boundary.
+            self.write(&mut res, format!("{}.{}", self.cookie_name, index), String::from_utf8_lossy(chunk));
+        }
+
+        self.remove_chunks(&mut res, count, previous_chunks);
+
+        self.write(&mut res, self.cookie_name.clone(), format!("{}{}", CHUNK_MARKER, count));
+
+        next.handle(req, res)
     }
 }
 
 impl CookieSession {
-    pub fn string_fixed_length(input: &str, length: usize) -> String {
-        return match input.len() < length {
-            true => format!("{:<width$}", input, width = length),
-            false => input.chars().take(length).collect(),
-        };
+    pub fn encrypt(&self, data: &str) -> Result<String> {
+        crypto::encrypt(&self.keys[0], data.as_bytes())
     }
 
-    pub fn encrypt(&self, data: &str) -> Result<String> {
-        let key = Key::<Aes256Gcm>::from_slice(self.encryption_key.as_bytes());
-        let cipher = Aes256Gcm::new(key);
-        let nonce = Aes256Gcm::generate_nonce(OsRng);
-        let cipher_text = cipher.encrypt(&nonce, data.as_bytes()).unwrap();
-        let mut combined = nonce.to_vec();
+    /// Fails on truncated, tampered or foreign cookies instead of panicking.
+    pub fn decrypt(&self, hash: &str) -> Result<String> {
+        let plaintext = crypto::decrypt(&self.keys, hash)?;
 
-        combined.extend_from_slice(&cipher_text);
+        String::from_utf8(plaintext).map_err(|err| err.into())
+    }
 
-        let mut buffer = String::new();
+    fn chunk_count(&self, value: &str) -> Option<usize> {
+        value
+            .strip_prefix(CHUNK_MARKER)
+            .and_then(|count| count.parse::<usize>().ok())
+            .filter(|count| *count <= MAX_CHUNKS)
+    }
 
-        general_purpose::STANDARD.encode_string(combined, &mut buffer);
+    fn read_payload(&self, req: &Request) -> String {
+        let value = req.cookie(self.cookie_name.clone());
 
-        return Ok(buffer);
+        match self.chunk_count(&value) {
+            Some(count) => (0..count)
+                .map(|index| req.cookie(format!("{}.{}", self.cookie_name, index)))
+                .collect(),
+            None => value,
+        }
     }
 
-    pub fn decrypt(&self, hash: &str) -> Result<String> {
-        let key = Key::<Aes256Gcm>::from_slice(self.encryption_key.as_bytes());
-        let cipher = Aes256Gcm::new(key);
-        let combined = general_purpose::STANDARD.decode(hash);
+    fn write(&self, res: &mut Response, name: String, value: impl Into<String>) {
+        res
+            .set_cookie(name, value)
+            .set_expires(self.duration)
+            .set_same_site(self.same_site)
+            .set_secure(self.secure)
+            .set_http_only(self.http_only)
+            .set_path("/");
+    }
+
+    fn remove(&self, res: &mut Response, name: String) {
+        res
+            .set_cookie(name, "")
+            .set_max_age(Duration::ZERO)
+            .set_secure(self.secure)
+            .set_http_only(self.http_only)
+            .set_path("/");
+    }
+
+    /// Expires chunk cookies left over from a larger session.
+    fn remove_chunks(&self, res: &mut Response, from: usize, to: usize) {
+        for index in from..to {
+            self.remove(res, format!("{}.{}", self.cookie_name, index));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cookies::Cookies;
+
+    fn hook() -> CookieSession {
+        CookieSession::new("session", "a secret long enough for the tests", Duration::from_secs(60))
+    }
+
+    /// The `name=value` pairs a browser would send back after `res`, expired cookies left out.
+    async fn cookie_header(res: Response) -> String {
+        let res = Cookies::new().after(Request::fake("GET", "/"), res, Next::new()).await;
+
+        res.headers
+            .get_all("Set-Cookie")
+            .iter()
+            .filter(|cookie| !cookie.contains("Max-Age=0"))
+            .filter_map(|cookie| cookie.split(';').next())
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    async fn save(hook: &CookieSession, values: &[(&str, &str)]) -> Response {
+        let mut res = Response::new();
 
-        if combined.is_err() {
-            return Ok(String::new());
+        for (key, value) in values {
+            res.session.set(*key, *value);
         }
 
-        let session = combined.unwrap();
-        let (nonce_bytes, ciphertext) = session.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
-        let plaintext = cipher.decrypt(nonce, ciphertext).unwrap();
+        hook.after(Request::fake("GET", "/"), res, Next::new()).await
+    }
+
+    async fn load(hook: &CookieSession, cookies: &str) -> Session {
+        let mut req = Request::fake("GET", "/");
+        req.headers.append("cookie", cookies);
+
+        let mut res = Cookies::new().before(req, Response::new(), Next::new()).await;
+        let req = res.request();
+        let mut res = hook.before(req, Response::new(), Next::new()).await;
+
+        res.request().session
+    }
+
+    #[tokio::test]
+    async fn session_round_trips() {
+        let hook = hook();
+        let cookies = cookie_header(save(&hook, &[("user", "jane")]).await).await;
+
+        assert!(cookies.starts_with("session="));
+        assert!(!cookies.contains("jane"));
+        assert_eq!(load(&hook, &cookies).await.get("user"), "jane");
+    }
+
+    #[tokio::test]
+    async fn tampered_or_truncated_cookies_are_ignored() {
+        let hook = hook();
+        let cookies = cookie_header(save(&hook, &[("user", "jane")]).await).await;
+
+        let mut tampered = cookies.clone().into_bytes();
+        let last = tampered.len() - 2;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+
+        assert_eq!(load(&hook, &String::from_utf8(tampered).unwrap()).await.get("user"), "");
+        assert_eq!(load(&hook, "session=abc").await.get("user"), "");
+        assert_eq!(load(&hook, "session=").await.get("user"), "");
+    }
+
+    #[tokio::test]
+    async fn previous_secrets_still_open_cookies() {
+        let old = CookieSession::new("session", "the old secret", Duration::from_secs(60));
+        let cookies = cookie_header(save(&old, &[("user", "jane")]).await).await;
+
+        let rotated = hook().previous_secrets(vec!["the old secret"]);
+
+        assert_eq!(load(&rotated, &cookies).await.get("user"), "jane");
+        assert_eq!(load(&hook(), &cookies).await.get("user"), "");
+    }
+
+    #[tokio::test]
+    async fn large_sessions_are_split_into_chunks() {
+        let hook = hook();
+        let large = "x".repeat(CHUNK_SIZE * 2);
+        let cookies = cookie_header(save(&hook, &[("notes", &large)]).await).await;
 
-        return String::from_utf8(plaintext).map_err(|err| err.into());
+        assert!(cookies.contains(&format!("session={}3", CHUNK_MARKER)));
+        assert!(cookies.contains("session.2="));
+        assert_eq!(load(&hook, &cookies).await.get("notes"), large);
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn oversized_sessions_fail_the_request_without_writing_cookies() {
+        let hook = hook();
+        let random: String = (0..CHUNK_SIZE * MAX_CHUNKS).map(|i| char::from(b'a' + (i * 7919 % 26) as u8)).collect();
+
+        let mut res = save(&hook, &[("notes", &random)]).await;
+
+        assert!(res.error.take().is_some_and(|error| error.message.contains("at most")));
+        assert_eq!(cookie_header(res).await, "");
+    }
+}
@@ -1,9 +1,12 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
 
 use crate::utils::Values;
 
 pub mod cookie;
 pub mod local;
+pub mod store;
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Session {
@@ -11,6 +14,16 @@ pub struct Session {
     pub(crate) flash: Values,
     pub(crate) errors: Values,
     pub(crate) old: Values,
+    #[serde(default)]
+    pub(crate) created_at: u64,
+    #[serde(default)]
+    pub(crate) last_activity: u64,
+    #[serde(skip)]
+    pub(crate) id: String,
+    #[serde(skip)]
+    pub(crate) regenerate: bool,
+    #[serde(skip)]
+    pub(crate) invalidated: bool,
 }
 
 impl Session {
@@ -20,10 +33,85 @@ impl Session {
             flash: Values::new(),
             errors: Values::new(),
             old: Values::new(),
+            created_at: 0,
+            last_activity: 0,
+            id: String::new(),
+            regenerate: false,
+            invalidated: false,
         };
     }
 }
 
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+impl Session {
+    /// Issues a new session id on the next response while keeping the data, e.g. after login.
+    pub fn regenerate(&mut self) {
+        self.regenerate = true;
+    }
+
+    /// Drops all session data and issues a new session id, e.g. on logout.
+    pub fn invalidate(&mut self) {
+        self.session.clear();
+        self.flash.clear();
+        self.errors.clear();
+        self.old.clear();
+        self.created_at = 0;
+        self.invalidated = true;
+        self.regenerate = true;
+    }
+
+    #[inline]
+    pub fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    pub(crate) fn is_expired(&self, idle: Duration, absolute: Option<Duration>) -> bool {
+        let now = now();
+
+        if self.last_activity > 0 && now.saturating_sub(self.last_activity) > idle.as_secs() {
+            return true;
+        }
+
+        if let Some(absolute) = absolute
+            && self.created_at > 0
+            && now.saturating_sub(self.created_at) > absolute.as_secs()
+        {
+            return true;
+        }
+
+        false
+    }
+
+    pub(crate) fn touch(&mut self) {
+        let now = now();
+
+        if self.created_at == 0 {
+            self.created_at = now;
+        }
+
+        self.last_activity = now;
+    }
+
+    /// Whether the data differs from `loaded`, the session the request came with. Flashed
+    /// values, errors and old input the request brought count as a change, as they are dropped.
+    pub(crate) fn is_changed(&self, loaded: &Session) -> bool {
+        self.session != loaded.session || self.flash != loaded.flash || self.errors != loaded.errors || self.old != loaded.old
+    }
+
+    /// Carries the bookkeeping of a loaded session into the response session.
+    pub(crate) fn resume(&mut self, loaded: &Session) {
+        self.set_values(loaded.session());
+        self.id = loaded.id.clone();
+        self.created_at = loaded.created_at;
+    }
+}
+
 impl Session {
     pub fn session(&self) -> Values {
         return self
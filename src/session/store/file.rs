@@ -0,0 +1,225 @@
+use std::{fmt::Write, path::{Path, PathBuf}, time::Duration};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    session::{Session, store::{SessionStore, is_valid_id}},
+    utils::Values,
+};
+
+/// Stores each session as a file named after its id.
+///
+/// #SessionFormat: one `name|s:length:"value"` line per `Session` field, where `value` is the JSON
+/// encoded field and `length` its size.
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<String>) -> Self {
+        let path_str: String = path.into();
+
+        std::fs::create_dir_all(&path_str)
+            .unwrap_or_else(|err| panic!("Failed to create session directory at '{}': {}", path_str, err));
+
+        Self {
+            path: PathBuf::from(path_str),
+        }
+    }
+
+    fn file_path(&self, id: &str) -> PathBuf {
+        Path::new(&self.path).join(id)
+    }
+
+    fn parse_session_file(&self, content: &str) -> Option<Session> {
+        let mut session = Values::default();
+        let mut flash = Values::default();
+        let mut errors = Values::default();
+        let mut old = Values::default();
+        let mut meta = Values::default();
+
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some((name, rest)) = line.split_once("|s:")
+                && let Some((_len, mut value_str)) = rest.split_once(':')
+            {
+                if value_str.starts_with('"') && value_str.ends_with('"') {
+                    value_str = &value_str[1..value_str.len() - 1];
+                }
+
+                if let Ok(parsed_value) = serde_json::from_str::<Values>(value_str) {
+                    match name {
+                        "session" => session = parsed_value,
+                        "flash" => flash = parsed_value,
+                        "errors" => errors = parsed_value,
+                        "old" => old = parsed_value,
+                        "meta" => meta = parsed_value,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let timestamp = |key: &str| meta.get(key).and_then(|v| v.parse::<u64>().ok()).unwrap_or(0);
+
+        Some(Session {
+            created_at: timestamp("created_at"),
+            last_activity: timestamp("last_activity"),
+            session,
+            flash,
+            errors,
+            old,
+            ..Session::new()
+        })
+    }
+
+    fn serialize_session(&self, session: &Session) -> Option<String> {
+        let mut output = String::with_capacity(512);
+        let meta = Values::from([
+            ("created_at".to_string(), session.created_at.to_string()),
+            ("last_activity".to_string(), session.last_activity.to_string()),
+        ]);
+
+        let fields = [
+            ("session", &session.session),
+            ("flash", &session.flash),
+            ("errors", &session.errors),
+            ("old", &session.old),
+            ("meta", &meta),
+        ];
+
+        for (i, (name, val)) in fields.iter().enumerate() {
+            let json_str = serde_json::to_string(val).ok()?;
+            if i > 0 {
+                output.push('\n');
+            }
+            write!(
+                &mut output,
+                "{}|s:{}:\"{}\"",
+                name,
+                json_str.len(),
+                json_str
+            ).ok()?;
+        }
+
+        Some(output)
+    }
+}
+
+impl SessionStore for FileStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        match tokio::fs::read_to_string(self.file_path(id)).await {
+            Ok(content) => Ok(self.parse_session_file(&content)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).context("Failed to read session file"),
+        }
+    }
+
+    async fn save(&self, id: &str, session: &Session, _ttl: Duration) -> Result<()> {
+        let serialized = self
+            .serialize_session(session)
+            .context("Failed to serialize session")?;
+
+        tokio::fs::write(self.file_path(id), serialized)
+            .await
+            .context("Failed to write session file")
+    }
+
+    async fn destroy(&self, id: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.file_path(id)).await {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err).context("Failed to delete session file"),
+            _ => Ok(()),
+        }
+    }
+
+    /// Only files named like a session ID are considered, so a shared directory keeps its other files.
+    async fn gc(&self, ttl: Duration) -> Result<u64> {
+        let mut removed = 0;
+        let mut entries = tokio::fs::read_dir(&self.path).await?;
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if !entry.file_name().to_str().is_some_and(is_valid_id) {
+                continue;
+            }
+
+            if let Ok(metadata) = entry.metadata().await
+                && let Ok(modified) = metadata.modified()
+                && modified.elapsed().is_ok_and(|elapsed| elapsed > ttl)
+                && tokio::fs::remove_file(entry.path()).await.is_ok()
+            {
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("flyer-file-store-{}-{}", name, ulid::Ulid::new()));
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn saved_session_loads_back() {
+        let path = directory("roundtrip");
+        let store = FileStore::new(path.to_string_lossy());
+        let mut session = Session::new();
+        session.set("user", "jane");
+        session.created_at = 1_700_000_000;
+
+        store.save(ID, &session, Duration::from_secs(60)).await.unwrap();
+        let loaded = store.load(ID).await.unwrap().unwrap();
+
+        assert_eq!(loaded.get("user"), "jane");
+        assert_eq!(loaded.created_at, 1_700_000_000);
+        assert!(store.load(&ID.replace('0', "1")).await.unwrap().is_none());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn gc_only_removes_session_files() {
+        let path = directory("gc");
+        let store = FileStore::new(path.to_string_lossy());
+
+        store.save(ID, &Session::new(), Duration::from_secs(60)).await.unwrap();
+        std::fs::write(path.join("unrelated.txt"), "keep me").unwrap();
+        std::fs::write(path.join(&ID[..63]), "keep me").unwrap();
+        std::fs::create_dir(path.join(ID.replace('0', "f"))).unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(store.gc(Duration::from_millis(10)).await.unwrap(), 1);
+        assert!(!path.join(ID).exists());
+        assert!(path.join("unrelated.txt").exists());
+        assert!(path.join(&ID[..63]).exists());
+        assert!(path.join(ID.replace('0', "f")).exists());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn gc_keeps_recent_sessions() {
+        let path = directory("recent");
+        let store = FileStore::new(path.to_string_lossy());
+
+        store.save(ID, &Session::new(), Duration::from_secs(60)).await.unwrap();
+
+        assert_eq!(store.gc(Duration::from_secs(60)).await.unwrap(), 0);
+        assert!(path.join(ID).exists());
+
+        std::fs::remove_dir_all(path).unwrap();
+    }
+}
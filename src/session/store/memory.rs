@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use moka::sync::Cache;
+
+use crate::session::{Session, now, store::SessionStore};
+
+/// Keeps sessions in process memory; sessions are lost on restart and not shared between instances.
+pub struct MemoryStore {
+    cache: Cache<String, Session>,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new(100_000)
+    }
+}
+
+impl MemoryStore {
+    pub fn new(max_sessions: u64) -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(max_sessions)
+                .build(),
+        }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        Ok(self.cache.get(id))
+    }
+
+    async fn save(&self, id: &str, session: &Session, _ttl: Duration) -> Result<()> {
+        self.cache.insert(id.to_string(), session.clone());
+        Ok(())
+    }
+
+    async fn destroy(&self, id: &str) -> Result<()> {
+        self.cache.invalidate(id);
+        Ok(())
+    }
+
+    async fn gc(&self, ttl: Duration) -> Result<u64> {
+        let now = now();
+        let expired: Vec<String> = self
+            .cache
+            .iter()
+            .filter(|(_, session)| now.saturating_sub(session.last_activity) > ttl.as_secs())
+            .map(|(id, _)| id.as_ref().clone())
+            .collect();
+
+        for id in &expired {
+            self.cache.invalidate(id);
+        }
+
+        Ok(expired.len() as u64)
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    any::type_name,
+    future::Future,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use anyhow::Result;
+use uuid::Uuid;
+
+use crate::{
+    cookies::SameSite,
+    hooks::Hook,
+    metrics,
+    request::Request,
+    response::Response,
+    routing::next::Next,
+    session::{Session, now},
+};
+
+pub mod file;
+pub mod memory;
+pub mod redis;
+pub mod sqlite;
+
+/// Where sessions are kept between requests. Implementations may use `async fn`; the futures
+/// must be `Send` since the collector runs them on a spawned task.
+pub trait SessionStore: Send + Sync {
+    fn load(&self, id: &str) -> impl Future<Output = Result<Option<Session>>> + Send;
+    fn save(&self, id: &str, session: &Session, ttl: Duration) -> impl Future<Output = Result<()>> + Send;
+    fn destroy(&self, id: &str) -> impl Future<Output = Result<()>> + Send;
+    /// Removes sessions idle for longer than `ttl`, returning how many were removed.
+    fn gc(&self, ttl: Duration) -> impl Future<Output = Result<u64>> + Send;
+}
+
+/// Whether `id` has the shape of an ID made by `StoreSession`: 64 hex characters.
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+pub struct StoreSession<S: SessionStore + 'static> {
+    store: Arc<S>,
+    cookie_name: String,
+    duration: Duration,
+    absolute: Option<Duration>,
+    gc_interval: Duration,
+    gc_started: OnceLock<()>,
+}
+
+impl<S: SessionStore + 'static> StoreSession<S> {
+    pub fn new(store: S, expires: Duration) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie_name: "session-id".into(),
+            duration: expires,
+            absolute: None,
+            gc_interval: Duration::from_secs(60 * 30),
+            gc_started: OnceLock::new(),
+        }
+    }
+
+    pub fn cookie_name(mut self, name: impl Into<String>) -> Self {
+        self.cookie_name = name.into();
+        self
+    }
+
+    /// Expires sessions this long after they were created, however active they are.
+    pub fn absolute_timeout(mut self, lifetime: Duration) -> Self {
+        self.absolute = Some(lifetime);
+        self
+    }
+
+    pub fn gc_interval(mut self, interval: Duration) -> Self {
+        self.gc_interval = interval;
+        self
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// The store's type name without its path, e.g. `RedisStore`, for metric labels.
+    fn store_name(&self) -> &'static str {
+        let name = type_name::<S>();
+        let name = name.split('<').next().unwrap_or(name);
+
+        name.rsplit("::").next().unwrap_or(name)
+    }
+
+    fn generate_id() -> String {
+        format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+    }
+
+    // The hook is built before the runtime exists, so the collector starts with the first request.
+    fn spawn_gc(&self) {
+        self.gc_started.get_or_init(|| {
+            let store = Arc::clone(&self.store);
+            let ttl = self.duration;
+            let interval = self.gc_interval;
+
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+
+                loop {
+                    ticker.tick().await;
+
+                    let _ = store.gc(ttl).await;
+                }
+            });
+        });
+    }
+}
+
+impl<S: SessionStore + 'static> Hook for StoreSession<S> {
+    async fn before(&self, mut req: Request, mut res: Response, next: Next) -> Response {
+        self.spawn_gc();
+
+        let session_id = req.cookie(self.cookie_name.clone());
+
+        if !is_valid_id(&session_id) {
+            return next.handle(req, res);
+        }
+
+        let Ok(Some(mut session)) = metrics::time_session(self.store_name(), "load", self.store.load(&session_id)).await else {
+            return next.handle(req, res);
+        };
+
+        if session.is_expired(self.duration, self.absolute) {
+            let _ = metrics::time_session(self.store_name(), "destroy", self.store.destroy(&session_id)).await;
+
+            return next.handle(req, res);
+        }
+
+        session.id = session_id;
+        req.session = session;
+        res.session.resume(&req.session);
+
+        next.handle(req, res)
+    }
+
+    async fn after(&self, req: Request, mut res: Response, next: Next) -> Response {
+        let current_id = req.session.id.clone();
+
+        // Ids the client sent which do not belong to a live session are never reused.
+        let session_id = if current_id.is_empty() || res.session.regenerate {
+            if !current_id.is_empty() {
+                let _ = metrics::time_session(self.store_name(), "destroy", self.store.destroy(&current_id)).await;
+            }
+
+            Self::generate_id()
+        } else {
+            current_id
+        };
+
+        // An unchanged session is only saved again to keep it from idling out, at most once per
+        // tenth of the idle timeout and once a minute.
+        let refresh = (self.duration / 10).min(Duration::from_secs(60)).as_secs();
+        let stale = now().saturating_sub(req.session.last_activity) >= refresh;
+
+        if session_id != req.session.id || res.session.is_changed(&req.session) || stale {
+            res.session.touch();
+
+            let _ = metrics::time_session(self.store_name(), "save", self.store.save(&session_id, &res.session, self.duration)).await;
+        }
+
+        res.session.id = session_id.clone();
+        res.session.regenerate = false;
+        res.session.invalidated = false;
+
+        let cookie = res
+            .set_cookie(self.cookie_name.clone(), session_id)
+            .set_expires(self.duration)
+            .set_same_site(SameSite::Lax)
+            .set_path("/");
+
+        if let Ok(url) = url_domain_parse::Url::parse(&format!("http://{}", req.host.clone())) {
+            cookie.set_domain(&url.base_host().unwrap_or(url.host().unwrap_or(String::new())));
+        }
+
+        next.handle(req, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{
+            Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use super::*;
+    use crate::cookies::Cookies;
+
+    /// Keeps sessions in a map and records which calls the hook made.
+    #[derive(Default)]
+    struct StandIn {
+        sessions: Mutex<HashMap<String, Session>>,
+        calls: Mutex<Vec<String>>,
+        gc_runs: AtomicU64,
+    }
+
+    impl StandIn {
+        fn with(id: &str, session: Session) -> Self {
+            let store = Self::default();
+            store.sessions.lock().unwrap().insert(id.to_string(), session);
+            store
+        }
+
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl SessionStore for StandIn {
+        async fn load(&self, id: &str) -> Result<Option<Session>> {
+            self.calls.lock().unwrap().push(format!("load {}", id));
+            Ok(self.sessions.lock().unwrap().get(id).cloned())
+        }
+
+        async fn save(&self, id: &str, session: &Session, _ttl: Duration) -> Result<()> {
+            self.calls.lock().unwrap().push(format!("save {}", id));
+            self.sessions.lock().unwrap().insert(id.to_string(), session.clone());
+            Ok(())
+        }
+
+        async fn destroy(&self, id: &str) -> Result<()> {
+            self.calls.lock().unwrap().push(format!("destroy {}", id));
+            self.sessions.lock().unwrap().remove(id);
+            Ok(())
+        }
+
+        async fn gc(&self, _ttl: Duration) -> Result<u64> {
+            self.gc_runs.fetch_add(1, Ordering::SeqCst);
+            Ok(0)
+        }
+    }
+
+    const ID: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    fn active(value: &str) -> Session {
+        let mut session = Session::new();
+        session.set("user", value);
+        session.created_at = now();
+        session.last_activity = now();
+        session
+    }
+
+    /// Runs `before` with the `session-id` cookie set to `id`, returning the request handlers see.
+    async fn before(hook: &StoreSession<StandIn>, id: &str) -> Request {
+        let mut req = Request::fake("GET", "/");
+        req.headers.insert("cookie".to_string(), format!("session-id={}", id));
+
+        let mut res = Cookies::new().before(req, Response::new(), Next::new()).await;
+        let req = res.request();
+
+        let mut res = hook.before(req, Response::new(), Next::new()).await;
+        res.request()
+    }
+
+    #[tokio::test]
+    async fn new_session_is_saved_under_a_generated_id() {
+        let hook = StoreSession::new(StandIn::default(), Duration::from_secs(60));
+
+        let mut res = Response::new();
+        res.session.set("user", "jane");
+
+        let res = hook.after(Request::fake("GET", "/"), res, Next::new()).await;
+        let id = res.session.id();
+
+        assert!(is_valid_id(&id));
+        assert_eq!(hook.store().calls(), vec![format!("save {}", id)]);
+        assert_eq!(hook.store().sessions.lock().unwrap()[&id].get("user"), "jane");
+    }
+
+    #[tokio::test]
+    async fn known_session_is_resumed() {
+        let hook = StoreSession::new(StandIn::with(ID, active("jane")), Duration::from_secs(60));
+
+        let req = before(&hook, ID).await;
+
+        assert_eq!(req.session.id(), ID);
+        assert_eq!(req.session.get("user"), "jane");
+        assert_eq!(hook.store().calls(), vec![format!("load {}", ID)]);
+    }
+
+    #[tokio::test]
+    async fn malformed_id_is_never_loaded() {
+        let hook = StoreSession::new(StandIn::default(), Duration::from_secs(60));
+
+        let req = before(&hook, "../../etc/passwd").await;
+
+        assert!(req.session.id().is_empty());
+        assert!(hook.store().calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_destroyed() {
+        let mut session = active("jane");
+        session.last_activity = now() - 120;
+
+        let hook = StoreSession::new(StandIn::with(ID, session), Duration::from_secs(60));
+        let req = before(&hook, ID).await;
+
+        assert!(req.session.id().is_empty());
+        assert_eq!(hook.store().calls(), vec![format!("load {}", ID), format!("destroy {}", ID)]);
+    }
+
+    #[tokio::test]
+    async fn unchanged_session_is_not_saved() {
+        let hook = StoreSession::new(StandIn::with(ID, active("jane")), Duration::from_secs(60 * 60));
+
+        let req = before(&hook, ID).await;
+        let mut res = Response::new();
+        res.session.resume(&req.session);
+
+        let res = hook.after(req, res, Next::new()).await;
+
+        assert_eq!(res.session.id(), ID);
+        assert_eq!(hook.store().calls(), vec![format!("load {}", ID)]);
+    }
+
+    #[tokio::test]
+    async fn changed_or_idle_session_is_saved() {
+        let mut idle = active("jane");
+        idle.last_activity = now() - 120;
+
+        for (session, change) in [(active("jane"), true), (idle, false)] {
+            let hook = StoreSession::new(StandIn::with(ID, session), Duration::from_secs(60 * 60));
+
+            let req = before(&hook, ID).await;
+            let mut res = Response::new();
+            res.session.resume(&req.session);
+
+            if change {
+                res.session.set("theme", "dark");
+            }
+
+            hook.after(req, res, Next::new()).await;
+
+            assert_eq!(hook.store().calls(), vec![format!("load {}", ID), format!("save {}", ID)]);
+            assert!(hook.store().sessions.lock().unwrap()[ID].last_activity >= now() - 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn regenerate_moves_the_session_to_a_new_id() {
+        let hook = StoreSession::new(StandIn::with(ID, active("jane")), Duration::from_secs(60));
+
+        let req = before(&hook, ID).await;
+        let mut res = Response::new();
+        res.session.resume(&req.session);
+        res.session.regenerate();
+
+        let res = hook.after(req, res, Next::new()).await;
+        let id = res.session.id();
+        let sessions = hook.store().sessions.lock().unwrap().clone();
+
+        assert_ne!(id, ID);
+        assert!(!sessions.contains_key(ID));
+        assert_eq!(sessions[&id].get("user"), "jane");
+    }
+
+    #[tokio::test]
+    async fn collector_runs_in_the_background() {
+        let hook = StoreSession::new(StandIn::default(), Duration::from_secs(60)).gc_interval(Duration::from_millis(10));
+
+        hook.before(Request::fake("GET", "/"), Response::new(), Next::new()).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(hook.store().gc_runs.load(Ordering::SeqCst) >= 2);
+    }
+}
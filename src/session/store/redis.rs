@@ -0,0 +1,340 @@
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::Mutex,
+};
+
+use crate::session::{Session, store::SessionStore};
+
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Simple(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+    /// An error the server answered with, such as `ERR unknown command`.
+    Error(String),
+}
+
+/// Keeps sessions in any server speaking the Redis protocol (Redis, Valkey, KeyDB, ...),
+/// letting every app instance share them. Expiry is left to the server via `SET ... EX`.
+pub struct RedisStore {
+    address: String,
+    password: Option<String>,
+    database: u32,
+    prefix: String,
+    connection: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl RedisStore {
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            password: None,
+            database: 0,
+            prefix: "flyer:session:".into(),
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    pub fn database(mut self, database: u32) -> Self {
+        self.database = database;
+        self
+    }
+
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{}", self.prefix, id)
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>> {
+        let stream = TcpStream::connect(&self.address)
+            .await
+            .with_context(|| format!("Failed to connect to session store at {}", self.address))?;
+        let mut connection = BufReader::new(stream);
+
+        if let Some(password) = &self.password {
+            Self::call(&mut connection, &[b"AUTH", password.as_bytes()]).await?;
+        }
+
+        if self.database != 0 {
+            Self::call(&mut connection, &[b"SELECT", self.database.to_string().as_bytes()]).await?;
+        }
+
+        Ok(connection)
+    }
+
+    /// Runs one command. A pooled connection that fails while the command is written is replaced
+    /// and the command written again; once written it is never resent, as the server may have
+    /// run it.
+    async fn command(&self, args: &[&[u8]]) -> Result<Reply> {
+        let mut guard = self.connection.lock().await;
+        let request = encode(args);
+
+        let pooled = match guard.take() {
+            Some(mut connection) => Self::write(&mut connection, &request).await.map(|_| connection).ok(),
+            None => None,
+        };
+
+        let mut connection = match pooled {
+            Some(connection) => connection,
+            None => {
+                let mut connection = self.connect().await?;
+                Self::write(&mut connection, &request).await?;
+                connection
+            }
+        };
+
+        let reply = read_reply(&mut connection).await;
+
+        // After a failed read the connection is out of step with the server, so it is dropped.
+        if reply.is_ok() {
+            *guard = Some(connection);
+        }
+
+        checked(reply?)
+    }
+
+    async fn call(connection: &mut BufReader<TcpStream>, args: &[&[u8]]) -> Result<Reply> {
+        Self::write(connection, &encode(args)).await?;
+
+        checked(read_reply(connection).await?)
+    }
+
+    async fn write(connection: &mut BufReader<TcpStream>, request: &[u8]) -> Result<()> {
+        connection.get_mut().write_all(request).await?;
+        connection.get_mut().flush().await?;
+
+        Ok(())
+    }
+}
+
+fn checked(reply: Reply) -> Result<Reply> {
+    match reply {
+        Reply::Error(message) => Err(anyhow!("Session store error: {}", message)),
+        reply => Ok(reply),
+    }
+}
+
+pub(crate) fn encode(args: &[&[u8]]) -> Vec<u8> {
+    let mut buffer = format!("*{}\r\n", args.len()).into_bytes();
+
+    for arg in args {
+        buffer.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buffer.extend_from_slice(arg);
+        buffer.extend_from_slice(b"\r\n");
+    }
+
+    buffer
+}
+
+pub(crate) async fn read_reply<R: AsyncBufRead + Unpin + Send>(reader: &mut R) -> Result<Reply> {
+    let mut line = String::new();
+
+    if reader.read_line(&mut line).await? == 0 {
+        bail!("Session store closed the connection");
+    }
+
+    let line = line.trim_end_matches("\r\n");
+    let (kind, rest) = line.split_at(1.min(line.len()));
+
+    match kind {
+        "+" => Ok(Reply::Simple(rest.to_string())),
+        "-" => Ok(Reply::Error(rest.to_string())),
+        ":" => Ok(Reply::Integer(rest.parse()?)),
+        "$" => {
+            let length: i64 = rest.parse()?;
+
+            if length < 0 {
+                return Ok(Reply::Bulk(None));
+            }
+
+            let mut data = vec![0; length as usize + 2];
+            reader.read_exact(&mut data).await?;
+            data.truncate(length as usize);
+
+            Ok(Reply::Bulk(Some(data)))
+        }
+        "*" => {
+            let length: i64 = rest.parse()?;
+            let mut items = Vec::with_capacity(length.max(0) as usize);
+
+            for _ in 0..length.max(0) {
+                items.push(Box::pin(read_reply(reader)).await?);
+            }
+
+            Ok(Reply::Array(items))
+        }
+        _ => Err(anyhow!("Unexpected reply from session store: {}", line)),
+    }
+}
+
+impl SessionStore for RedisStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        match self.command(&[b"GET", self.key(id).as_bytes()]).await? {
+            Reply::Bulk(Some(payload)) => Ok(serde_json::from_slice::<Session>(&payload).ok()),
+            _ => Ok(None),
+        }
+    }
+
+    async fn save(&self, id: &str, session: &Session, ttl: Duration) -> Result<()> {
+        let payload = serde_json::to_vec(session)?;
+        let ttl = ttl.as_secs().max(1).to_string();
+
+        self.command(&[b"SET", self.key(id).as_bytes(), &payload, b"EX", ttl.as_bytes()])
+            .await?;
+
+        Ok(())
+    }
+
+    async fn destroy(&self, id: &str) -> Result<()> {
+        self.command(&[b"DEL", self.key(id).as_bytes()]).await?;
+        Ok(())
+    }
+
+    async fn gc(&self, _ttl: Duration) -> Result<u64> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc};
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Answers GET, SET, DEL, AUTH and SELECT like a Redis server would, keeping keys in memory,
+    /// and anything else with an error.
+    async fn stand_in() -> (String, Arc<std::sync::Mutex<Vec<Vec<String>>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let commands = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&commands);
+
+        tokio::spawn(async move {
+            let mut keys: HashMap<String, Vec<u8>> = HashMap::new();
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut connection = BufReader::new(stream);
+
+            while let Ok(Reply::Array(items)) = read_reply(&mut connection).await {
+                let args: Vec<Vec<u8>> = items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Reply::Bulk(Some(data)) => Some(data),
+                        _ => None,
+                    })
+                    .collect();
+                let text = |index: usize| String::from_utf8_lossy(&args[index]).to_string();
+
+                log.lock().unwrap().push((0..args.len()).map(text).collect());
+
+                let reply = match text(0).as_str() {
+                    "GET" => match keys.get(&text(1)) {
+                        Some(value) => [format!("${}\r\n", value.len()).into_bytes(), value.clone(), b"\r\n".to_vec()].concat(),
+                        None => b"$-1\r\n".to_vec(),
+                    },
+                    "SET" => {
+                        keys.insert(text(1), args[2].clone());
+                        b"+OK\r\n".to_vec()
+                    }
+                    "DEL" => format!(":{}\r\n", keys.remove(&text(1)).map_or(0, |_| 1)).into_bytes(),
+                    "AUTH" if text(1) == "secret" => b"+OK\r\n".to_vec(),
+                    "AUTH" => b"-WRONGPASS invalid password\r\n".to_vec(),
+                    "SELECT" => b"+OK\r\n".to_vec(),
+                    _ => b"-ERR unknown command\r\n".to_vec(),
+                };
+
+                connection.get_mut().write_all(&reply).await.unwrap();
+            }
+        });
+
+        (address, commands)
+    }
+
+    #[tokio::test]
+    async fn sessions_round_trip_through_the_server() {
+        let (address, commands) = stand_in().await;
+        let store = RedisStore::new(address).password("secret").database(2).prefix("app:");
+        let mut session = Session::new();
+        session.set("user", "jane");
+
+        store.save("abc", &session, Duration::from_secs(90)).await.unwrap();
+        assert_eq!(store.load("abc").await.unwrap().unwrap().get("user"), "jane");
+
+        store.destroy("abc").await.unwrap();
+        assert!(store.load("abc").await.unwrap().is_none());
+
+        let commands = commands.lock().unwrap().clone();
+        assert_eq!(commands[0], ["AUTH", "secret"]);
+        assert_eq!(commands[1], ["SELECT", "2"]);
+        assert_eq!(commands[2][..2], ["SET", "app:abc"]);
+        assert_eq!(commands[2][3..], ["EX", "90"]);
+        assert_eq!(commands[4], ["DEL", "app:abc"]);
+    }
+
+    #[tokio::test]
+    async fn server_errors_are_reported() {
+        let (address, _) = stand_in().await;
+        let store = RedisStore::new(address).password("wrong");
+
+        let err = store.load("abc").await.unwrap_err();
+
+        assert!(format!("{:#}", err).contains("WRONGPASS"));
+    }
+
+    #[tokio::test]
+    async fn error_replies_keep_the_connection() {
+        let (address, commands) = stand_in().await;
+        let store = RedisStore::new(address);
+
+        let err = store.command(&[b"BOGUS"]).await.unwrap_err();
+        assert_eq!(err.to_string(), "Session store error: ERR unknown command");
+
+        // The stand-in accepts a single connection, so this only passes on the same one.
+        assert!(store.load("abc").await.unwrap().is_none());
+        assert_eq!(commands.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn written_commands_are_not_resent() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let received = Arc::new(std::sync::Mutex::new(0));
+        let count = Arc::clone(&received);
+
+        // Reads each command and hangs up without answering, like a server that went away.
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut connection = BufReader::new(stream);
+
+                if read_reply(&mut connection).await.is_ok() {
+                    *count.lock().unwrap() += 1;
+                }
+            }
+        });
+
+        let store = RedisStore::new(address);
+
+        assert!(store.save("abc", &Session::new(), Duration::from_secs(60)).await.is_err());
+        assert_eq!(*received.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn commands_are_encoded_as_bulk_arrays() {
+        assert_eq!(encode(&[b"GET", b"key"]), b"*2\r\n$3\r\nGET\r\n$3\r\nkey\r\n");
+    }
+}
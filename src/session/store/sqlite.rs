@@ -0,0 +1,152 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, anyhow};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::session::{Session, now, store::SessionStore};
+
+/// Keeps sessions in a SQLite table so several app instances on one host can share them.
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    table: String,
+}
+
+impl SqliteStore {
+    pub fn new(path: impl Into<String>) -> Result<Self> {
+        Self::from_connection(Connection::open(path.into())?)
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> Result<Self> {
+        let store = Self {
+            connection: Arc::new(Mutex::new(connection)),
+            table: "sessions".into(),
+        };
+
+        store.migrate()?;
+
+        Ok(store)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        self.connection
+            .lock()
+            .map_err(|_| anyhow!("Session database lock poisoned"))?
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {table} (
+                    id TEXT PRIMARY KEY,
+                    payload TEXT NOT NULL,
+                    last_activity INTEGER NOT NULL
+                );
+                CREATE INDEX IF NOT EXISTS {table}_last_activity ON {table} (last_activity);",
+                table = self.table
+            ))
+            .context("Failed to create sessions table")
+    }
+
+    async fn blocking<T, F>(&self, callback: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection, &str) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        let table = self.table.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| anyhow!("Session database lock poisoned"))?;
+
+            callback(&connection, &table).map_err(|err| err.into())
+        })
+        .await?
+    }
+}
+
+impl SessionStore for SqliteStore {
+    async fn load(&self, id: &str) -> Result<Option<Session>> {
+        let id = id.to_string();
+        let payload = self.blocking(move |conn, table| {
+            conn.query_row(
+                &format!("SELECT payload FROM {table} WHERE id = ?1"),
+                params![id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+        })
+        .await?;
+
+        Ok(payload.and_then(|payload| serde_json::from_str::<Session>(&payload).ok()))
+    }
+
+    async fn save(&self, id: &str, session: &Session, _ttl: Duration) -> Result<()> {
+        let id = id.to_string();
+        let payload = serde_json::to_string(session)?;
+        let last_activity = session.last_activity as i64;
+
+        self.blocking(move |conn, table| {
+            conn.execute(
+                &format!(
+                    "INSERT INTO {table} (id, payload, last_activity) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(id) DO UPDATE SET payload = excluded.payload, last_activity = excluded.last_activity"
+                ),
+                params![id, payload, last_activity],
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn destroy(&self, id: &str) -> Result<()> {
+        let id = id.to_string();
+
+        self.blocking(move |conn, table| {
+            conn.execute(&format!("DELETE FROM {table} WHERE id = ?1"), params![id])
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn gc(&self, ttl: Duration) -> Result<u64> {
+        let cutoff = now().saturating_sub(ttl.as_secs()) as i64;
+
+        let removed = self.blocking(move |conn, table| {
+            conn.execute(&format!("DELETE FROM {table} WHERE last_activity < ?1"), params![cutoff])
+        })
+        .await?;
+
+        Ok(removed as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(last_activity: u64) -> Session {
+        let mut session = Session::new();
+        session.set("user", "jane");
+        session.last_activity = last_activity;
+        session
+    }
+
+    #[tokio::test]
+    async fn gc_removes_idle_sessions() {
+        let store = SqliteStore::in_memory().unwrap();
+
+        store.save("idle", &session(now() - 600), Duration::from_secs(60)).await.unwrap();
+        store.save("active", &session(now()), Duration::from_secs(60)).await.unwrap();
+
+        assert_eq!(store.gc(Duration::from_secs(60)).await.unwrap(), 1);
+        assert!(store.load("idle").await.unwrap().is_none());
+        assert_eq!(store.load("active").await.unwrap().unwrap().get("user"), "jane");
+    }
+}
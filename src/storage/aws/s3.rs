@@ -193,6 +193,7 @@ impl Storage for S3Storage {
             name: file_name,
             mime,
             content: data.into_bytes(),
+            stored: None,
         })
     }
 }
\ No newline at end of file
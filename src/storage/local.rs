@@ -3,11 +3,12 @@ use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result};
 use bytes::Bytes;
-use tokio::fs;
+use futures::StreamExt;
+use tokio::{fs, io::AsyncWriteExt};
 use uuid::Uuid;
 
 use crate::request::form::File;
-use crate::storage::Storage;
+use crate::storage::{ByteStream, Storage};
 
 pub struct LocalStorage {
     directory: PathBuf,
@@ -95,6 +96,50 @@ impl Storage for LocalStorage {
             name: file_name,
             mime,
             content: Bytes::from(content_bytes),
+            stored: None,
         })
     }
-}
\ No newline at end of file
+
+    async fn save_stream(&self, folder: impl Into<String>, file: File, mut stream: ByteStream) -> Result<String> {
+        let folder_str = folder.into();
+        let mut name_str = Uuid::new_v4().to_string().replace("-", "");
+
+        if let Some((_, extension)) = file.name.rsplit_once('.') {
+            name_str = name_str.add(&format!(".{}", extension));
+        }
+
+        let target_dir = self.resolve_path(&folder_str);
+        let target_path = target_dir.join(&name_str);
+
+        fs::create_dir_all(&target_dir)
+            .await
+            .with_context(|| format!("Failed to create directory: {}", target_dir.display()))?;
+
+        let mut target = fs::File::create(&target_path)
+            .await
+            .with_context(|| format!("Failed to create file: {}", target_path.display()))?;
+
+        let written: Result<()> = async {
+            while let Some(chunk) = stream.next().await {
+                target.write_all(&chunk?).await?;
+            }
+
+            target.flush().await?;
+
+            Ok(())
+        }
+        .await;
+
+        // Never leave a partial upload behind when the client or a limit aborts the stream.
+        if let Err(err) = written {
+            drop(target);
+            let _ = fs::remove_file(&target_path).await;
+
+            return Err(err).with_context(|| format!("Failed to write file: {}", target_path.display()));
+        }
+
+        let relative_result = Path::new(&folder_str).join(&name_str);
+
+        Ok(format!("{}/{}", self.directory.to_string_lossy(), relative_result.to_string_lossy().into_owned()))
+    }
+}
@@ -4,9 +4,10 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
-use futures::future::BoxFuture;
+use bytes::{Bytes, BytesMut};
+use futures::{StreamExt, future::BoxFuture, stream::BoxStream};
 
-use crate::{request::form::File, utils::future::SendFuture};
+use crate::{metrics, request::form::File, utils::future::SendFuture};
 
 pub mod local;
 
@@ -14,6 +15,9 @@ pub mod aws;
 
 pub const DEFAULT_STORAGE: &'static str = "default";
 
+/// File content arriving in chunks, such as an upload still being received.
+pub type ByteStream = BoxStream<'static, Result<Bytes>>;
+
 static GLOBAL_STORAGE: LazyLock<RwLock<HashMap<String, Arc<dyn StorageErasure>>>> =
     LazyLock::new(|| RwLock::new(HashMap::new()));
 
@@ -24,6 +28,21 @@ pub trait Storage: Send + Sync {
     async fn delete(&self, filename: impl Into<String>) -> Result<()>;
     async fn exists(&self, filename: impl Into<String>) -> Result<bool>;
     async fn get(&self, filename: impl Into<String>) -> Result<File>;
+
+    /// Saves `file` (name and mime only) with its content read from `stream`, under a generated
+    /// name like `save`. Buffers the stream by default; backends able to write chunks as they
+    /// arrive should override it.
+    async fn save_stream(&self, folder: impl Into<String>, mut file: File, mut stream: ByteStream) -> Result<String> {
+        let mut content = BytesMut::new();
+
+        while let Some(chunk) = stream.next().await {
+            content.extend_from_slice(&chunk?);
+        }
+
+        file.content = content.freeze();
+
+        self.save(folder, file).await
+    }
 }
 
 trait StorageErasure: Send + Sync {
@@ -32,6 +51,7 @@ trait StorageErasure: Send + Sync {
     fn delete<'a>(&'a self, filename: String) -> BoxFuture<'a, Result<()>>;
     fn exists<'a>(&'a self, filename: String) -> BoxFuture<'a, Result<bool>>;
     fn get<'a>(&'a self, filename: String) -> BoxFuture<'a, Result<File>>;
+    fn save_stream<'a>(&'a self, folder: String, file: File, stream: ByteStream) -> BoxFuture<'a, Result<String>>;
 }
 
 impl<T: Storage + 'static> StorageErasure for T {
@@ -54,6 +74,10 @@ impl<T: Storage + 'static> StorageErasure for T {
     fn get<'a>(&'a self, filename: String) -> BoxFuture<'a, Result<File>> {
         Box::pin(SendFuture(Storage::get(self, filename)))
     }
+
+    fn save_stream<'a>(&'a self, folder: String, file: File, stream: ByteStream) -> BoxFuture<'a, Result<String>> {
+        Box::pin(SendFuture(Storage::save_stream(self, folder, file, stream)))
+    }
 }
 
 pub fn add(name: impl Into<String>, storage: impl Storage + 'static) {
@@ -73,31 +97,37 @@ fn get_storage(name: &str) -> Result<Arc<dyn StorageErasure>> {
 }
 
 pub async fn save_as(storage: &str, folder: impl Into<String>, name: impl Into<String>, file: File) -> Result<String> {
-    get_storage(storage)?
-        .save_as(folder.into(), name.into(), file)
-        .await
+    let instance = get_storage(storage)?;
+
+    metrics::time_storage(storage, "save_as", instance.save_as(folder.into(), name.into(), file)).await
 }
 
 pub async fn save(storage: &str, folder: impl Into<String>, file: File) -> Result<String> {
-    get_storage(storage)?
-        .save(folder.into(), file)
-        .await
+    let instance = get_storage(storage)?;
+
+    metrics::time_storage(storage, "save", instance.save(folder.into(), file)).await
 }
 
 pub async fn delete(storage: &str, filename: impl Into<String>) -> Result<()> {
-    get_storage(storage)?
-        .delete(filename.into())
-        .await
+    let instance = get_storage(storage)?;
+
+    metrics::time_storage(storage, "delete", instance.delete(filename.into())).await
 }
 
 pub async fn exists(storage: &str, filename: impl Into<String>) -> Result<bool> {
-    get_storage(storage)?
-        .exists(filename.into())
-        .await
+    let instance = get_storage(storage)?;
+
+    metrics::time_storage(storage, "exists", instance.exists(filename.into())).await
 }
 
 pub async fn get(storage: &str, filename: impl Into<String>) -> Result<File> {
-    get_storage(storage)?
-        .get(filename.into())
-        .await
-}
\ No newline at end of file
+    let instance = get_storage(storage)?;
+
+    metrics::time_storage(storage, "get", instance.get(filename.into())).await
+}
+
+pub async fn save_stream(storage: &str, folder: impl Into<String>, file: File, stream: ByteStream) -> Result<String> {
+    let instance = get_storage(storage)?;
+
+    metrics::time_storage(storage, "save_stream", instance.save_stream(folder.into(), file, stream)).await
+}
@@ -0,0 +1,191 @@
+use std::{collections::HashMap, sync::OnceLock, time::Duration};
+
+use anyhow::{Context, Result};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::{
+    Resource,
+    trace::{SdkTracer, SdkTracerProvider},
+};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::{layer::SubscriberExt, registry::LookupSpan, util::SubscriberInitExt};
+
+static GLOBAL_TRACER_PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Exports the request spans (and any other `tracing` span) to an OpenTelemetry collector over
+/// OTLP/HTTP:
+///
+/// ```ignore
+/// Otlp::new("http://localhost:4318/v1/traces").service_name("shop").install()?;
+/// ```
+pub struct Otlp {
+    endpoint: String,
+    service_name: String,
+    headers: HashMap<String, String>,
+    timeout: Duration,
+}
+
+impl Otlp {
+    /// `endpoint` is the collector's traces URL, usually ending in `/v1/traces`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            service_name: String::from("flyer"),
+            headers: HashMap::new(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn service_name(mut self, name: impl Into<String>) -> Self {
+        self.service_name = name.into();
+        self
+    }
+
+    /// Sends a header with every export, such as an API key for a hosted collector.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The exporting layer, to add to a subscriber the application builds itself.
+    pub fn layer<S>(self) -> Result<OpenTelemetryLayer<S, SdkTracer>>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(self.endpoint)
+            .with_headers(self.headers)
+            .with_timeout(self.timeout)
+            .build()
+            .context("Failed to build the OTLP exporter")?;
+
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(self.service_name).build())
+            .build();
+
+        let tracer = provider.tracer("flyer");
+
+        GLOBAL_TRACER_PROVIDER
+            .set(provider)
+            .map_err(|_| anyhow::anyhow!("An OTLP exporter is already installed"))?;
+
+        Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+    }
+
+    /// Installs a global subscriber that only exports spans.
+    pub fn install(self) -> Result<()> {
+        tracing_subscriber::registry()
+            .with(self.layer()?)
+            .try_init()
+            .context("A global tracing subscriber is already installed")
+    }
+}
+
+/// Exports the spans still buffered. Call it before the process exits.
+pub fn flush() -> Result<()> {
+    if let Some(provider) = GLOBAL_TRACER_PROVIDER.get() {
+        provider.force_flush().context("Failed to flush spans")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::mpsc,
+        thread,
+    };
+
+    use super::*;
+
+    /// One export as the collector received it.
+    struct Export {
+        head: String,
+        body: Vec<u8>,
+    }
+
+    /// A stand-in collector answering every OTLP/HTTP export with `200`.
+    fn collector() -> (String, mpsc::Receiver<Export>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let (exports, received) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+
+                loop {
+                    let mut line = String::new();
+
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+
+                    head.push_str(&line);
+                }
+
+                let length = head
+                    .lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: application/x-protobuf\r\nContent-Length: 0\r\n\r\n");
+                let _ = exports.send(Export { head, body });
+            }
+        });
+
+        (endpoint, received)
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+    }
+
+    #[test]
+    fn request_spans_are_exported_to_the_collector() {
+        let (endpoint, received) = collector();
+
+        let layer = Otlp::new(endpoint)
+            .service_name("otlp-test-service")
+            .header("x-api-key", "secret")
+            .timeout(Duration::from_secs(5))
+            .layer()
+            .unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("GET /orders/{id}", request_id = "req-123");
+            span.in_scope(|| {});
+        });
+
+        flush().unwrap();
+
+        let export = received.recv_timeout(Duration::from_secs(10)).unwrap();
+
+        assert!(export.head.starts_with("POST /v1/traces "));
+        assert!(export.head.to_ascii_lowercase().contains("x-api-key: secret"));
+        assert!(contains(&export.body, "otlp-test-service"));
+        assert!(contains(&export.body, "GET /orders/{id}"));
+        assert!(contains(&export.body, "req-123"));
+
+        // Only one exporter may be installed per process.
+        assert!(Otlp::new("http://127.0.0.1:9/v1/traces").layer::<tracing_subscriber::Registry>().is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use aes_gcm::{
+    AeadCore,
+    Aes256Gcm,
+    Nonce,
+    aead::{Aead, KeyInit, OsRng}
+};
+use anyhow::{Result, anyhow, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use openssl::{hash::MessageDigest, memcmp, pkcs5::pbkdf2_hmac, pkey::PKey, sign::Signer};
+
+pub const KEY_LENGTH: usize = 32;
+
+const NONCE_LENGTH: usize = 12;
+
+const KDF_ITERATIONS: usize = 10_000;
+
+pub type Key = [u8; KEY_LENGTH];
+
+/// Derives a 256-bit key from an application secret. `context` separates keys used for
+/// different purposes so the same secret never encrypts two kinds of data with one key.
+pub fn derive_key(secret: &str, context: &str) -> Key {
+    let mut key = [0u8; KEY_LENGTH];
+
+    pbkdf2_hmac(secret.as_bytes(), context.as_bytes(), KDF_ITERATIONS, MessageDigest::sha256(), &mut key)
+        .expect("Failed to derive key from secret");
+
+    key
+}
+
+/// Encrypts and authenticates `plaintext`, returning `base64url(nonce || ciphertext)`.
+pub fn encrypt(key: &Key, plaintext: &[u8]) -> Result<String> {
+    let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| anyhow!("Invalid encryption key"))?;
+    let nonce = Aes256Gcm::generate_nonce(OsRng);
+    let cipher_text = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("Failed to encrypt payload"))?;
+
+    let mut combined = nonce.to_vec();
+    combined.extend_from_slice(&cipher_text);
+
+    Ok(URL_SAFE_NO_PAD.encode(combined))
+}
+
+/// Opens a payload produced by [`encrypt`] with the first key that authenticates it, so
+/// payloads sealed with a retired key keep working while it is still listed.
+pub fn decrypt(keys: &[Key], payload: &str) -> Result<Vec<u8>> {
+    let combined = URL_SAFE_NO_PAD.decode(payload.trim())?;
+
+    if combined.len() <= NONCE_LENGTH {
+        bail!("Encrypted payload is too short");
+    }
+
+    let (nonce_bytes, cipher_text) = combined.split_at(NONCE_LENGTH);
+    let nonce = Nonce::from_slice(nonce_bytes);
+
+    for key in keys {
+        let Ok(cipher) = Aes256Gcm::new_from_slice(key) else {
+            continue;
+        };
+
+        if let Ok(plaintext) = cipher.decrypt(nonce, cipher_text) {
+            return Ok(plaintext);
+        }
+    }
+
+    bail!("Encrypted payload failed authentication")
+}
+
+/// HMAC-SHA256 of `data`, base64url encoded.
+pub fn sign(key: &Key, data: &[u8]) -> String {
+    let pkey = PKey::hmac(key).expect("Invalid signing key");
+    let signature = Signer::new(MessageDigest::sha256(), &pkey)
+        .and_then(|mut signer| signer.sign_oneshot_to_vec(data))
+        .expect("Failed to sign payload");
+
+    URL_SAFE_NO_PAD.encode(signature)
+}
+
+/// Checks a [`sign`] signature against every key in constant time.
+pub fn verify(keys: &[Key], data: &[u8], signature: &str) -> bool {
+    keys.iter().any(|key| {
+        let expected = sign(key, data);
+
+        expected.len() == signature.len() && memcmp::eq(expected.as_bytes(), signature.as_bytes())
+    })
+}
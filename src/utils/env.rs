@@ -4,4 +4,9 @@ pub fn load(path: &str) {
 
 pub fn env(key: impl Into<String>) -> String {
     return std::env::var(key.into()).unwrap_or(String::new());
-} 
\ No newline at end of file
+} 
+/// Debug builds not started with `APP_ENV=production`. Development helpers such as template
+/// reloading and detailed error pages are only enabled in this mode.
+pub fn is_development() -> bool {
+    cfg!(debug_assertions) && env("APP_ENV") != "production"
+}
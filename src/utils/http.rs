@@ -1,3 +1,201 @@
-use std::collections::HashMap;
+use serde::{Serialize, Serializer, ser::SerializeMap};
 
-pub type Headers = HashMap<String, String>;
\ No newline at end of file
+/// HTTP header map. Names are matched case-insensitively but keep the casing they were set
+/// with, and a name can carry several values (`Set-Cookie`, `Vary`, `Link`, ...), which are
+/// written as separate header lines in insertion order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// First value set for `name`.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self
+            .entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&String> {
+        self
+            .entries
+            .iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Sets `name` to a single value, replacing every value it had.
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+
+        self.remove(&name);
+        self.entries.push((name, value.into()));
+    }
+
+    /// Adds a value to `name`, keeping the values it already had.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    pub fn remove(&mut self, name: &str) -> Vec<String> {
+        let (removed, kept) = std::mem::take(&mut self.entries)
+            .into_iter()
+            .partition(|(k, _)| k.eq_ignore_ascii_case(name));
+
+        self.entries = kept;
+
+        removed.into_iter().map(|(_, v)| v).collect()
+    }
+
+    /// Copies every header of `other`, replacing the values of names both maps carry.
+    pub fn merge(&mut self, other: Headers) {
+        for (name, _) in &other.entries {
+            self.remove(name);
+        }
+
+        self.entries.extend(other.entries);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.entries.iter().map(|(k, v)| (k, v))
+    }
+
+    /// Number of header lines, counting each value of a multi-value name.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = &'a (String, String);
+    type IntoIter = std::slice::Iter<'a, (String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
+
+impl IntoIterator for Headers {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Headers::new();
+
+        for (k, v) in iter {
+            headers.append(k, v);
+        }
+
+        headers
+    }
+}
+
+impl<K: Into<String>, V: Into<String>, const N: usize> From<[(K, V); N]> for Headers {
+    fn from(entries: [(K, V); N]) -> Self {
+        entries.into_iter().collect()
+    }
+}
+
+/// Serializes as a map; names carrying several values map to a list.
+impl Serialize for Headers {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut names: Vec<&String> = Vec::new();
+
+        for (k, _) in &self.entries {
+            if !names.iter().any(|name| name.eq_ignore_ascii_case(k)) {
+                names.push(k);
+            }
+        }
+
+        let mut map = serializer.serialize_map(Some(names.len()))?;
+
+        for name in names {
+            match self.get_all(name).as_slice() {
+                [value] => map.serialize_entry(name, value)?,
+                values => map.serialize_entry(name, values)?,
+            }
+        }
+
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_match_case_insensitively_and_keep_their_casing() {
+        let headers = Headers::from([("Content-Type", "text/html")]);
+
+        assert_eq!(headers.get("content-type").unwrap(), "text/html");
+        assert!(headers.contains_key("CONTENT-TYPE"));
+        assert_eq!(headers.iter().next().unwrap().0, "Content-Type");
+        assert!(headers.get("accept").is_none());
+    }
+
+    #[test]
+    fn append_keeps_values_and_insert_replaces_them() {
+        let mut headers = Headers::new();
+        headers.append("Set-Cookie", "a=1");
+        headers.append("set-cookie", "b=2");
+
+        assert_eq!(headers.get_all("SET-COOKIE"), vec!["a=1", "b=2"]);
+        assert_eq!(headers.get("set-cookie").unwrap(), "a=1");
+        assert_eq!(headers.len(), 2);
+
+        headers.insert("SET-COOKIE", "c=3");
+
+        assert_eq!(headers.get_all("set-cookie"), vec!["c=3"]);
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn remove_takes_every_value() {
+        let mut headers = Headers::from([("Vary", "Accept"), ("Host", "example.com"), ("vary", "Cookie")]);
+
+        assert_eq!(headers.remove("VARY"), vec!["Accept", "Cookie"]);
+        assert!(headers.remove("vary").is_empty());
+        assert_eq!(headers.len(), 1);
+    }
+
+    #[test]
+    fn merge_replaces_shared_names() {
+        let mut headers = Headers::from([("Link", "</a>"), ("Link", "</b>"), ("Server", "flyer")]);
+        headers.merge(Headers::from([("link", "</c>")]));
+
+        assert_eq!(headers.get_all("link"), vec!["</c>"]);
+        assert_eq!(headers.get("server").unwrap(), "flyer");
+    }
+
+    #[test]
+    fn several_values_serialize_as_a_list() {
+        let headers = Headers::from([("Set-Cookie", "a=1"), ("Host", "example.com"), ("set-cookie", "b=2")]);
+
+        assert_eq!(
+            serde_json::to_value(&headers).unwrap(),
+            serde_json::json!({ "Set-Cookie": ["a=1", "b=2"], "Host": "example.com" })
+        );
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 pub mod development;
 pub mod future;
 pub mod collections;
+pub mod crypto;
 pub mod http;
 pub mod url;
 pub mod vec;
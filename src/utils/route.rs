@@ -11,10 +11,11 @@ pub fn get_middleware_pointer(callback: MiddlewareHandler) -> (String, Box<Middl
     return (ptr, re_call);
 }
 
-pub fn middleware_resolver(server: Instance<Server>, callback: MiddlewareHandler, middlewares: &mut HashSet<String>) {
+pub fn middleware_resolver(server: Instance<Server>, name: &'static str, callback: MiddlewareHandler, middlewares: &mut HashSet<String>) {
     let (k, v) = get_middleware_pointer(callback);
 
     server.as_mut().routes.middlewares.insert(k.clone(), v);
+    server.as_mut().routes.middleware_names.insert(k.clone(), name);
 
     middlewares.insert(k);   
 }
\ No newline at end of file
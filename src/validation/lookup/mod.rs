@@ -0,0 +1,88 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use anyhow::{Result, anyhow, bail};
+use futures::future::BoxFuture;
+
+use crate::utils::future::SendFuture;
+
+pub mod sqlite;
+
+pub const DEFAULT_LOOKUP: &str = "default";
+
+static GLOBAL_LOOKUPS: LazyLock<RwLock<HashMap<String, Arc<dyn LookupErasure>>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Data source behind the `unique` and `exists` rules.
+#[allow(async_fn_in_trait)]
+pub trait Lookup: Send + Sync {
+    /// Rows of `table` whose `column` equals `value`, leaving out the row whose `except.0`
+    /// column equals `except.1`. Table and column names are checked by the caller.
+    async fn count(&self, table: &str, column: &str, value: &str, except: Option<(&str, &str)>) -> Result<u64>;
+}
+
+trait LookupErasure: Send + Sync {
+    fn count<'a>(&'a self, table: &'a str, column: &'a str, value: &'a str, except: Option<(&'a str, &'a str)>) -> BoxFuture<'a, Result<u64>>;
+}
+
+impl<T: Lookup + 'static> LookupErasure for T {
+    fn count<'a>(&'a self, table: &'a str, column: &'a str, value: &'a str, except: Option<(&'a str, &'a str)>) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(SendFuture(Lookup::count(self, table, column, value, except)))
+    }
+}
+
+/// Registers a lookup. Rules use `default` unless the table is written `connection.table`
+/// with a registered `connection`.
+pub fn add(name: impl Into<String>, lookup: impl Lookup + 'static) {
+    GLOBAL_LOOKUPS
+        .write()
+        .expect("Lookup registry lock poisoned")
+        .insert(name.into(), Arc::new(lookup));
+}
+
+/// Splits `connection.table` into the registered lookup and the table.
+fn resolve(table: &str) -> Result<(Arc<dyn LookupErasure>, &str)> {
+    let lookups = GLOBAL_LOOKUPS.read().expect("Lookup registry lock poisoned");
+
+    if let Some((connection, name)) = table.split_once('.') && let Some(lookup) = lookups.get(connection) {
+        return Ok((Arc::clone(lookup), name));
+    }
+
+    let lookup = lookups
+        .get(DEFAULT_LOOKUP)
+        .cloned()
+        .ok_or_else(|| anyhow!("No validation lookup registered; add one with validation::lookup::add"))?;
+
+    Ok((lookup, table))
+}
+
+/// Table and column names are spliced into queries, so only plain identifiers are allowed.
+pub(crate) fn check_identifier(name: &str) -> Result<()> {
+    let valid = name.split('.').all(|part| {
+        let mut chars = part.chars();
+
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+
+    if !valid {
+        bail!("Invalid table or column name '{}'", name);
+    }
+
+    Ok(())
+}
+
+pub async fn count(table: &str, column: &str, value: &str, except: Option<(&str, &str)>) -> Result<u64> {
+    let (lookup, table) = resolve(table)?;
+
+    check_identifier(table)?;
+    check_identifier(column)?;
+
+    if let Some((id_column, _)) = except {
+        check_identifier(id_column)?;
+    }
+
+    lookup.count(table, column, value, except).await
+}
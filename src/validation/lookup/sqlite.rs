@@ -0,0 +1,64 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Result, anyhow};
+use rusqlite::{Connection, params};
+
+use crate::validation::lookup::Lookup;
+
+/// Runs `unique` and `exists` against a SQLite database; handy in tests with `in_memory`.
+pub struct SqliteLookup {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteLookup {
+    pub fn new(path: impl Into<String>) -> Result<Self> {
+        Ok(Self::from_connection(Connection::open(path.into())?))
+    }
+
+    pub fn in_memory() -> Result<Self> {
+        Ok(Self::from_connection(Connection::open_in_memory()?))
+    }
+
+    pub fn from_connection(connection: Connection) -> Self {
+        Self {
+            connection: Arc::new(Mutex::new(connection)),
+        }
+    }
+
+    /// Runs SQL such as a schema and fixtures, mostly for tests.
+    pub fn execute_batch(&self, sql: &str) -> Result<()> {
+        self.connection
+            .lock()
+            .map_err(|_| anyhow!("Lookup database lock poisoned"))?
+            .execute_batch(sql)
+            .map_err(|err| err.into())
+    }
+}
+
+impl Lookup for SqliteLookup {
+    async fn count(&self, table: &str, column: &str, value: &str, except: Option<(&str, &str)>) -> Result<u64> {
+        let connection = Arc::clone(&self.connection);
+        let value = value.to_string();
+        let (sql, except_value) = match except {
+            Some((id_column, id)) => (
+                format!("SELECT COUNT(*) FROM {table} WHERE {column} = ?1 AND {id_column} != ?2"),
+                Some(id.to_string()),
+            ),
+            None => (format!("SELECT COUNT(*) FROM {table} WHERE {column} = ?1"), None),
+        };
+
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .map_err(|_| anyhow!("Lookup database lock poisoned"))?;
+
+            let count: i64 = match except_value {
+                Some(id) => connection.query_row(&sql, params![value, id], |row| row.get(0))?,
+                None => connection.query_row(&sql, params![value], |row| row.get(0))?,
+            };
+
+            Ok(count as u64)
+        })
+        .await?
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{Context, Result, bail};
+use arc_swap::ArcSwap;
+use serde_json::Value;
+
+use crate::{i18n::{flatten, negotiate}, request::Request};
+
+/// Session key holding the locale a user picked, which wins over `Accept-Language`.
+pub const LOCALE_SESSION_KEY: &str = "locale";
+
+type Catalog = HashMap<String, String>;
+
+static CATALOGS: LazyLock<ArcSwap<HashMap<String, Arc<Catalog>>>> = LazyLock::new(|| ArcSwap::from_pointee(HashMap::new()));
+
+/// Per-locale validation messages. A catalog maps rule names to messages with `:attribute`
+/// and rule argument placeholders (`:min`, `:max`, `:size`, `:other`, `:values`, ...):
+///
+/// ```json
+/// {
+///     "required": "Le champ :attribute est obligatoire.",
+///     "min": { "string": ":attribute doit contenir au moins :min caractères.", "numeric": ":attribute doit être au moins :min." },
+///     "attributes": { "email": "adresse e-mail" }
+/// }
+/// ```
+///
+/// Rules without a message in the request's locale keep their built-in English message.
+pub struct Messages;
+
+impl Messages {
+    /// Loads every `<locale>.json` and `<locale>.toml` file of `dir`, e.g. `lang/fr.json`.
+    pub fn load(dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir).with_context(|| format!("Failed to read message directory {}", dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+
+            let Some(locale) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let tree: Value = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("json") => serde_json::from_str(&fs::read_to_string(&path)?)
+                    .with_context(|| format!("Invalid messages in {}", path.display()))?,
+                Some("toml") => toml::from_str(&fs::read_to_string(&path)?)
+                    .with_context(|| format!("Invalid messages in {}", path.display()))?,
+                _ => continue,
+            };
+
+            Self::add_tree(locale, &tree)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds messages for `locale`, keyed like the catalog files with nested keys dotted
+    /// (`min.string`, `attributes.email`).
+    pub fn add(locale: impl Into<String>, messages: HashMap<String, String>) {
+        let locale = locale.into().to_lowercase();
+
+        CATALOGS.rcu(|current| {
+            let mut catalogs = (**current).clone();
+            let mut catalog = catalogs.get(&locale).map(|catalog| (**catalog).clone()).unwrap_or_default();
+
+            catalog.extend(messages.clone());
+            catalogs.insert(locale.clone(), Arc::new(catalog));
+            catalogs
+        });
+    }
+
+    /// Adds messages shaped like a catalog file.
+    pub fn add_tree(locale: impl Into<String>, tree: &Value) -> Result<()> {
+        let mut messages = Catalog::new();
+
+        if !tree.is_object() {
+            bail!("Validation messages must be a map of rule names to messages");
+        }
+
+        flatten(tree, String::new(), &mut messages);
+        Self::add(locale, messages);
+
+        Ok(())
+    }
+
+    pub fn locales() -> Vec<String> {
+        CATALOGS.load().keys().cloned().collect()
+    }
+
+    pub(crate) fn catalog(locale: &str) -> Option<Arc<Catalog>> {
+        CATALOGS.load().get(&locale.to_lowercase()).cloned()
+    }
+
+    /// The locale picked by the `I18n` hook, else the session's locale when set, else the best
+    /// `Accept-Language` match among the loaded catalogs.
+    pub(crate) fn request_locale(req: &Request) -> Option<String> {
+        if let Some(locale) = &req.locale {
+            return Some(locale.clone());
+        }
+
+        let chosen = req.session.get(LOCALE_SESSION_KEY);
+
+        if !chosen.is_empty() {
+            return Some(chosen);
+        }
+
+        negotiate(&req.header("accept-language"), &Self::locales())
+    }
+}
+
+/// Names of the placeholders filled from a rule's arguments, in argument order.
+pub(crate) fn placeholders(rule: &str) -> &'static [&'static str] {
+    match rule {
+        "min" | "min_digits" => &["min"],
+        "max" | "max_digits" => &["max"],
+        "between" | "digits_between" => &["min", "max"],
+        "size" => &["size"],
+        "digits" => &["digits"],
+        "decimal" => &["decimal"],
+        "gt" | "gte" | "lt" | "lte" | "multiple_of" => &["value"],
+        "same" | "different" => &["other"],
+        "after" | "after_or_equal" | "before" | "before_or_equal" | "date_equals" => &["date"],
+        "date_format" => &["format"],
+        "accepted_if" | "declined_if" | "required_if" | "required_unless" | "prohibited_if"
+        | "prohibited_unless" | "missing_if" | "missing_unless" | "present_if" | "present_unless" => &["other", "value"],
+        "required_if_accepted" => &["other"],
+        _ => &[],
+    }
+}
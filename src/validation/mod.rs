@@ -7,17 +7,23 @@ use std::{
 use arc_swap::ArcSwap;
 use futures::future::BoxFuture;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 
 use crate::{
     request::{form::Form, Request},
     response::{Response, HTTP_UNPROCESSABLE_CONTENT},
     routing::next::Next,
     utils::Values,
-    validation::rules::*,
+    i18n::replace_placeholders,
+    validation::{messages::{Messages, placeholders}, rules::*},
 };
 
+pub mod lookup;
+pub mod messages;
 pub mod rules;
 
+pub use flyer_derive::Validate;
+
 pub type Rule = dyn for<'a> Fn(&'a Form, String, Vec<String>) -> BoxFuture<'a, Option<String>> + Send + Sync + 'static;
 
 pub trait AsyncRule<'a>: Send + Sync {
@@ -38,12 +44,12 @@ where
 
 pub(crate) struct Field {
     pub(crate) name: String,
-    pub(crate) rules: Vec<(Arc<Rule>, Vec<String>)>,
+    pub(crate) rules: Vec<(String, Arc<Rule>, Vec<String>)>,
     pub(crate) nullable: bool,
 }
 
 impl Field {
-    pub(crate) fn new(field: impl Into<String>, rules: Vec<(Arc<Rule>, Vec<String>)>, nullable: bool) -> Self {
+    pub(crate) fn new(field: impl Into<String>, rules: Vec<(String, Arc<Rule>, Vec<String>)>, nullable: bool) -> Self {
         Self {
             name: field.into(),
             rules: rules,
@@ -61,6 +67,9 @@ pub struct Validator<'f> {
 #[derive(Default)]
 pub struct Rules {
     pub(crate) fields: Vec<Field>,
+    pub(crate) attributes: HashMap<String, String>,
+    pub(crate) messages: HashMap<String, String>,
+    pub(crate) locale: Option<String>,
 }
 
 impl Rules {
@@ -96,13 +105,49 @@ impl Rules {
                 .cloned()
                 .unwrap_or_else(|| panic!("The rule `{}` does not exist", name));
 
-            v.push((rule_callback, args));
+            v.push((name.to_string(), rule_callback, args));
         }
 
         self.fields.push(Field::new(field, v, is_nullable));
         self
     }
 
+    /// Adds the rules of another set under `prefix`, so `city` becomes `prefix.city`.
+    pub fn nest(&mut self, prefix: &str, rules: Rules) -> &mut Self {
+        for mut field in rules.fields {
+            field.name = format!("{}.{}", prefix, field.name);
+            self.fields.push(field);
+        }
+        for (field, name) in rules.attributes {
+            self.attributes.insert(format!("{}.{}", prefix, field), name);
+        }
+        for (key, message) in rules.messages {
+            self.messages.insert(format!("{}.{}", prefix, key), message);
+        }
+        self
+    }
+
+    /// Name shown for `field` in messages instead of the field name, e.g. `email` as
+    /// "email address".
+    pub fn attribute(&mut self, field: &str, name: impl Into<String>) -> &mut Self {
+        self.attributes.insert(field.to_string(), name.into());
+        self
+    }
+
+    /// Message for one rule of one field, keyed `field.rule` (`email.required`), or for a rule
+    /// on every field, keyed by the rule name. Takes the same placeholders as the catalogs.
+    pub fn message(&mut self, key: &str, message: impl Into<String>) -> &mut Self {
+        self.messages.insert(key.to_string(), message.into());
+        self
+    }
+
+    /// Locale of the messages; `Rules::handle` otherwise takes it from the session or the
+    /// `Accept-Language` header.
+    pub fn locale(&mut self, locale: impl Into<String>) -> &mut Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
     pub fn add<F>(name: impl Into<String>, callback: F)
     where
         F: for<'a> AsyncRule<'a> + Send + Sync + 'static,
@@ -129,6 +174,31 @@ impl Rules {
     }
 }
 
+/// Implemented with `#[derive(Validate)]` from the `#[validate(...)]` attributes on the
+/// fields, so a misspelled rule fails to compile instead of panicking on the first request.
+pub trait Validate: DeserializeOwned + Send {
+    fn rules() -> Rules;
+
+    /// Runs the rules over `form` and deserializes it into `Self` when they pass.
+    fn validate(form: &Form) -> impl Future<Output = Result<Self, Values>> + Send {
+        async move {
+            let mut validator = Validator::new(form, Self::rules());
+
+            if !validator.validate().await {
+                return Err(validator.errors());
+            }
+
+            form.deserialize::<Self>()
+                .map_err(|err| Values::from([(String::from("form"), err.to_string())]))
+        }
+    }
+
+    /// Middleware rejecting the request like `Rules::handle` when the rules fail.
+    fn handle(req: Request, res: Response, next: Next) -> impl Future<Output = Response> + Send {
+        Self::rules().handle(req, res, next)
+    }
+}
+
 impl<'f> Validator<'f> {
     pub fn new(form: &'f Form, rules: Rules) -> Self {
         Self {
@@ -180,7 +250,7 @@ impl<'f> Validator<'f> {
                             &normalized_dot_key,
                             &key_map,
                         ).await {
-                            self.errors.insert(lookup_key, message);
+                            self.errors.insert(normalized_dot_key, message);
                         }
                     }
                 }
@@ -199,7 +269,7 @@ impl<'f> Validator<'f> {
                     &normalized,
                     &key_map,
                 ).await {
-                    self.errors.insert(lookup_key, message);
+                    self.errors.insert(normalized, message);
                 }
             }
         }
@@ -207,6 +277,7 @@ impl<'f> Validator<'f> {
         self.errors.is_empty()
     }
 
+    /// Messages keyed by the dotted path of the field, such as `items.0.name`.
     pub fn errors(&mut self) -> Values {
         self.errors.clone()
     }
@@ -224,16 +295,16 @@ impl<'f> Validator<'f> {
             return None;
         }
 
-        for (rule, args) in &field.rules {
+        for (name, rule, args) in &field.rules {
             let localized_args = self.localize_args(args, pattern, normalized_key);
             let final_args: Vec<String> = localized_args
                 .into_iter()
                 .map(|arg| key_map.get(&arg).cloned().unwrap_or(arg))
                 .collect();
 
-            if let Some(error) = rule(form, lookup_key.to_string(), final_args).await {
+            if let Some(error) = rule(form, lookup_key.to_string(), final_args.clone()).await {
                 if !error.is_empty() {
-                    return Some(error);
+                    return Some(self.message(name, pattern, lookup_key, &final_args, error));
                 }
                 return None;
             }
@@ -242,10 +313,60 @@ impl<'f> Validator<'f> {
         None
     }
 
+    /// Picks the message for a failed rule: the `Rules::message` override, then the locale's
+    /// catalog, then the rule's own English message with the attribute name swapped in.
+    fn message(&self, rule: &str, pattern: &str, lookup_key: &str, args: &[String], fallback: String) -> String {
+        let catalog = self.rules.locale.as_deref().and_then(Messages::catalog);
+        let attribute = |field: &str, own: bool| -> String {
+            let mut keys = vec![self.normalize_key(field)];
+
+            if own {
+                keys.insert(0, pattern.to_string());
+            }
+
+            keys.iter()
+                .find_map(|key| {
+                    self.rules.attributes.get(key)
+                        .or_else(|| catalog.as_ref()?.get(&format!("attributes.{}", key)))
+                })
+                .cloned()
+                .unwrap_or_else(|| field.replace('_', " "))
+        };
+
+        let kind = measure_kind(self.form, lookup_key);
+        let template = self.rules.messages.get(&format!("{}.{}", pattern, rule))
+            .or_else(|| self.rules.messages.get(&format!("{}.{}", self.normalize_key(lookup_key), rule)))
+            .or_else(|| self.rules.messages.get(rule))
+            .cloned()
+            .or_else(|| {
+                let catalog = catalog.as_ref()?;
+                kind.and_then(|kind| catalog.get(&format!("{}.{}", rule, kind)))
+                    .or_else(|| catalog.get(rule))
+                    .cloned()
+            });
+
+        let name = attribute(lookup_key, true);
+
+        let Some(template) = template else {
+            let pretty = lookup_key.replace('_', " ");
+            return fallback.replacen(&pretty, &name, 1);
+        };
+
+        let mut replacements = vec![
+            (String::from("attribute"), name),
+            (String::from("values"), args.join(", ")),
+        ];
+
+        for (placeholder, arg) in placeholders(rule).iter().zip(args) {
+            let value = if *placeholder == "other" { attribute(arg, false) } else { arg.clone() };
+            replacements.push((placeholder.to_string(), value));
+        }
+
+        replace_placeholders(&template, replacements)
+    }
+
     pub fn normalize_key(&self, key: &str) -> String {
-        let re = Regex::new(r"\[([^\]]+)\]").unwrap();
-        let dotted = re.replace_all(key, ".$1");
-        dotted.trim_start_matches('.').to_string()
+        dotted(key)
     }
 
     pub fn expand_wildcard_pattern(&self, all_normalized_keys: &[String], pattern: &str) -> Vec<String> {
@@ -327,18 +448,31 @@ impl<'f> Validator<'f> {
 }
 
 impl Validator<'_> {
-    pub async fn handle(req: Request, res: Response, next: Next, rules: Rules) -> Response {
+    pub async fn handle(req: Request, res: Response, next: Next, mut rules: Rules) -> Response {
+        if rules.locale.is_none() {
+            rules.locale = Messages::request_locale(&req);
+        }
+
         let mut validator = Validator::new(&req.form, rules);
 
         if validator.validate().await {
             return next.handle(req, res);
         }
 
-        if req.is_json() {
+        if req.wants_json() {
+            let errors: HashMap<&String, [&String; 1]> = validator
+                .errors
+                .iter()
+                .map(|(path, message)| (path, [message]))
+                .collect();
+
             return res
                 .status_code(HTTP_UNPROCESSABLE_CONTENT)
                 .set_header("Content-Type", "application/json")
-                .json(&validator.errors);
+                .json(&serde_json::json!({
+                    "message": "The given data was invalid.",
+                    "errors": errors,
+                }));
         }
 
         res.with_old(req.form.values.clone())
@@ -347,6 +481,13 @@ impl Validator<'_> {
     }
 }
 
+/// Turns `user[address][city]` into `user.address.city`, the form errors are keyed by.
+pub(crate) fn dotted(key: &str) -> String {
+    let re = Regex::new(r"\[([^\]]+)\]").unwrap();
+    let dotted = re.replace_all(key, ".$1");
+    dotted.trim_start_matches('.').to_string()
+}
+
 static RULES: LazyLock<ArcSwap<HashMap<String, Arc<Rule>>>> = LazyLock::new(|| {
     let mut map: HashMap<String, Arc<Rule>> = HashMap::new();
 
@@ -359,6 +500,7 @@ static RULES: LazyLock<ArcSwap<HashMap<String, Arc<Rule>>>> = LazyLock::new(|| {
     map.insert(String::from("alpha_dash"), Arc::new(|form, field, args| Box::pin(alpha_dash(form, field, args))));
     map.insert(String::from("alpha_numeric"), Arc::new(|form, field, args| Box::pin(alpha_numeric(form, field, args))));
     map.insert(String::from("alpha_num"), Arc::new(|form, field, args| Box::pin(alpha_numeric(form, field, args))));
+    map.insert(String::from("array"), Arc::new(|form, field, args| Box::pin(array(form, field, args))));
     map.insert(String::from("ascii"), Arc::new(|form, field, args| Box::pin(ascii(form, field, args))));
     map.insert(String::from("before"), Arc::new(|form, field, args| Box::pin(before(form, field, args))));
     map.insert(String::from("before_or_equal"), Arc::new(|form, field, args| Box::pin(before_or_equal(form, field, args))));
@@ -374,10 +516,12 @@ static RULES: LazyLock<ArcSwap<HashMap<String, Arc<Rule>>>> = LazyLock::new(|| {
     map.insert(String::from("different"), Arc::new(|form, field, args| Box::pin(different(form, field, args))));
     map.insert(String::from("digits"), Arc::new(|form, field, args| Box::pin(digits(form, field, args))));
     map.insert(String::from("digits_between"), Arc::new(|form, field, args| Box::pin(digits_between(form, field, args))));
+    map.insert(String::from("distinct"), Arc::new(|form, field, args| Box::pin(distinct(form, field, args))));
     map.insert(String::from("doesnt_start_with"), Arc::new(|form, field, args| Box::pin(doesnt_start_with(form, field, args))));
     map.insert(String::from("doesnt_end_with"), Arc::new(|form, field, args| Box::pin(doesnt_end_with(form, field, args))));
     map.insert(String::from("email"), Arc::new(|form, field, args| Box::pin(email(form, field, args))));
     map.insert(String::from("ends_with"), Arc::new(|form, field, args| Box::pin(ends_with(form, field, args))));
+    map.insert(String::from("exists"), Arc::new(|form, field, args| Box::pin(exists(form, field, args))));
     map.insert(String::from("extensions"), Arc::new(|form, field, args| Box::pin(extensions(form, field, args))));
     map.insert(String::from("file"), Arc::new(|form, field, args| Box::pin(file(form, field, args))));
     map.insert(String::from("filled"), Arc::new(|form, field, args| Box::pin(filled(form, field, args))));
@@ -429,10 +573,68 @@ static RULES: LazyLock<ArcSwap<HashMap<String, Arc<Rule>>>> = LazyLock::new(|| {
     map.insert(String::from("size"), Arc::new(|form, field, args| Box::pin(size(form, field, args))));
     map.insert(String::from("starts_with"), Arc::new(|form, field, args| Box::pin(starts_with(form, field, args))));
     map.insert(String::from("string"), Arc::new(|form, field, args| Box::pin(string(form, field, args))));
+    map.insert(String::from("unique"), Arc::new(|form, field, args| Box::pin(unique(form, field, args))));
     map.insert(String::from("uppercase"), Arc::new(|form, field, args| Box::pin(uppercase(form, field, args))));
     map.insert(String::from("url"), Arc::new(|form, field, args| Box::pin(url(form, field, args))));
     map.insert(String::from("ulid"), Arc::new(|form, field, args| Box::pin(ulid(form, field, args))));
     map.insert(String::from("uuid"), Arc::new(|form, field, args| Box::pin(uuid(form, field, args))));
 
     ArcSwap::from_pointee(map)
-});
\ No newline at end of file
+});
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use serde_json::json;
+
+    use super::*;
+
+    async fn errors(form: &Form, field: &str, rules: Vec<&str>) -> Values {
+        let mut set = Rules::new();
+        set.rule(field, rules);
+
+        let mut validator = Validator::new(form, set);
+        validator.validate().await;
+        validator.errors()
+    }
+
+    fn urlencoded(query: &str) -> Form {
+        Form::from_pairs(serde_urlencoded::from_str(query).unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn json_strings_are_measured_by_characters() {
+        let form = Form::from_value(json!({"pin": "12345", "code": "0042", "age": 30}));
+
+        assert!(errors(&form, "pin", vec!["max:6"]).await.is_empty());
+        assert!(errors(&form, "code", vec!["size:4"]).await.is_empty());
+        assert!(!errors(&form, "code", vec!["size:42"]).await.is_empty());
+        assert!(!errors(&form, "age", vec!["max:6"]).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn urlencoded_numbers_are_measured_by_value() {
+        let form = urlencoded("pin=12345&code=0042&name=ada");
+
+        assert!(!errors(&form, "pin", vec!["max:6"]).await.is_empty());
+        assert!(errors(&form, "code", vec!["size:42"]).await.is_empty());
+        assert!(errors(&form, "name", vec!["size:3"]).await.is_empty());
+    }
+
+    mod derive {
+        include!("../../flyer-derive/src/rules.rs");
+    }
+
+    #[test]
+    fn derive_macro_knows_every_builtin_rule() {
+        // `nullable` is read by `Rules::rule` itself rather than registered.
+        let mut registered: HashSet<String> = RULES.load().keys().cloned().collect();
+        registered.insert("nullable".to_string());
+
+        let known: HashSet<String> = derive::RULES.iter().map(|rule| rule.to_string()).collect();
+
+        assert_eq!(registered, known);
+        assert!(derive::NEEDS_ARGUMENTS.iter().all(|rule| known.contains(*rule)));
+    }
+}
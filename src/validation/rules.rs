@@ -8,6 +8,8 @@ use uuid::Uuid;
 use ulid::Ulid;
 use serde_json::Value as JsonValue;
 
+use super::{dotted, lookup};
+
 // TODO: Added new logic if Rule returns empty String error skip all validation
 fn pretty(value: String) -> String {
     let temp: Vec<&str> = value.split('_').collect();
@@ -18,7 +20,77 @@ fn get_value(form: &Form, field: &str) -> Option<String> {
     form.values.get(field).cloned()
 }
 
+/// The field in the structured body, where JSON numbers, booleans, lists and objects keep
+/// their type.
+fn node<'f>(form: &'f Form, field: &str) -> Option<&'f JsonValue> {
+    form.get(field)
+}
+
+/// What the size rules compare: the items of a list, the value of a number, the characters
+/// of a string or the kilobytes of a file.
+enum Measure {
+    Number(f64),
+    Items(usize),
+    Characters(usize),
+    Kilobytes(usize),
+}
+
+impl Measure {
+    fn amount(&self) -> f64 {
+        match self {
+            Measure::Number(num) => *num,
+            Measure::Items(count) | Measure::Characters(count) | Measure::Kilobytes(count) => *count as f64,
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Measure::Number(_) => "",
+            Measure::Items(_) => " items",
+            Measure::Characters(_) => " characters",
+            Measure::Kilobytes(_) => " kilobytes",
+        }
+    }
+}
+
+fn measure(form: &Form, field: &str) -> Option<Measure> {
+    match node(form, field) {
+        Some(JsonValue::Array(items)) => return Some(Measure::Items(items.len())),
+        Some(JsonValue::Object(fields)) => return Some(Measure::Items(fields.len())),
+        Some(JsonValue::Number(num)) => return num.as_f64().map(Measure::Number),
+        // A JSON string is text even when it holds digits, like a PIN or a zip code.
+        Some(JsonValue::String(val)) if form.typed => return Some(Measure::Characters(val.chars().count())),
+        _ => {}
+    }
+    if let Some(val) = get_value(form, field) {
+        return match val.parse::<f64>() {
+            Ok(num) => Some(Measure::Number(num)),
+            Err(_) => Some(Measure::Characters(val.chars().count())),
+        };
+    }
+    form.files.get(field).map(|file| {
+        let bytes = file.stored.as_ref().map(|stored| stored.size as usize).unwrap_or(file.content.len());
+        Measure::Kilobytes(bytes / 1024)
+    })
+}
+
+/// Which variant of a size message fits the field: `numeric`, `string`, `array` or `file`.
+pub(crate) fn measure_kind(form: &Form, field: &str) -> Option<&'static str> {
+    Some(match measure(form, field)? {
+        Measure::Number(_) => "numeric",
+        Measure::Items(_) => "array",
+        Measure::Characters(_) => "string",
+        Measure::Kilobytes(_) => "file",
+    })
+}
+
 pub fn is_empty(form: &Form, field: &str) -> bool {
+    match node(form, field) {
+        Some(JsonValue::Null) => return true,
+        Some(JsonValue::Array(items)) => return items.is_empty(),
+        Some(JsonValue::Object(fields)) => return fields.is_empty(),
+        _ => {}
+    }
     if let Some(val) = form.values.get(field) {
         return val.is_empty();
     }
@@ -29,7 +101,7 @@ pub fn is_empty(form: &Form, field: &str) -> bool {
 }
 
 fn is_present(form: &Form, field: &str) -> bool {
-    form.values.contains_key(field) || form.files.contains_key(field)
+    form.values.contains_key(field) || form.files.contains_key(field) || node(form, field).is_some()
 }
 
 // Booleans
@@ -57,6 +129,12 @@ pub async fn accepted_if(form: &Form, field: String, args: Vec<String>) -> Optio
 }
 
 pub async fn boolean(form: &Form, field: String, _args: Vec<String>) -> Option<String> {
+    match node(form, &field) {
+        Some(JsonValue::Bool(_)) => return None,
+        Some(JsonValue::Number(num)) if num.as_u64() == Some(0) || num.as_u64() == Some(1) => return None,
+        Some(JsonValue::String(_)) | None => {}
+        Some(_) => return Some(format!("The {} must be a boolean", pretty(field))),
+    }
     if let Some(val) = get_value(form, &field) {
         let bool_vals = vec!["true", "false", "1", "0", "on", "off", "yes", "no"];
         if bool_vals.contains(&val.to_lowercase().as_str()) {
@@ -352,20 +430,9 @@ pub async fn between(form: &Form, field: String, args: Vec<String>) -> Option<St
     let min: f64 = args[0].parse().unwrap_or(0.0);
     let max: f64 = args[1].parse().unwrap_or(0.0);
 
-    if let Some(val) = get_value(form, &field) {
-        if let Ok(num) = val.parse::<f64>() {
-            if num >= min && num <= max { return None; }
-            return Some(format!("The {} must be between {} and {}", pretty(field), min, max));
-        }
-        if val.len() >= min as usize && val.len() <= max as usize { return None; }
-        return Some(format!("The {} must be between {} and {} characters", pretty(field), min, max));
-    }
-    if let Some(file) = form.files.get(&field) {
-        let size_kb = file.content.len() / 1024;
-        if size_kb >= min as usize && size_kb <= max as usize { return None; }
-        return Some(format!("The {} must be between {} and {} kilobytes", pretty(field), min, max));
-    }
-    None
+    let measured = measure(form, &field)?;
+    if measured.amount() >= min && measured.amount() <= max { return None; }
+    Some(format!("The {} must be between {} and {}{}", pretty(field), min, max, measured.unit()))
 }
 
 pub async fn decimal(form: &Form, field: String, args: Vec<String>) -> Option<String> {
@@ -451,6 +518,11 @@ pub async fn gte(form: &Form, field: String, args: Vec<String>) -> Option<String
 }
 
 pub async fn integer(form: &Form, field: String, _args: Vec<String>) -> Option<String> {
+    match node(form, &field) {
+        Some(JsonValue::Number(num)) if num.is_i64() || num.is_u64() => return None,
+        Some(JsonValue::String(_)) | None => {}
+        Some(_) => return Some(format!("The {} must be an integer", pretty(field))),
+    }
     if let Some(val) = get_value(form, &field) {
         if val.parse::<i128>().is_ok() { return None; }
     }
@@ -495,40 +567,18 @@ pub async fn max(form: &Form, field: String, args: Vec<String>) -> Option<String
     if args.is_empty() { return None; }
     let max_val: f64 = args[0].parse().unwrap_or(0.0);
 
-    if let Some(val) = get_value(form, &field) {
-        if let Ok(num) = val.parse::<f64>() {
-            if num <= max_val { return None; }
-            return Some(format!("The {} must not be greater than {}", pretty(field), max_val));
-        }
-        if val.len() <= max_val as usize { return None; }
-        return Some(format!("The {} must not be greater than {} characters", pretty(field), max_val));
-    }
-    if let Some(file) = form.files.get(&field) {
-        let size_kb = file.content.len() / 1024;
-        if size_kb <= max_val as usize { return None; }
-        return Some(format!("The {} must not be greater than {} kilobytes", pretty(field), max_val));
-    }
-    None
+    let measured = measure(form, &field)?;
+    if measured.amount() <= max_val { return None; }
+    Some(format!("The {} must not be greater than {}{}", pretty(field), max_val, measured.unit()))
 }
 
 pub async fn min(form: &Form, field: String, args: Vec<String>) -> Option<String> {
     if args.is_empty() { return None; }
     let min_val: f64 = args[0].parse().unwrap_or(0.0);
 
-    if let Some(val) = get_value(form, &field) {
-        if let Ok(num) = val.parse::<f64>() {
-            if num >= min_val { return None; }
-            return Some(format!("The {} must be at least {}", pretty(field), min_val));
-        }
-        if val.len() >= min_val as usize { return None; }
-        return Some(format!("The {} must be at least {} characters", pretty(field), min_val));
-    }
-    if let Some(file) = form.files.get(&field) {
-        let size_kb = file.content.len() / 1024;
-        if size_kb >= min_val as usize { return None; }
-        return Some(format!("The {} must be at least {} kilobytes", pretty(field), min_val));
-    }
-    None
+    let measured = measure(form, &field)?;
+    if measured.amount() >= min_val { return None; }
+    Some(format!("The {} must be at least {}{}", pretty(field), min_val, measured.unit()))
 }
 
 pub async fn max_digits(form: &Form, field: String, args: Vec<String>) -> Option<String> {
@@ -565,6 +615,11 @@ pub async fn multiple_of(form: &Form, field: String, args: Vec<String>) -> Optio
 }
 
 pub async fn numeric(form: &Form, field: String, _args: Vec<String>) -> Option<String> {
+    match node(form, &field) {
+        Some(JsonValue::Number(_)) => return None,
+        Some(JsonValue::String(_)) | None => {}
+        Some(_) => return Some(format!("The {} must be a number", pretty(field))),
+    }
     if let Some(val) = get_value(form, &field) {
         if val.parse::<f64>().is_ok() { return None; }
     }
@@ -910,7 +965,11 @@ pub async fn present_unless(form: &Form, field: String, args: Vec<String>) -> Op
 }
 
 pub async fn string(form: &Form, field: String, _args: Vec<String>) -> Option<String> {
-    if get_value(form, &field).is_some() { return None; }
+    match node(form, &field) {
+        Some(JsonValue::String(_)) => return None,
+        None if get_value(form, &field).is_some() => return None,
+        _ => {}
+    }
     Some(format!("The {} must be a string", pretty(field)))
 }
 
@@ -918,19 +977,97 @@ pub async fn size(form: &Form, field: String, args: Vec<String>) -> Option<Strin
     if args.is_empty() { return None; }
     let size: usize = args[0].parse().unwrap_or(0);
 
-    if let Some(val) = get_value(form, &field) {
-        if let Ok(num) = val.parse::<f64>() {
-            if num == size as f64 { return None; }
-            return Some(format!("The {} must be {}", pretty(field), size));
+    let measured = measure(form, &field)?;
+    if measured.amount() == size as f64 { return None; }
+    Some(format!("The {} must be {}{}", pretty(field), size, measured.unit()))
+}
+
+// Lists
+pub async fn array(form: &Form, field: String, args: Vec<String>) -> Option<String> {
+    match node(form, &field) {
+        Some(JsonValue::Array(_)) => return None,
+        Some(JsonValue::Object(fields)) => {
+            if args.is_empty() || fields.keys().all(|key| args.contains(key)) { return None; }
+            return Some(format!("The {} may only contain the keys: {}", pretty(field), args.join(", ")));
         }
-        if val.len() == size { return None; }
-        return Some(format!("The {} must be {} characters", pretty(field), size));
-    }
-    if let Some(file) = form.files.get(&field) {
-        let size_kb = file.content.len() / 1024;
-        if size_kb == size { return None; }
-        return Some(format!("The {} must be {} kilobytes", pretty(field), size));
+        _ => {}
+    }
+    Some(format!("The {} must be an array", pretty(field)))
+}
+
+/// Applied to a list, its items must differ; applied to `items.*.id`, each id must differ
+/// from the ids of the other items.
+pub async fn distinct(form: &Form, field: String, args: Vec<String>) -> Option<String> {
+    let ignore_case = args.iter().any(|arg| arg == "ignore_case");
+    let comparable = |value: &JsonValue| match value {
+        JsonValue::String(s) if ignore_case => JsonValue::String(s.to_lowercase()),
+        other => other.clone(),
+    };
+    let message = format!("The {} field has a duplicate value", pretty(field.clone()));
+
+    if let Some(JsonValue::Array(items)) = node(form, &field) {
+        let mut seen = Vec::with_capacity(items.len());
+        for item in items {
+            let item = comparable(item);
+            if seen.contains(&item) { return Some(message); }
+            seen.push(item);
+        }
+        return None;
     }
+
+    let path = dotted(&field);
+    let segments: Vec<&str> = path.split('.').collect();
+    let position = segments.iter().rposition(|segment| segment.parse::<usize>().is_ok())?;
+    let current = comparable(node(form, &field)?);
+    let parent = segments[..position].join(".");
+    let Some(JsonValue::Array(siblings)) = node(form, &parent) else { return None; };
+
+    let duplicates = (0..siblings.len())
+        .filter(|index| {
+            let mut sibling = vec![parent.clone(), index.to_string()];
+            sibling.extend(segments[position + 1..].iter().map(|segment| segment.to_string()));
+            node(form, &sibling.join(".")).is_some_and(|value| comparable(value) == current)
+        })
+        .count();
+
+    if duplicates > 1 { return Some(message); }
     None
 }
 
+// Database
+/// `unique:table,column,except_id,id_column`; the column defaults to the field name and the
+/// id column to `id`, so `unique:users,email,5` ignores the user being updated.
+pub async fn unique(form: &Form, field: String, args: Vec<String>) -> Option<String> {
+    if args.is_empty() { return None; }
+    let val = get_value(form, &field)?;
+    let column = column_arg(&args, &field);
+    let except = args.get(2)
+        .filter(|id| !id.is_empty() && !id.eq_ignore_ascii_case("null"))
+        .map(|id| (args.get(3).map(String::as_str).unwrap_or("id"), id.as_str()));
+
+    match lookup::count(&args[0], &column, &val, except).await {
+        Ok(0) => None,
+        Ok(_) => Some(format!("The {} has already been taken", pretty(field))),
+        Err(_) => Some(format!("The {} could not be checked", pretty(field))),
+    }
+}
+
+/// `exists:table,column`; the column defaults to the field name.
+pub async fn exists(form: &Form, field: String, args: Vec<String>) -> Option<String> {
+    if args.is_empty() { return None; }
+    let val = get_value(form, &field)?;
+    let column = column_arg(&args, &field);
+
+    match lookup::count(&args[0], &column, &val, None).await {
+        Ok(0) => Some(format!("The selected {} is invalid", pretty(field))),
+        Ok(_) => None,
+        Err(_) => Some(format!("The {} could not be checked", pretty(field))),
+    }
+}
+
+fn column_arg(args: &[String], field: &str) -> String {
+    match args.get(1).filter(|column| !column.is_empty()) {
+        Some(column) => column.clone(),
+        None => dotted(field).rsplit('.').next().unwrap_or(field).to_string(),
+    }
+}
@@ -0,0 +1,118 @@
+use std::fs;
+
+use regex::Regex;
+
+use crate::view::ViewData;
+
+/// Lines of template source shown before and after the failing line.
+const SOURCE_CONTEXT_LINES: usize = 4;
+
+/// The development page for a view that failed to render: the template name, the error, the
+/// template source around the failing line and the data the view was rendered with.
+pub(crate) fn render_error_page(view: &str, error: &str, source: Option<String>, data: &ViewData) -> String {
+    let context = serde_json::to_string_pretty(&data.to_json()).unwrap_or_default();
+
+    // A reload can fail on another template than the one rendered; show that one instead.
+    let parsed = Regex::new(r#"Failed to parse "([^"]+)""#).expect("valid regex");
+    let (view, source) = match parsed.captures(error) {
+        Some(captures) => (captures[1].to_string(), fs::read_to_string(&captures[1]).ok()),
+        None => (view.to_string(), source),
+    };
+
+    let view = view.as_str();
+    let source = source.as_deref();
+    let line = source.and_then(|source| failing_line(error, source));
+
+    let excerpt = match (source, line) {
+        (Some(source), Some(line)) => excerpt(source, line),
+        _ => String::new(),
+    };
+
+    let location = match line {
+        Some(line) => format!("{}, line {}", escape(view), line),
+        None => escape(view),
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <title>View error: {view}</title>
+    <style>
+        body {{ font-family: -apple-system, "Segoe UI", sans-serif; margin: 0; background: #f6f7f9; color: #1f2328; }}
+        header {{ background: #b42318; color: #fff; padding: 24px 32px; }}
+        header h1 {{ margin: 0 0 8px; font-size: 20px; }}
+        main {{ padding: 24px 32px; }}
+        h2 {{ font-size: 15px; margin: 24px 0 8px; }}
+        pre {{ background: #fff; border: 1px solid #d0d7de; border-radius: 6px; padding: 12px; overflow: auto; font-size: 13px; line-height: 1.5; }}
+        .line {{ display: block; }}
+        .line.failing {{ background: #ffebe9; }}
+        .number {{ color: #8c959f; display: inline-block; width: 40px; user-select: none; }}
+    </style>
+</head>
+<body>
+    <header>
+        <h1>Failed to render {location}</h1>
+    </header>
+    <main>
+        <h2>Error</h2>
+        <pre>{error}</pre>
+        {excerpt}
+        <h2>Context</h2>
+        <pre>{context}</pre>
+    </main>
+</body>
+</html>"#,
+        view = escape(view),
+        location = location,
+        error = escape(error),
+        excerpt = excerpt,
+        context = escape(&context),
+    )
+}
+
+/// Tera reports parse errors as ` --> line:column`; render errors only name the variable or
+/// filter, so the first line mentioning it is used.
+fn failing_line(error: &str, source: &str) -> Option<usize> {
+    let position = Regex::new(r"-->\s*(\d+):(\d+)").expect("valid regex");
+
+    if let Some(captures) = position.captures(error) {
+        return captures[1].parse().ok();
+    }
+
+    let quoted = Regex::new(r"`([^`]+)`").expect("valid regex");
+
+    quoted.captures_iter(error).find_map(|captures| {
+        source
+            .lines()
+            .position(|line| line.contains(&captures[1]))
+            .map(|index| index + 1)
+    })
+}
+
+fn excerpt(source: &str, line: usize) -> String {
+    let first = line.saturating_sub(SOURCE_CONTEXT_LINES).max(1);
+    let lines: String = source
+        .lines()
+        .enumerate()
+        .skip(first - 1)
+        .take(line - first + SOURCE_CONTEXT_LINES + 1)
+        .map(|(index, text)| {
+            let number = index + 1;
+            let class = if number == line { "line failing" } else { "line" };
+
+            format!(r#"<span class="{}"><span class="number">{}</span>{}</span>"#, class, number, escape(text))
+        })
+        .collect();
+
+    format!("<h2>Source</h2>\n        <pre>{}</pre>", lines)
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
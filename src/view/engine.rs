@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, mpsc},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context as _, Result, bail};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tera::Tera;
+
+use crate::view::{ViewData, functions::register};
+
+/// Renders named templates. Implement it to use another engine than Tera:
+///
+/// ```ignore
+/// struct Jinja(minijinja::Environment<'static>);
+///
+/// impl TemplateEngine for Jinja {
+///     fn render(&self, template: &str, data: &ViewData) -> Result<String> {
+///         Ok(self.0.get_template(template)?.render(data.to_json())?)
+///     }
+/// }
+/// ```
+pub trait TemplateEngine: Send + Sync {
+    fn render(&self, template: &str, data: &ViewData) -> Result<String>;
+
+    /// The template's source, shown around the failing line on the development error page.
+    fn source(&self, _template: &str) -> Option<String> {
+        None
+    }
+}
+
+/// The default engine: Tera with every file under a directory loaded and Flyer's template
+/// functions (`csrf_token`, `error`, `can`, `t`, ...) registered.
+pub struct TeraEngine {
+    tera: Arc<ArcSwap<Tera>>,
+    directory: Option<PathBuf>,
+    reload_error: Arc<ArcSwapOption<String>>,
+    watcher: Option<RecommendedWatcher>,
+}
+
+impl TeraEngine {
+    pub fn new(directory: impl Into<String>) -> Result<Self> {
+        let directory = directory.into();
+        let glob_path = format!("{}/**/*", directory.trim_end_matches('/'));
+        let tera = Tera::new(&glob_path).with_context(|| format!("Failed to load templates from {}", directory))?;
+
+        let mut engine = Self::from_tera(tera);
+        engine.directory = Some(PathBuf::from(directory));
+
+        Ok(engine)
+    }
+
+    /// Uses an already configured Tera instance, adding Flyer's functions to it.
+    pub fn from_tera(mut tera: Tera) -> Self {
+        register(&mut tera);
+
+        Self {
+            tera: Arc::new(ArcSwap::from_pointee(tera)),
+            directory: None,
+            reload_error: Arc::new(ArcSwapOption::empty()),
+            watcher: None,
+        }
+    }
+
+    /// Reloads all templates whenever a file under the template directory changes. Requests
+    /// keep rendering with the previous templates until the new set is parsed; while a template
+    /// fails to parse, renders fail with that parse error.
+    pub fn watch(&mut self) -> Result<()> {
+        let Some(directory) = &self.directory else {
+            bail!("Only engines created with TeraEngine::new can be watched");
+        };
+
+        let (sender, receiver) = mpsc::channel();
+
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if matches!(&event, Ok(event) if !event.kind.is_access()) {
+                let _ = sender.send(());
+            }
+        })?;
+
+        watcher
+            .watch(directory, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch templates in {}", directory.display()))?;
+
+        let tera = Arc::clone(&self.tera);
+        let reload_error = Arc::clone(&self.reload_error);
+
+        // Ends when the watcher, which owns the sender, is dropped with the engine.
+        thread::spawn(move || {
+            while receiver.recv().is_ok() {
+                // Saving a file fires several events; reload once they settle.
+                while receiver.recv_timeout(Duration::from_millis(50)).is_ok() {}
+
+                let mut fresh = (**tera.load()).clone();
+
+                match fresh.full_reload() {
+                    Ok(()) => {
+                        tera.store(Arc::new(fresh));
+                        reload_error.store(None);
+                    }
+                    Err(err) => {
+                        reload_error.store(Some(Arc::new(format!("{:#}", anyhow::Error::from(err)))));
+                    }
+                }
+            }
+        });
+
+        self.watcher = Some(watcher);
+
+        Ok(())
+    }
+}
+
+impl TemplateEngine for TeraEngine {
+    fn render(&self, template: &str, data: &ViewData) -> Result<String> {
+        if let Some(error) = self.reload_error.load_full() {
+            bail!("{}", error);
+        }
+
+        Ok(self.tera.load().render(template, &data.context)?)
+    }
+
+    fn source(&self, template: &str) -> Option<String> {
+        fs::read_to_string(self.directory.as_ref()?.join(template)).ok()
+    }
+}
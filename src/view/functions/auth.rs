@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use tera::{to_value, Tera, Value};
+
+use crate::auth::{Identity, gate::Gate};
+
+tokio::task_local! {
+    pub(crate) static GLOBAL_CURRENT_IDENTITY: Option<Identity>;
+}
+
+pub(crate) fn register_global_functions(render: &mut Tera) {
+    render.register_function("can", can_fn());
+    render.register_function("has_role", has_role_fn());
+}
+
+fn get_arg<'a>(args: &'a HashMap<String, Value>, key: &str) -> Option<&'a str> {
+    args.get(key).and_then(|v| v.as_str())
+}
+
+/// Checks abilities defined with `Gate::define` only: template values have lost the Rust type an
+/// ability defined for a resource needs, so passing a resource is an error rather than `false`.
+fn can_fn() -> impl Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send + Sync + 'static {
+    |args| {
+        if let Some(extra) = args.keys().find(|key| *key != "ability") {
+            return Err(tera::Error::msg(format!(
+                "can() only checks abilities without a resource, `{}` is not supported; use req.can() in the handler",
+                extra
+            )));
+        }
+
+        let ability = get_arg(args, "ability").unwrap_or_default();
+        GLOBAL_CURRENT_IDENTITY.try_with(|identity| {
+            match identity {
+                Some(identity) => to_value(Gate::allows(identity, ability, &())),
+                None => to_value(false),
+            }
+        })
+        .unwrap_or(to_value(false))
+        .map_err(|err| err.into())
+    }
+}
+
+fn has_role_fn() -> impl Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send + Sync + 'static {
+    |args| {
+        let name = get_arg(args, "name").unwrap_or_default();
+        GLOBAL_CURRENT_IDENTITY.try_with(|identity| {
+            match identity {
+                Some(identity) => to_value(identity.has_role(name)),
+                None => to_value(false),
+            }
+        })
+        .unwrap_or(to_value(false))
+        .map_err(|err| err.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn can(args: &[(&str, &str)]) -> tera::Result<Value> {
+        let args = args.iter().map(|(key, value)| (key.to_string(), to_value(value).unwrap())).collect();
+        let identity = Identity::new("jane", Vec::new());
+
+        GLOBAL_CURRENT_IDENTITY.sync_scope(Some(identity), || can_fn()(&args))
+    }
+
+    #[test]
+    fn can_checks_abilities_without_a_resource() {
+        Gate::define("template-test-settings", |identity| identity.id == "jane");
+
+        assert_eq!(can(&[("ability", "template-test-settings")]).unwrap(), Value::Bool(true));
+        assert_eq!(can(&[("ability", "template-test-missing")]).unwrap(), Value::Bool(false));
+        assert!(can(&[("ability", "template-test-settings"), ("post", "1")]).is_err());
+    }
+}
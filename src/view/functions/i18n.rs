@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use tera::{to_value, Tera, Value};
+
+use crate::i18n::{self, DEFAULT_LOCALE};
+
+tokio::task_local! {
+    pub(crate) static GLOBAL_CURRENT_LOCALE: String;
+}
+
+pub(crate) fn register_global_functions(render: &mut Tera) {
+    render.register_function("t", trans_fn());
+    render.register_function("trans", trans_fn());
+}
+
+/// `t(key="cart.items", count=3, name=user.name)`; every argument other than `key`, `count`
+/// and `locale` fills the placeholder of the same name.
+fn trans_fn() -> impl Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send + Sync + 'static {
+    |args| {
+        let key = args
+            .get("key")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| tera::Error::msg("t() needs a `key` argument"))?;
+
+        let locale = match args.get("locale").and_then(|v| v.as_str()) {
+            Some(locale) => locale.to_string(),
+            None => GLOBAL_CURRENT_LOCALE.try_with(|locale| locale.clone()).unwrap_or_else(|_| DEFAULT_LOCALE.to_string()),
+        };
+
+        let params: Vec<(String, String)> = args
+            .iter()
+            .filter(|(name, _)| !matches!(name.as_str(), "key" | "count" | "locale"))
+            .map(|(name, value)| match value {
+                Value::String(s) => (name.clone(), s.clone()),
+                other => (name.clone(), other.to_string()),
+            })
+            .collect();
+
+        let params: Vec<(&str, &str)> = params.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+
+        let translated = match args.get("count").and_then(|v| v.as_f64()) {
+            Some(count) => i18n::trans_choice(&locale, key, count, &params),
+            None => i18n::trans(&locale, key, &params),
+        };
+
+        to_value(translated).map_err(|err| err.into())
+    }
+}
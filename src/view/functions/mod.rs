@@ -2,11 +2,15 @@ use tera::Tera;
 
 use crate::view::functions;
 
+pub(crate) mod auth;
+pub(crate) mod i18n;
 pub(crate) mod utils;
 pub(crate) mod session;
 
 pub(crate) fn register<'r>(engine: &mut Tera) {
     register_session_functions(engine);
+    register_auth_functions(engine);
+    register_i18n_functions(engine);
     register_utils_functions(engine);
 }
 
@@ -14,6 +18,14 @@ pub(crate) fn register_session_functions(render: &mut Tera) {
     functions::session::register_global_functions(render);
 }
 
+pub(crate) fn register_auth_functions(render: &mut Tera) {
+    functions::auth::register_global_functions(render);
+}
+
+pub(crate) fn register_i18n_functions(render: &mut Tera) {
+    functions::i18n::register_global_functions(render);
+}
+
 pub(crate) fn register_utils_functions<'r>(engine: &mut Tera) {
     functions::utils::register(engine);
 }
\ No newline at end of file
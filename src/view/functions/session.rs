@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use tera::{to_value, Tera, Value};
 
-use crate::session::Session;
+use crate::{session::Session, validation::dotted};
 
 tokio::task_local! {
     pub(crate) static GLOBAL_CURRENT_SESSION: Session;
@@ -51,7 +51,7 @@ fn error_fn() -> impl Fn(&HashMap<String, Value>) -> tera::Result<Value> + Send
     |args| {
         let name = get_arg(args, "name").unwrap_or_default();
         GLOBAL_CURRENT_SESSION.try_with(|s| {
-            match s.errors.get(name) {
+            match s.errors.get(&dotted(name)) {
                 Some(err) => to_value(err),
                 None => to_value(""),
             }
@@ -76,7 +76,7 @@ fn error_has_fn() -> impl Fn(&HashMap<String, Value>) -> tera::Result<Value> + S
         let class = args.get("class");
 
         GLOBAL_CURRENT_SESSION.try_with(|s| {
-            let error = s.errors.get(name);
+            let error = s.errors.get(&dotted(name));
 
             if error.is_none() && class.is_none() {
                 return to_value(false);
@@ -1,22 +1,34 @@
-use std::{collections::HashMap, sync::Arc};
-use anyhow::{Context as _, Result};
+use std::{collections::HashMap, sync::{Arc, LazyLock, RwLock}};
+use anyhow::Result;
 use bytes::Bytes;
 use serde::Serialize;
 use tera::{Context, Tera};
 
 use crate::{
+    error::Error,
     hooks::Hook,
     request::Request,
     response::Response,
     routing::next::Next,
-    view::functions::{register, session::GLOBAL_CURRENT_SESSION}
+    utils::env::is_development,
+    view::functions::{auth::GLOBAL_CURRENT_IDENTITY, i18n::GLOBAL_CURRENT_LOCALE, session::GLOBAL_CURRENT_SESSION}
 };
 
+pub use engine::{TemplateEngine, TeraEngine};
+
+pub(crate) mod debug;
+pub(crate) mod engine;
 pub(crate) mod functions;
 
+pub type Composer = dyn Fn(&Request, &mut ViewData) + Send + Sync;
+
+type Composers = Vec<(String, Arc<Composer>)>;
+
+static GLOBAL_COMPOSERS: LazyLock<RwLock<Composers>> = LazyLock::new(|| RwLock::new(Vec::new()));
+
 #[derive(Clone, Default)]
 pub struct View {
-    engine: Option<Arc<Tera>>,
+    engine: Option<Arc<dyn TemplateEngine>>,
 }
 
 impl Hook for View {
@@ -26,15 +38,28 @@ impl Hook for View {
 
     async fn after(&self, req: Request, mut res: Response, next: Next) -> Response {
         if let Some(engine) = &self.engine {
-            if let Some(mut view) = res.view.take() {
+            if let Some(view) = res.view.take() {
+                let data = compose(&view, &req);
                 let rendered_result = GLOBAL_CURRENT_SESSION
-                    .scope(req.session.clone(), async {
-                        self.render_with_engine(engine, &mut view)
-                    })
+                    .scope(req.session.clone(), GLOBAL_CURRENT_IDENTITY.scope(req.identity.clone(), GLOBAL_CURRENT_LOCALE.scope(req.locale(), async {
+                        engine.render(&view.view, &data)
+                    })))
                     .await;
 
-                if let Ok(rendered) = rendered_result {
-                    res.content = Bytes::from(rendered);
+                match rendered_result {
+                    Ok(rendered) => res.content = Bytes::from(rendered),
+                    Err(err) => {
+                        let message = format!("{:#}", err);
+
+                        if is_development() {
+                            let source = engine.source(&view.view);
+                            let page = debug::render_error_page(&view.view, &message, source, &data);
+
+                            res = res.html(page);
+                        }
+
+                        res.error = Some(Error::new(format!("Failed to render view {}", view.view), message));
+                    }
                 }
             }
         }
@@ -44,26 +69,23 @@ impl Hook for View {
 }
 
 impl View {
+    /// Loads the templates of `directory`, reloading them on change in development.
     pub(crate) fn new(directory: Option<impl Into<String>>) -> Self {
         let engine = directory.map(|dir| {
-            let glob_path = format!("{}/**/*", dir.into().trim_end_matches('/'));
-            let mut tera = Tera::new(&glob_path).expect("Failed to initialize Tera engine");
+            let mut engine = TeraEngine::new(dir).unwrap_or_else(|err| panic!("Failed to initialize Tera engine: {:#}", err));
 
-            register(&mut tera);
+            if is_development() && let Err(err) = engine.watch() {
+                tracing::warn!("Template reloading disabled: {:#}", err);
+            }
 
-            Arc::new(tera)
+            Arc::new(engine) as Arc<dyn TemplateEngine>
         });
 
         Self { engine }
     }
 
-    fn render_with_engine(&self, engine: &Tera, bag: &mut ViewBag) -> Result<String> {
-        let default_data = &mut ViewData::default();
-        let context = &bag.data.as_mut().unwrap_or(default_data).context;
-
-        engine
-            .render(&bag.view, context)
-            .context("Tera render error")
+    pub(crate) fn with_engine(engine: impl TemplateEngine + 'static) -> Self {
+        Self { engine: Some(Arc::new(engine)) }
     }
 
     pub fn render(path: impl Into<String>, template: impl Into<String>, data: Option<ViewData>) -> Result<Bytes> {
@@ -81,6 +103,48 @@ impl View {
     }
 }
 
+/// Adds shared data to every view matching `views`: a template name, a `prefix/*` pattern or
+/// `*` for all of them. Data the handler passes under the same key wins.
+pub fn composer<C>(views: impl Into<String>, composer: C)
+where
+    C: Fn(&Request, &mut ViewData) + Send + Sync + 'static,
+{
+    GLOBAL_COMPOSERS
+        .write()
+        .expect("View composer registry lock poisoned")
+        .push((views.into(), Arc::new(composer)));
+}
+
+/// The data of the view's composers, overridden by the data the handler passed.
+fn compose(bag: &ViewBag, req: &Request) -> ViewData {
+    let composers: Vec<Arc<Composer>> = GLOBAL_COMPOSERS
+        .read()
+        .expect("View composer registry lock poisoned")
+        .iter()
+        .filter(|(pattern, _)| matches(pattern, &bag.view))
+        .map(|(_, composer)| Arc::clone(composer))
+        .collect();
+
+    let mut data = ViewData::new();
+
+    for composer in composers {
+        composer(req, &mut data);
+    }
+
+    if let Some(own) = &bag.data {
+        data.context.extend(own.context.clone());
+    }
+
+    data
+}
+
+fn matches(pattern: &str, view: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => view.starts_with(prefix),
+        None => pattern == view,
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct ViewBag {
     pub(crate) view: String,
@@ -129,4 +193,9 @@ impl ViewData {
         self.context.insert(key, val);
         self
     }
+
+    /// The data as a JSON object, for engines other than Tera.
+    pub fn to_json(&self) -> serde_json::Value {
+        self.context.clone().into_json()
+    }
 }
\ No newline at end of file